@@ -0,0 +1,115 @@
+//! Size and write cost of the compact positions format against CSV. Replays the player's positions of the
+//! capture given by F1_EVENTLOGGER_BENCH_CAPTURE, or a synthetic race of one car circling the track.
+#![allow(dead_code)]
+
+#[path = "../src/capture.rs"]
+mod capture;
+#[path = "../src/positions.rs"]
+mod positions;
+
+use std::{env, path};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use f1_telemetry::packet::{parse_packet, Packet};
+
+use crate::capture::CaptureReader;
+use crate::positions::{CompactPositionsWriter, PositionPoint};
+
+/// Trace interval of the synthetic race, the default of `--player-trace-interval-ms`
+const SYNTHETIC_INTERVAL_MS: u32 = 100;
+const SYNTHETIC_LAPS: u32 = 50;
+const SYNTHETIC_LAP_MS: u32 = 90_000;
+
+fn synthetic_points() -> Vec<PositionPoint> {
+    (0..SYNTHETIC_LAPS * SYNTHETIC_LAP_MS / SYNTHETIC_INTERVAL_MS)
+        .map(|i| {
+            let session_time = i * SYNTHETIC_INTERVAL_MS;
+            let angle = (session_time % SYNTHETIC_LAP_MS) as f32 / SYNTHETIC_LAP_MS as f32 * std::f32::consts::TAU;
+            PositionPoint {
+                session_time,
+                lap: (session_time / SYNTHETIC_LAP_MS + 1) as u8,
+                x: 800.0 * angle.cos(),
+                z: 500.0 * angle.sin(),
+            }
+        })
+        .collect()
+}
+
+fn capture_points(path: &path::Path) -> Vec<PositionPoint> {
+    let mut lap = 0;
+    let mut points = Vec::new();
+
+    for datagram in CaptureReader::open(path).unwrap() {
+        let datagram = datagram.unwrap();
+        match parse_packet(datagram.data.len(), &datagram.data) {
+            Ok(Packet::LapData(lp)) => {
+                if let Some(lap_data) = lp.lap_data.get(lp.header.player_car_index as usize) {
+                    lap = lap_data.current_lap_num;
+                }
+            },
+            Ok(Packet::Motion(mp)) => {
+                if let Some(motion) = mp.car_motion_data.get(mp.header.player_car_index as usize) {
+                    points.push(PositionPoint {
+                        session_time: mp.header.session_time,
+                        lap,
+                        x: motion.world_position.x,
+                        z: motion.world_position.z,
+                    });
+                }
+            },
+            _ => {},
+        }
+    }
+
+    points
+}
+
+fn write_csv(points: &[PositionPoint]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["Sessiontime [ms]", "Lap", "X", "Z"]).unwrap();
+    for point in points {
+        writer
+            .write_record([
+                point.session_time.to_string(),
+                point.lap.to_string(),
+                point.x.to_string(),
+                point.z.to_string(),
+            ])
+            .unwrap();
+    }
+    writer.into_inner().unwrap()
+}
+
+fn write_compact(points: &[PositionPoint]) -> Vec<u8> {
+    let mut writer = CompactPositionsWriter::new(Vec::new()).unwrap();
+    for point in points {
+        writer.push(0, *point).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+fn bench_positions(c: &mut Criterion) {
+    let points = match env::var_os("F1_EVENTLOGGER_BENCH_CAPTURE") {
+        Some(path) => capture_points(path::Path::new(&path)),
+        None => synthetic_points(),
+    };
+
+    let csv_size = write_csv(&points).len();
+    let compact_size = write_compact(&points).len();
+    println!(
+        "{} points: CSV {} bytes, compact {} bytes ({:.1} %)",
+        points.len(),
+        csv_size,
+        compact_size,
+        compact_size as f64 / csv_size as f64 * 100.0
+    );
+
+    let mut group = c.benchmark_group("positions");
+    group.throughput(Throughput::Elements(points.len() as u64));
+    group.bench_function("csv", |b| b.iter(|| write_csv(&points)));
+    group.bench_function("compact", |b| b.iter(|| write_compact(&points)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_positions);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+//! Throughput of the packet handlers for a synthetic race: lap data at 60 Hz, telemetry and bursts of
+//! overtakes, which is the load a full grid produces with the game's highest send rate.
+#![allow(dead_code)]
+
+include!("../tests/common/session.rs");
+
+use std::{env, fs, path};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use f1_telemetry::packet::car_status::CarStatusData;
+use f1_telemetry::packet::car_telemetry::CarTelemetryData;
+use f1_telemetry::packet::event::{Event, Overtake, PacketEventData};
+use f1_telemetry::packet::header::PacketHeader;
+use f1_telemetry::packet::lap::{LapData, PacketLapData};
+use f1_telemetry::packet::participants::ParticipantData;
+use f1_telemetry::packet::session::{PacketSessionData, RuleSet};
+use f1_telemetry::packet::Packet;
+
+use crate::session::SessionState;
+
+const NUM_CARS: u8 = 22;
+const FRAMES_PER_SECOND: u32 = 60;
+/// Simulated race time per benchmark iteration
+const FRAMES_PER_ITERATION: u32 = FRAMES_PER_SECOND * 10;
+/// One overtake every half second, far above a real race to stress the event path
+const FRAMES_PER_OVERTAKE: u32 = FRAMES_PER_SECOND / 2;
+
+fn header(session_time: u32) -> PacketHeader {
+    PacketHeader { session_uid: 1, session_time, player_car_index: 0, ..Default::default() }
+}
+
+fn new_session_state(output_dir: &path::Path) -> SessionState {
+    let options = session_options(output_dir);
+
+    let mut state = SessionState::new(options);
+    state
+        .update_session(PacketSessionData {
+            header: header(0),
+            rule_set: Some(RuleSet::Race),
+            track_length: 5000,
+            ..Default::default()
+        })
+        .unwrap();
+    state.cars = (0..NUM_CARS)
+        .map(|i| ParticipantData { name: format!("Driver {}", i), race_number: i, ..Default::default() })
+        .collect();
+    state.car_status = (0..NUM_CARS).map(|_| CarStatusData::default()).collect();
+
+    state
+}
+
+fn lap_packet(frame: u32) -> PacketLapData {
+    let session_time = frame * 1000 / FRAMES_PER_SECOND;
+    let lap_data = (0..NUM_CARS)
+        .map(|i| LapData {
+            car_position: i + 1,
+            current_lap_num: (session_time / 90_000 + 1) as u8,
+            lap_distance: (session_time % 90_000) as f32 / 18.0,
+            ..Default::default()
+        })
+        .collect();
+
+    PacketLapData { header: header(session_time), lap_data, ..Default::default() }
+}
+
+fn telemetry(frame: u32) -> Vec<CarTelemetryData> {
+    (0..NUM_CARS)
+        .map(|i| CarTelemetryData { speed: 250 + (frame % 50) as u16 + i as u16, ..Default::default() })
+        .collect()
+}
+
+fn overtake(frame: u32) -> PacketEventData {
+    let overtaker = (frame / FRAMES_PER_OVERTAKE % NUM_CARS as u32) as u8;
+    PacketEventData {
+        header: header(frame * 1000 / FRAMES_PER_SECOND),
+        event: Event::Overtake(Overtake {
+            overtaking_vehicle_idx: overtaker,
+            being_overtaken_vehicle_idx: (overtaker + 1) % NUM_CARS,
+        }),
+    }
+}
+
+fn bench_race(c: &mut Criterion) {
+    let output_dir = env::temp_dir().join("f1-eventlogger-bench");
+    fs::create_dir_all(&output_dir).unwrap();
+
+    let mut group = c.benchmark_group("session");
+    group.throughput(Throughput::Elements(FRAMES_PER_ITERATION as u64));
+    group.bench_function("race_frames", |b| {
+        let mut state = new_session_state(&output_dir);
+        let mut frame = 0;
+
+        b.iter(|| {
+            for _ in 0..FRAMES_PER_ITERATION {
+                frame += 1;
+                state.update_car_speeds(&telemetry(frame));
+                let packet = Packet::LapData(lap_packet(frame));
+                state.observe(&packet);
+                if let Packet::LapData(lap_packet) = packet {
+                    state.update_lap_data(lap_packet).unwrap();
+                }
+                if frame % FRAMES_PER_OVERTAKE == 0 {
+                    state.handle_event(&overtake(frame)).unwrap();
+                }
+            }
+        });
+        state.close_session().unwrap();
+    });
+    group.finish();
+
+    fs::remove_dir_all(&output_dir).ok();
+}
+
+criterion_group!(benches, bench_race);
+criterion_main!(benches);
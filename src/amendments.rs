@@ -0,0 +1,35 @@
+/// A car whose position changed between two final classifications of the same session, e.g. when the game
+/// sends the classification again after applying post-race penalties
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Amendment {
+    pub(crate) car_idx: usize,
+    pub(crate) old_position: u8,
+    pub(crate) new_position: u8,
+    pub(crate) penalty_secs_added: u8,
+}
+
+impl Amendment {
+    /// Positions gained by the amendment, negative if the car was moved back
+    pub(crate) fn position_change(&self) -> i16 {
+        self.old_position as i16 - self.new_position as i16
+    }
+}
+
+/// Compares two classifications given as position and penalty seconds by car index, returning the cars whose
+/// position changed ordered by their new position
+pub(crate) fn amendments(provisional: &[(u8, u8)], last: &[(u8, u8)]) -> Vec<Amendment> {
+    let mut amendments: Vec<Amendment> = provisional
+        .iter()
+        .zip(last)
+        .enumerate()
+        .filter(|(_, (old, new))| old.0 != new.0)
+        .map(|(car_idx, (old, new))| Amendment {
+            car_idx,
+            old_position: old.0,
+            new_position: new.0,
+            penalty_secs_added: new.1.saturating_sub(old.1),
+        })
+        .collect();
+    amendments.sort_by_key(|amendment| amendment.new_position);
+    amendments
+}
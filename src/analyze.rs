@@ -0,0 +1,59 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path;
+
+use f1_telemetry::packet::{parse_packet, Packet};
+
+use crate::capture::CaptureReader;
+use crate::pipeline::packet_header;
+use crate::Result;
+
+fn packet_name(packet: &Packet) -> &'static str {
+    match packet {
+        Packet::Motion(_) => "Motion",
+        Packet::Session(_) => "Session",
+        Packet::LapData(_) => "LapData",
+        Packet::Event(_) => "Event",
+        Packet::Participants(_) => "Participants",
+        Packet::CarTelemetry(_) => "CarTelemetry",
+        Packet::CarStatus(_) => "CarStatus",
+        Packet::FinalClassification(_) => "FinalClassification",
+        _ => "Other",
+    }
+}
+
+/// Prints the packet counts, sessions and duration of a capture without writing any output files
+pub(crate) fn analyze_capture(path: &path::Path) -> Result<()> {
+    let mut packet_counts = BTreeMap::new();
+    let mut session_uids = BTreeSet::new();
+    let mut parse_errors = 0;
+    let mut duration_us = 0;
+
+    for datagram in CaptureReader::open(path)? {
+        let datagram = datagram?;
+        duration_us = datagram.received_us;
+
+        match parse_packet(datagram.data.len(), &datagram.data) {
+            Ok(packet) => {
+                if let Some(header) = packet_header(&packet) {
+                    session_uids.insert(header.session_uid);
+                }
+                *packet_counts.entry(packet_name(&packet)).or_insert(0u64) += 1;
+            },
+            Err(_) => parse_errors += 1,
+        }
+    }
+
+    println!("Capture: {}", path.display());
+    println!("Duration: {:.1} s", duration_us as f64 / 1_000_000.0);
+    println!("Sessions: {}", session_uids.len());
+    for uid in &session_uids {
+        println!("  {}", uid);
+    }
+    println!("Packets:");
+    for (name, count) in &packet_counts {
+        println!("  {:<20} {}", name, count);
+    }
+    println!("Unparseable datagrams: {}", parse_errors);
+
+    Ok(())
+}
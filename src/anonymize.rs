@@ -0,0 +1,68 @@
+use f1_telemetry::packet::participants::ParticipantData;
+
+/// Replaces the participants' names with pseudonyms like "Driver 01" for publishing the outputs. Numbers are
+/// given in the order of the vehicle indexes as drivers first appear, so a replay of the same session gets
+/// the same pseudonyms. A driver keeps the pseudonym when changing cars, and across a linked weekend.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Anonymizer {
+    /// Real names in the order of their pseudonyms
+    names: Vec<String>,
+    /// Pseudonym by car index, as index into `names`
+    by_car: Vec<Option<usize>>,
+}
+
+impl Anonymizer {
+    /// Continues the pseudonyms of an earlier session, given as the real names in the order of their pseudonyms
+    pub(crate) fn continuing(names: Vec<String>) -> Self {
+        Self { names, by_car: Vec::new() }
+    }
+
+    pub(crate) fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    fn pseudonym(number: usize) -> String {
+        format!("Driver {:02}", number + 1)
+    }
+
+    /// Replaces the names of the participants, returning whether new pseudonyms were assigned. Empty names,
+    /// i.e. unused slots, are kept.
+    pub(crate) fn anonymize(&mut self, cars: &mut [ParticipantData]) -> bool {
+        if self.by_car.len() < cars.len() {
+            self.by_car.resize(cars.len(), None);
+        }
+
+        let mut assigned = false;
+        for (i, car) in cars.iter_mut().enumerate() {
+            if car.name.is_empty() {
+                continue;
+            }
+
+            let number = match self.by_car[i] {
+                Some(number) if self.names[number] == car.name => number,
+                _ => {
+                    // Two drivers may share a name, e.g. "Player" for players hiding their online names
+                    let taken = |number: usize| {
+                        self.by_car.iter().enumerate().any(|(other, &assigned)| other != i && assigned == Some(number))
+                    };
+                    match (0..self.names.len()).find(|&number| self.names[number] == car.name && !taken(number)) {
+                        Some(number) => number,
+                        None => {
+                            self.names.push(car.name.clone());
+                            assigned = true;
+                            self.names.len() - 1
+                        },
+                    }
+                },
+            };
+            self.by_car[i] = Some(number);
+            car.name = Self::pseudonym(number);
+        }
+        assigned
+    }
+
+    /// Pseudonym and real name of every driver so far
+    pub(crate) fn mapping(&self) -> impl Iterator<Item = (String, &str)> {
+        self.names.iter().enumerate().map(|(number, name)| (Self::pseudonym(number), name.as_str()))
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::{fs, path, time};
+
+use f1_telemetry::packet::header::PacketHeader;
+use f1_telemetry::packet::Packet;
+use serde::{Deserialize, Serialize};
+
+/// Identifies capture files and their format version
+const CAPTURE_MAGIC: &[u8; 8] = b"F1EVCAP1";
+
+/// A raw datagram as received from the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CapturedDatagram {
+    /// Time since the start of the capture in microseconds
+    pub(crate) received_us: u64,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Byte offset of the first datagram of a lap, by the lap the leader is on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IndexedLap {
+    pub(crate) lap: u8,
+    pub(crate) offset: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IndexedSession {
+    pub(crate) session_uid: u64,
+    pub(crate) offset: u64,
+    pub(crate) laps: Vec<IndexedLap>,
+}
+
+/// Byte offsets of the sessions and laps in a capture, written next to it for starting a replay at a
+/// specific session or lap without reading everything before it
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct CaptureIndex {
+    pub(crate) sessions: Vec<IndexedSession>,
+}
+
+impl CaptureIndex {
+    /// Reads the index sidecar of the capture. `None` if it is missing or older than the capture, as when the
+    /// recording crashed before writing the index, so the index has to be rebuilt from the capture.
+    pub(crate) fn read_current(capture: &path::Path) -> io::Result<Option<Self>> {
+        let path = index_path(capture);
+        let modified = |path: &path::Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+        match (modified(&path), modified(capture)?) {
+            (Ok(index_modified), capture_modified) if index_modified >= capture_modified => {
+                Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    pub(crate) fn write(&self, capture: &path::Path) -> io::Result<()> {
+        fs::write(index_path(capture), serde_json::to_string_pretty(self)?)
+    }
+
+    /// Adds the packet's session or lap if it is the first datagram of it
+    pub(crate) fn record(&mut self, offset: u64, header: &PacketHeader, packet: &Packet) {
+        // Packets sent in the menus have no session
+        if header.session_uid == 0 {
+            return;
+        }
+
+        if self.sessions.last().is_none_or(|session| session.session_uid != header.session_uid) {
+            self.sessions.push(IndexedSession { session_uid: header.session_uid, offset, laps: Vec::new() });
+        }
+
+        if let Packet::LapData(lp) = packet {
+            let Some(lap) = lp.lap_data.iter().map(|lap| lap.current_lap_num).max() else {
+                return;
+            };
+            let Some(session) = self.sessions.last_mut() else {
+                return;
+            };
+            if session.laps.last().is_none_or(|last| lap > last.lap) {
+                session.laps.push(IndexedLap { lap, offset });
+            }
+        }
+    }
+
+    /// Offset of the session, or of the given lap of it
+    pub(crate) fn offset(&self, session_uid: u64, lap: Option<u8>) -> Option<u64> {
+        let session = self.sessions.iter().find(|session| session.session_uid == session_uid)?;
+        match lap {
+            Some(lap) => session.laps.iter().find(|indexed| indexed.lap == lap).map(|indexed| indexed.offset),
+            None => Some(session.offset),
+        }
+    }
+}
+
+/// Index sidecar of a capture, e.g. "race.f1cap.index.json" for "race.f1cap"
+pub(crate) fn index_path(capture: &path::Path) -> path::PathBuf {
+    let mut path = capture.as_os_str().to_owned();
+    path.push(".index.json");
+    path::PathBuf::from(path)
+}
+
+/// Records raw datagrams, each prefixed with its receive time and length, for replaying a session later
+pub(crate) struct CaptureWriter {
+    writer: BufWriter<fs::File>,
+    started: time::Instant,
+    path: path::PathBuf,
+    /// Offset of the next datagram
+    offset: u64,
+    index: CaptureIndex,
+}
+
+impl CaptureWriter {
+    pub(crate) fn create(path: &path::Path) -> io::Result<Self> {
+        // The index of a previous capture of the same name would be taken for this one's after a crash
+        match fs::remove_file(index_path(path)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {},
+        }
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writer.write_all(CAPTURE_MAGIC)?;
+
+        Ok(Self {
+            writer,
+            started: time::Instant::now(),
+            path: path.to_path_buf(),
+            offset: CAPTURE_MAGIC.len() as u64,
+            index: CaptureIndex::default(),
+        })
+    }
+
+    /// Appends the datagram and returns its offset in the capture
+    pub(crate) fn write(&mut self, data: &[u8]) -> io::Result<u64> {
+        let received_us = self.started.elapsed().as_micros() as u64;
+
+        self.writer.write_all(&received_us.to_le_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.writer.write_all(data)?;
+
+        let offset = self.offset;
+        self.offset += 12 + data.len() as u64;
+        Ok(offset)
+    }
+
+    /// Adds the parsed packet of the datagram written at `offset` to the index
+    pub(crate) fn index(&mut self, offset: u64, header: &PacketHeader, packet: &Packet) {
+        self.index.record(offset, header, packet);
+    }
+
+    /// Flushes the capture and writes its index
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.index.write(&self.path)
+    }
+}
+
+/// Reads the datagrams of a capture file in the order they were received
+pub(crate) struct CaptureReader {
+    reader: BufReader<fs::File>,
+}
+
+impl CaptureReader {
+    pub(crate) fn open(path: &path::Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+
+        let mut magic = [0; CAPTURE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a capture file"));
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Offset of the next datagram
+    pub(crate) fn position(&mut self) -> io::Result<u64> {
+        self.reader.stream_position()
+    }
+
+    /// Continues reading at the datagram at the given offset, e.g. from the capture's index
+    pub(crate) fn seek(&mut self, offset: u64) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        Ok(())
+    }
+
+    fn read_datagram(&mut self) -> io::Result<Option<CapturedDatagram>> {
+        let mut received_us = [0; 8];
+        match self.reader.read_exact(&mut received_us) {
+            Ok(()) => {},
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut data = vec![0; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut data)?;
+
+        Ok(Some(CapturedDatagram { received_us: u64::from_le_bytes(received_us), data }))
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = io::Result<CapturedDatagram>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_datagram().transpose()
+    }
+}
@@ -0,0 +1,64 @@
+use std::str::FromStr;
+
+/// Output column with a key that stays the same when the header text changes, for selecting columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Column {
+    pub(crate) key: &'static str,
+    pub(crate) header: &'static str,
+}
+
+/// Column keys selected on the command line, separated by commas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ColumnSelection(Vec<String>);
+
+impl FromStr for ColumnSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let keys: Vec<String> =
+            s.split(',').map(|key| key.trim().to_lowercase()).filter(|key| !key.is_empty()).collect();
+        if keys.is_empty() {
+            return Err("Expected comma separated column keys".to_string());
+        }
+        Ok(Self(keys))
+    }
+}
+
+impl ColumnSelection {
+    /// Checks that every selected key names one of the columns
+    pub(crate) fn validate(&self, columns: &[Column]) -> Result<(), String> {
+        match self.0.iter().find(|key| !columns.iter().any(|column| column.key == key.as_str())) {
+            Some(key) => {
+                let keys: Vec<&str> = columns.iter().map(|column| column.key).collect();
+                Err(format!("Unknown column '{}', expected one of {}", key, keys.join(", ")))
+            },
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.0.iter().any(|other| other == key)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.iter().map(String::as_str).collect()
+    }
+}
+
+/// Indexes of the columns with the given headers, in the order of the headers, for outputs whose columns are
+/// fixed and registered by their headers
+pub(crate) fn columns_by_header(columns: &[Column], headers: &[&str]) -> Vec<usize> {
+    headers.iter().filter_map(|&header| columns.iter().position(|column| column.header == header)).collect()
+}
+
+/// Indexes of the columns to write: the first `required` columns, then the selected ones or else the
+/// profile's, in the order of `columns` so the files of all session types line up
+pub(crate) fn select_columns(
+    columns: &[Column],
+    required: usize,
+    profile: &[&str],
+    selection: Option<&ColumnSelection>,
+) -> Vec<usize> {
+    let keys = selection.map_or_else(|| profile.to_vec(), ColumnSelection::keys);
+    (0..columns.len()).filter(|&i| i < required || keys.contains(&columns[i].key)).collect()
+}
@@ -0,0 +1,77 @@
+use f1_telemetry::packet::car_status::{CarStatusData, ErsDeployMode, FuelMix};
+
+/// Changes faster than this are treated as scrolling through the modes and coalesced to the final value
+const COALESCE_MS: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ControlModes {
+    pub(crate) fuel_mix: FuelMix,
+    pub(crate) ers_deploy_mode: ErsDeployMode,
+}
+
+impl ControlModes {
+    pub(crate) fn from_status(status: &CarStatusData) -> Self {
+        Self { fuel_mix: status.fuel_mix, ers_deploy_mode: status.ers_deploy_mode }
+    }
+}
+
+/// A settled change of a car's control modes, with the lap context of when the change started
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ControlChange {
+    pub(crate) car_idx: usize,
+    pub(crate) modes: ControlModes,
+    pub(crate) lap: u8,
+    pub(crate) lap_distance: f32,
+    pub(crate) session_time: u32,
+}
+
+/// Follows fuel mix and ERS deployment modes per car, reporting changes once they settled
+#[derive(Debug, Default)]
+pub(crate) struct ControlTracker {
+    logged: Vec<Option<ControlModes>>,
+    pending: Vec<Option<ControlChange>>,
+}
+
+impl ControlTracker {
+    pub(crate) fn clear(&mut self) {
+        self.logged.clear();
+        self.pending.clear();
+    }
+
+    /// Returns the change to log, if any. The first observation of a car is reported immediately, later
+    /// changes only once they held for [`COALESCE_MS`].
+    pub(crate) fn update(&mut self, change: ControlChange) -> Option<ControlChange> {
+        let i = change.car_idx;
+        if self.logged.len() <= i {
+            self.logged.resize(i + 1, None);
+            self.pending.resize(i + 1, None);
+        }
+
+        if self.logged[i].is_none() {
+            self.logged[i] = Some(change.modes.clone());
+            return Some(change);
+        }
+
+        if self.logged[i].as_ref() == Some(&change.modes) {
+            // Scrolled back to the logged modes before the change settled
+            self.pending[i] = None;
+            return None;
+        }
+
+        match &self.pending[i] {
+            Some(pending) if pending.modes == change.modes => {
+                if change.session_time.saturating_sub(pending.session_time) < COALESCE_MS {
+                    return None;
+                }
+
+                let settled = self.pending[i].take()?;
+                self.logged[i] = Some(settled.modes.clone());
+                Some(settled)
+            },
+            _ => {
+                self.pending[i] = Some(change);
+                None
+            },
+        }
+    }
+}
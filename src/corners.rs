@@ -0,0 +1,117 @@
+use std::path;
+
+use crate::locations::TrackRanges;
+
+/// Samples needed within a corner for its minimum speed to be logged
+const MIN_CORNER_SAMPLES: u32 = 3;
+
+/// Corners by track, read from a CSV with the columns "Track", "Corner", "Start [m]" and "End [m]". Tracks are
+/// named like the {track} placeholder of the filename template, e.g. "Melbourne". Corners crossing the finish
+/// line end at a lower distance than they start.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Corners {
+    corners: TrackRanges,
+}
+
+impl Corners {
+    pub(crate) fn from_file(path: &path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self { corners: TrackRanges::from_file(path, "corner", true)? })
+    }
+
+    pub(crate) fn has_track(&self, track: &str) -> bool {
+        self.corners.has_track(track)
+    }
+}
+
+/// Minimum speed of one pass through a corner
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CornerSpeed {
+    pub(crate) lap: u8,
+    pub(crate) corner: String,
+    /// `None` with fewer than [`MIN_CORNER_SAMPLES`] samples, e.g. at low telemetry rates or after a flashback
+    pub(crate) min_speed: Option<u16>,
+    pub(crate) lap_distance: Option<f32>,
+    pub(crate) samples: u32,
+    /// Whether the speed fell towards the minimum and rose again after it, rather than being lowest at the
+    /// corner's entry or exit, e.g. where the braking zone or the acceleration extends past the markers
+    pub(crate) clear: bool,
+}
+
+#[derive(Debug, Clone)]
+struct CornerPass {
+    corner: usize,
+    lap: u8,
+    samples: u32,
+    min_speed: u16,
+    min_distance: f32,
+    /// Whether the minimum was the first sample, or the last one so far
+    min_at_entry: bool,
+    min_at_last: bool,
+}
+
+/// Follows the player through the corners of the track on every telemetry packet. Only the lowest speed of
+/// each pass is kept, so the rows are one per corner and lap whatever the telemetry rate.
+#[derive(Debug, Default)]
+pub(crate) struct CornerSpeedTracker {
+    pass: Option<CornerPass>,
+}
+
+impl CornerSpeedTracker {
+    pub(crate) fn clear(&mut self) {
+        self.pass = None;
+    }
+
+    /// Records a speed sample, returning the pass completed by leaving a corner
+    pub(crate) fn record(
+        &mut self,
+        corners: &Corners,
+        track: &str,
+        lap: u8,
+        lap_distance: f32,
+        speed: u16,
+    ) -> Option<CornerSpeed> {
+        let corner = corners.corners.find(track, lap_distance);
+        let continues = self.pass.as_ref().is_some_and(|pass| Some(pass.corner) == corner && pass.lap == lap);
+
+        if continues {
+            let pass = self.pass.as_mut()?;
+            pass.samples += 1;
+            pass.min_at_last = speed <= pass.min_speed;
+            if speed < pass.min_speed {
+                pass.min_speed = speed;
+                pass.min_distance = lap_distance;
+                pass.min_at_entry = false;
+            }
+            return None;
+        }
+
+        let completed = self.pass.take().map(|pass| Self::finish(corners, pass));
+        self.pass = corner.map(|corner| CornerPass {
+            corner,
+            lap,
+            samples: 1,
+            min_speed: speed,
+            min_distance: lap_distance,
+            min_at_entry: true,
+            min_at_last: true,
+        });
+        completed
+    }
+
+    /// Ends the pass under way, e.g. when the session ends in a corner
+    pub(crate) fn take_pass(&mut self, corners: &Corners) -> Option<CornerSpeed> {
+        self.pass.take().map(|pass| Self::finish(corners, pass))
+    }
+
+    fn finish(corners: &Corners, pass: CornerPass) -> CornerSpeed {
+        let sampled = pass.samples >= MIN_CORNER_SAMPLES;
+        CornerSpeed {
+            lap: pass.lap,
+            corner: corners.corners.get(pass.corner).map(|corner| corner.name.clone()).unwrap_or_default(),
+            min_speed: sampled.then_some(pass.min_speed),
+            lap_distance: sampled.then_some(pass.min_distance),
+            samples: pass.samples,
+            clear: sampled && !pass.min_at_entry && !pass.min_at_last,
+        }
+    }
+}
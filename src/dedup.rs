@@ -0,0 +1,59 @@
+use std::collections::VecDeque;
+
+use f1_telemetry::packet::event::PacketEventData;
+
+use crate::rawevents;
+
+/// Number of recent events remembered. Duplicated datagrams arrive right after the original, so a small
+/// window suffices and doesn't suppress the same event code repeating in later frames.
+const DEDUP_WINDOW: usize = 256;
+
+/// Tells events apart: a duplicated datagram repeats the code, the header's session time and frame
+/// identifier, while distinct events of the same code in one frame differ in the cars involved
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EventKey {
+    code: String,
+    session_time: u32,
+    frame_identifier: u32,
+    vehicles: [Option<u8>; 2],
+}
+
+/// Drops event packets that arrive more than once, as happens on some Wi-Fi bridges and UDP forwarders
+#[derive(Debug, Default)]
+pub(crate) struct EventDeduplicator {
+    recent: VecDeque<EventKey>,
+    hits: u64,
+}
+
+impl EventDeduplicator {
+    /// Returns `true` if the event was already seen within the window. The code read from the event's
+    /// datagram also covers the events the telemetry crate parses without dedicated handling, without it
+    /// only those with dedicated handling are deduplicated.
+    pub(crate) fn is_duplicate(&mut self, event: &PacketEventData, raw_code: Option<&str>) -> bool {
+        let Some(code) = raw_code.or_else(|| rawevents::event_code(&event.event)) else {
+            return false;
+        };
+        let key = EventKey {
+            code: code.to_string(),
+            session_time: event.header.session_time,
+            frame_identifier: event.header.frame_identifier,
+            vehicles: rawevents::event_vehicles(&event.event),
+        };
+
+        if self.recent.contains(&key) {
+            self.hits += 1;
+            println!(
+                "Dropped duplicated event {} (frame {}), {} duplicates so far",
+                key.code, key.frame_identifier, self.hits
+            );
+            return true;
+        }
+
+        if self.recent.len() == DEDUP_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(key);
+
+        false
+    }
+}
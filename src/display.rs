@@ -0,0 +1,146 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use f1_telemetry::packet::lap::LapData;
+
+use crate::laps;
+
+/// Whether the progress line is on the console, for other output to clear it first
+static PROGRESS_SHOWN: AtomicBool = AtomicBool::new(false);
+
+const PURPLE: &str = "\x1b[35m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Console layout for time trial: the player's sector and lap times with their deltas, coloured like the
+/// timing screens: purple for a session best, green for a personal best, yellow otherwise.
+///
+/// The personal best covers the player's laps of this session, the telemetry crate doesn't decode the
+/// time trial packet with the all-time personal best and rival times.
+#[derive(Debug, Default)]
+pub(crate) struct TimeTrialDisplay {
+    /// Sectors 1 to 3 and the lap
+    personal_best: [Option<u32>; 4],
+    session_best: [Option<u32>; 4],
+}
+
+impl TimeTrialDisplay {
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Prints the sector or lap the player, or the spectated car, just completed. Session bests are tracked
+    /// over all cars.
+    pub(crate) fn update(
+        &mut self,
+        previous: &[LapData],
+        current: &[LapData],
+        player_idx: Option<u8>,
+        spectating: bool,
+    ) {
+        for (i, (previous, current)) in previous.iter().zip(current).enumerate() {
+            let is_player = player_idx == Some(i as u8);
+
+            for (sector, time) in completed_times(previous, current) {
+                let line = self.record(sector, time, is_player);
+                if is_player && spectating {
+                    println!("[Spectating] {}", line);
+                } else if is_player {
+                    println!("{}", line);
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, sector: usize, time_ms: u32, is_player: bool) -> String {
+        let session_best = self.session_best[sector];
+        let personal_best = self.personal_best[sector];
+
+        let colour = if session_best.is_none_or(|best| time_ms < best) {
+            PURPLE
+        } else if personal_best.is_none_or(|best| time_ms < best) {
+            GREEN
+        } else {
+            YELLOW
+        };
+
+        let name = match sector {
+            3 => "Lap".to_string(),
+            _ => format!("S{}", sector + 1),
+        };
+        let line = format!(
+            "{}{:<4} {:>9}{}  session best {}  personal best {}",
+            colour,
+            name,
+            format_time(time_ms),
+            RESET,
+            format_delta(time_ms, session_best),
+            format_delta(time_ms, personal_best),
+        );
+
+        self.session_best[sector] = Some(session_best.map_or(time_ms, |best| best.min(time_ms)));
+        if is_player {
+            self.personal_best[sector] = Some(personal_best.map_or(time_ms, |best| best.min(time_ms)));
+        }
+        line
+    }
+}
+
+/// Sectors (0 to 2) and laps (3) completed between both lap data, with their times
+fn completed_times(previous: &LapData, current: &LapData) -> Vec<(usize, u32)> {
+    let mut times = Vec::new();
+
+    if current.current_lap_num > previous.current_lap_num {
+        if let Some(sectors) = laps::sector_times(previous, current.last_lap_time) {
+            times.push((2, sectors[2]));
+        }
+        if current.last_lap_time > 0 {
+            times.push((3, current.last_lap_time));
+        }
+    } else {
+        if previous.sector_1_time == 0 && current.sector_1_time > 0 {
+            times.push((0, current.sector_1_time as u32));
+        }
+        if previous.sector_2_time == 0 && current.sector_2_time > 0 {
+            times.push((1, current.sector_2_time as u32));
+        }
+    }
+
+    times
+}
+
+pub(crate) fn format_time(ms: u32) -> String {
+    match ms / 60_000 {
+        0 => format!("{}.{:03}", ms / 1000, ms % 1000),
+        minutes => format!("{}:{:02}.{:03}", minutes, ms / 1000 % 60, ms % 1000),
+    }
+}
+
+fn format_delta(time_ms: u32, best: Option<u32>) -> String {
+    match best {
+        Some(best) => format!("{:+.3}", (time_ms as f64 - best as f64) / 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+/// Prints the progress line over the previous one, cleared to the end as the previous line may have been
+/// longer. The cursor stays at the end of the line, so other output must clear it first, see [`clear_progress`].
+pub(crate) fn print_progress(line: &str) {
+    print!("\r\x1b[K{}", line);
+    let _ = io::stdout().flush();
+    PROGRESS_SHOWN.store(true, Ordering::Relaxed);
+}
+
+/// Clears the progress line if shown, so the following output starts on an empty line. The next progress
+/// update draws it again.
+pub(crate) fn clear_progress() {
+    if PROGRESS_SHOWN.swap(false, Ordering::Relaxed) {
+        print!("\r\x1b[K");
+    }
+}
+
+/// Whether the progress line is on the console, i.e. wasn't cleared by other output since it was printed
+pub(crate) fn progress_shown() -> bool {
+    PROGRESS_SHOWN.load(Ordering::Relaxed)
+}
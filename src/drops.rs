@@ -0,0 +1,53 @@
+use f1_telemetry::packet::header::PacketHeader;
+
+/// Packet ids sent once per frame at the configured send rate: motion, lap data, car telemetry and car status
+const PER_FRAME_PACKET_IDS: [u8; 4] = [0, 2, 6, 7];
+
+#[derive(Debug, Default, Clone, Copy)]
+struct PacketStream {
+    last_frame: Option<u32>,
+    /// Smallest frame step seen, i.e. the send rate in frames
+    frame_step: Option<u32>,
+}
+
+/// Estimates lost datagrams from gaps in the frame identifiers of the per-frame packets
+#[derive(Debug, Default)]
+pub(crate) struct DropTracker {
+    streams: [PacketStream; PER_FRAME_PACKET_IDS.len()],
+    received: u64,
+    missed: u64,
+}
+
+impl DropTracker {
+    pub(crate) fn record(&mut self, header: &PacketHeader) {
+        let Some(i) = PER_FRAME_PACKET_IDS.iter().position(|&id| id == header.packet_id) else {
+            return;
+        };
+
+        self.received += 1;
+        let stream = &mut self.streams[i];
+        let frame = header.frame_identifier;
+
+        if let Some(last) = stream.last_frame {
+            // Frame identifiers restart with every session and flashback
+            if frame > last {
+                let step = frame - last;
+                let frame_step = *stream.frame_step.get_or_insert(step);
+                if step < frame_step {
+                    stream.frame_step = Some(step);
+                } else {
+                    self.missed += (step / frame_step - 1) as u64;
+                }
+            }
+        }
+        stream.last_frame = Some(frame);
+    }
+
+    /// Console summary of the received and missed per-frame packets
+    pub(crate) fn report(&self) -> String {
+        let expected = self.received + self.missed;
+        let percentage = if expected == 0 { 0.0 } else { self.missed as f64 * 100.0 / expected as f64 };
+
+        format!("Received {} per-frame packets, missed {} ({:.2}%)", self.received, self.missed, percentage)
+    }
+}
@@ -0,0 +1,24 @@
+use std::path;
+
+use crate::locations::TrackRanges;
+
+/// Gap to the car ahead at the detection point within which DRS is enabled
+pub(crate) const DRS_RANGE_MS: u32 = 1000;
+
+/// DRS activation zones by track, read from a CSV with the columns "Track", "Start [m]" and "End [m]".
+/// Tracks are named like the {track} placeholder of the filename template, e.g. "Melbourne".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct DrsZones {
+    zones: TrackRanges,
+}
+
+impl DrsZones {
+    pub(crate) fn from_file(path: &path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self { zones: TrackRanges::from_file(path, "DRS zone", false)? })
+    }
+
+    /// Whether the lap distance lies within a DRS zone of the track, `None` if the track's zones are unknown
+    pub(crate) fn contains(&self, track: &str, lap_distance: f32) -> Option<bool> {
+        self.zones.has_track(track).then(|| self.zones.find(track, lap_distance).is_some())
+    }
+}
@@ -0,0 +1,103 @@
+use f1_telemetry::packet::car_status::CarStatusData;
+
+/// Wear in percent from which a component is expected to fail
+const EXTREME_WEAR_PERCENT: u8 = 90;
+
+/// Components whose faults or wear the car status reports, in the order a retirement is attributed to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Component {
+    Engine,
+    Gearbox,
+    Ers,
+    Drs,
+}
+
+impl Component {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Component::Engine => "Engine",
+            Component::Gearbox => "Gearbox",
+            Component::Ers => "ERS",
+            Component::Drs => "DRS",
+        }
+    }
+
+    /// Whether a failure of the component ends the race. A car carries on without DRS.
+    pub(crate) fn can_retire(&self) -> bool {
+        !matches!(self, Component::Drs)
+    }
+}
+
+/// The failing component a retirement is attributed to, if any
+pub(crate) fn retirement_cause(status: &CarStatusData) -> Option<Component> {
+    failing_components(status).into_iter().find(Component::can_retire)
+}
+
+/// Components with a fault flag set or extreme wear. The fields are only sent by newer packet formats, older
+/// formats report no faults.
+pub(crate) fn failing_components(status: &CarStatusData) -> Vec<Component> {
+    let worn = |wear: Option<u8>| wear.is_some_and(|wear| wear >= EXTREME_WEAR_PERCENT);
+
+    let mut components = Vec::new();
+    if worn(status.engine_damage) {
+        components.push(Component::Engine);
+    }
+    if worn(status.gear_box_damage) {
+        components.push(Component::Gearbox);
+    }
+    if status.ers_fault == Some(true) {
+        components.push(Component::Ers);
+    }
+    if status.drs_fault == Some(true) {
+        components.push(Component::Drs);
+    }
+    components
+}
+
+/// Follows the failing components per car, to report each fault once when it first appears
+#[derive(Debug, Default)]
+pub(crate) struct FaultTracker {
+    reported: Vec<Vec<Component>>,
+}
+
+impl FaultTracker {
+    pub(crate) fn clear(&mut self) {
+        self.reported.clear();
+    }
+
+    /// The faults that appeared since the previous update, by car index
+    pub(crate) fn update(&mut self, car_status: &[CarStatusData]) -> Vec<(usize, Component)> {
+        if self.reported.len() < car_status.len() {
+            self.reported.resize(car_status.len(), Vec::new());
+        }
+
+        let mut new_faults = Vec::new();
+        for (i, status) in car_status.iter().enumerate() {
+            for component in failing_components(status) {
+                if !self.reported[i].contains(&component) {
+                    self.reported[i].push(component);
+                    new_faults.push((i, component));
+                }
+            }
+        }
+        new_faults
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_retirements_to_components_that_end_the_race() {
+        let status = CarStatusData { drs_fault: Some(true), ..Default::default() };
+        assert_eq!(failing_components(&status), [Component::Drs]);
+        assert_eq!(retirement_cause(&status), None);
+
+        let status = CarStatusData { drs_fault: Some(true), ers_fault: Some(true), ..Default::default() };
+        assert_eq!(retirement_cause(&status), Some(Component::Ers));
+
+        let status = CarStatusData { gear_box_damage: Some(95), ..Default::default() };
+        assert_eq!(retirement_cause(&status), Some(Component::Gearbox));
+    }
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Local};
+
+/// Maximum length in characters of a placeholder value, keeping full paths well within the Windows limit
+const MAX_COMPONENT_CHARS: usize = 64;
+
+/// Device names Windows reserves regardless of the extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Default naming of output files, see [`render_filename`] for the available placeholders
+pub(crate) const DEFAULT_FILENAME_TEMPLATE: &str = "{date}_{time} {track} {session} {type}_{uid}";
+
+/// Session details an output filename is derived from
+pub(crate) struct FilenameContext<'a> {
+    pub(crate) track: &'a str,
+    pub(crate) session: &'a str,
+    pub(crate) session_uid: u64,
+    /// Wall clock time the session was first seen, so files of sessions sharing a UID don't collide
+    pub(crate) started_at: DateTime<Local>,
+}
+
+/// Renders a filename template (without extension) for the given output type.
+///
+/// Supported placeholders are `{date}` (YYYY-MM-DD), `{time}` (HHMM), `{track}`, `{session}`,
+/// `{type}` and `{uid}`.
+pub(crate) fn render_filename(template: &str, context: &FilenameContext, event_type: &str) -> String {
+    template
+        .replace("{date}", &context.started_at.format("%Y-%m-%d").to_string())
+        .replace("{time}", &context.started_at.format("%H%M").to_string())
+        .replace("{track}", &sanitize_component(context.track))
+        .replace("{session}", &sanitize_component(context.session))
+        .replace("{type}", &sanitize_component(event_type))
+        .replace("{uid}", &context.session_uid.to_string())
+}
+
+/// Makes a placeholder value safe to use in a filename on Windows and Unix: characters reserved on either
+/// are replaced, whitespace is collapsed, trailing dots and spaces are trimmed and the length is capped.
+/// The template itself is not sanitized, so it can still contain directories.
+pub(crate) fn sanitize_component(value: &str) -> String {
+    let replaced: String = value
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_whitespace() => ' ',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let collapsed = replaced.split_whitespace().collect::<Vec<_>>().join(" ");
+    let capped: String = collapsed.chars().take(MAX_COMPONENT_CHARS).collect();
+    let mut sanitized = capped.trim_end_matches(['.', ' ']).to_string();
+
+    if sanitized.is_empty() {
+        sanitized.push('_');
+    }
+    if RESERVED_NAMES.iter().any(|name| name.eq_ignore_ascii_case(&sanitized)) {
+        sanitized.push('_');
+    }
+    sanitized
+}
@@ -0,0 +1,63 @@
+use f1_telemetry::packet::session::PacketSessionData;
+
+/// Weather forecast sample as logged, decoupled from the packet so samples can be compared field by field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ForecastSample {
+    pub(crate) session: String,
+    pub(crate) time_offset: u8,
+    pub(crate) weather: String,
+    pub(crate) track_temperature: i8,
+    pub(crate) air_temperature: i8,
+    pub(crate) rain_percentage: u8,
+}
+
+/// Current weather of the session, as opposed to the forecast
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Conditions {
+    pub(crate) weather: String,
+    pub(crate) track_temperature: i8,
+    pub(crate) air_temperature: i8,
+    /// Rain probability of the forecast sample for now, if the game sends one
+    pub(crate) rain_percentage: Option<u8>,
+}
+
+impl Conditions {
+    pub(crate) fn new(session_data: &PacketSessionData) -> Self {
+        let rain_percentage = session_data
+            .weather_forecast_samples
+            .iter()
+            .find(|sample| sample.session_type.name() == session_data.session_type.name() && sample.time_offset == 0)
+            .map(|sample| sample.rain_percentage);
+
+        Self {
+            weather: format!("{:?}", session_data.weather),
+            track_temperature: session_data.track_temperature,
+            air_temperature: session_data.air_temperature,
+            rain_percentage,
+        }
+    }
+}
+
+pub(crate) fn forecast_samples(session_data: &PacketSessionData) -> Vec<ForecastSample> {
+    session_data
+        .weather_forecast_samples
+        .iter()
+        .map(|sample| ForecastSample {
+            session: sample.session_type.name().to_string(),
+            time_offset: sample.time_offset,
+            weather: format!("{:?}", sample.weather),
+            track_temperature: sample.track_temperature,
+            air_temperature: sample.air_temperature,
+            rain_percentage: sample.rain_percentage,
+        })
+        .collect()
+}
+
+/// Highest rain probability forecast for the given session within the next `minutes`
+pub(crate) fn rain_probability_within(samples: &[ForecastSample], session: &str, minutes: u8) -> Option<u8> {
+    samples
+        .iter()
+        .filter(|sample| sample.session == session && sample.time_offset <= minutes)
+        .map(|sample| sample.rain_percentage)
+        .max()
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use f1_telemetry::packet::lap::LapData;
+
+/// Distance between two timing points on track
+const TIMING_POINT_SPACING_M: f32 = 50.0;
+
+/// Timing points remembered per car, enough for a gap of more than a lap on the longest tracks
+const MAX_TIMING_POINTS: usize = 400;
+
+/// Session time at which each car passed the timing points of its recent laps. The gap between two cars
+/// is the time between both passing the same point, like the timing screens of a real race.
+#[derive(Debug, Default)]
+pub(crate) struct GapTracker {
+    passings: Vec<VecDeque<(u32, u32)>>,
+}
+
+impl GapTracker {
+    pub(crate) fn clear(&mut self) {
+        self.passings.clear();
+    }
+
+    pub(crate) fn update(&mut self, lap_data: &[LapData], session_time: u32) {
+        if self.passings.len() < lap_data.len() {
+            self.passings.resize_with(lap_data.len(), VecDeque::new);
+        }
+
+        for (passings, lap) in self.passings.iter_mut().zip(lap_data) {
+            // The distance is negative before crossing the line for the first time
+            if lap.lap_distance < 0.0 {
+                continue;
+            }
+
+            let point = (lap.current_lap_num as u32) << 16 | (lap.lap_distance / TIMING_POINT_SPACING_M) as u32;
+            if passings.back().is_some_and(|&(last, _)| point <= last) {
+                continue;
+            }
+
+            if passings.len() == MAX_TIMING_POINTS {
+                passings.pop_front();
+            }
+            passings.push_back((point, session_time));
+        }
+    }
+
+    /// Time in ms by which `behind_idx` trails `ahead_idx` at the last timing point it passed, `None` if
+    /// the car ahead passed that point too long ago or not at all
+    pub(crate) fn gap_ms(&self, ahead_idx: usize, behind_idx: usize) -> Option<u32> {
+        let &(point, behind_time) = self.passings.get(behind_idx)?.back()?;
+        let ahead = self.passings.get(ahead_idx)?;
+        let i = ahead.binary_search_by_key(&point, |&(point, _)| point).ok()?;
+
+        Some(behind_time.saturating_sub(ahead[i].1))
+    }
+
+    /// Time in ms by which `behind_idx` trailed `ahead_idx` one lap before the last timing point it passed,
+    /// `None` if either car hadn't passed that point yet or `ahead_idx` was behind then
+    pub(crate) fn gap_lap_before_ms(&self, ahead_idx: usize, behind_idx: usize) -> Option<u32> {
+        let behind = self.passings.get(behind_idx)?;
+        let point = behind.back()?.0.checked_sub(1 << 16)?;
+        let passed_at = |passings: &VecDeque<(u32, u32)>| {
+            let i = passings.binary_search_by_key(&point, |&(point, _)| point).ok()?;
+            Some(passings[i].1)
+        };
+
+        passed_at(behind)?.checked_sub(passed_at(self.passings.get(ahead_idx)?)?)
+    }
+
+    /// Gap of `behind_idx` to `ahead_idx`, taken from the game's delta to the car in front when the packet
+    /// format has it and both cars run in consecutive positions, reconstructed from the timing points
+    /// otherwise
+    pub(crate) fn gap_to_car_in_front_ms(
+        &self,
+        lap_data: &[LapData],
+        ahead_idx: usize,
+        behind_idx: usize,
+    ) -> Option<u32> {
+        let reported = match (lap_data.get(ahead_idx), lap_data.get(behind_idx)) {
+            (Some(ahead), Some(behind)) => reported_gap_ms(ahead, behind),
+            _ => None,
+        };
+        reported.or_else(|| self.gap_ms(ahead_idx, behind_idx))
+    }
+}
+
+/// The game's delta of `behind` to the car in front, if that car is `ahead`. Older packet formats don't
+/// report the delta.
+fn reported_gap_ms(ahead: &LapData, behind: &LapData) -> Option<u32> {
+    if behind.car_position != ahead.car_position + 1 {
+        return None;
+    }
+    behind.delta_to_car_in_front_in_ms.map(u32::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lap_data(laps: &[(u8, f32)]) -> Vec<LapData> {
+        laps.iter()
+            .map(|&(current_lap_num, lap_distance)| LapData { current_lap_num, lap_distance, ..Default::default() })
+            .collect()
+    }
+
+    #[test]
+    fn gap_a_lap_before_the_last_timing_point() {
+        let mut gaps = GapTracker::default();
+        // Car 0 leads car 1 by 800 ms on lap 1, car 1 closes up to 100 ms on lap 2
+        gaps.update(&lap_data(&[(1, 100.0)]), 10_000);
+        gaps.update(&lap_data(&[(1, 150.0), (1, 100.0)]), 10_800);
+        gaps.update(&lap_data(&[(2, 100.0), (1, 150.0)]), 100_000);
+        gaps.update(&lap_data(&[(2, 150.0), (2, 100.0)]), 100_100);
+
+        assert_eq!(gaps.gap_ms(0, 1), Some(100));
+        assert_eq!(gaps.gap_lap_before_ms(0, 1), Some(800));
+        // Car 1 trailed car 0 a lap before, so it wasn't ahead
+        assert_eq!(gaps.gap_lap_before_ms(1, 0), None);
+    }
+
+    #[test]
+    fn no_gap_a_lap_before_on_the_first_lap() {
+        let mut gaps = GapTracker::default();
+        gaps.update(&lap_data(&[(1, 100.0), (1, 50.0)]), 10_000);
+        gaps.update(&lap_data(&[(1, 150.0), (1, 100.0)]), 10_500);
+
+        assert_eq!(gaps.gap_lap_before_ms(0, 1), None);
+    }
+}
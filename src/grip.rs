@@ -0,0 +1,62 @@
+/// Grip characteristics of a tyre compound. The game doesn't send the tyres' grip, so the figures are rough
+/// rules of thumb: softer compounds start with more grip but lose it faster, every compound loses grip
+/// outside its temperature window and a hot track wears all of them faster.
+#[derive(Debug, Clone, Copy)]
+struct CompoundModel {
+    /// Grip of a new set within its temperature window, relative to a new soft
+    base_grip: f32,
+    /// Grip lost per lap of age at 30 °C
+    wear_per_lap: f32,
+    /// Track temperatures in °C the compound works best in
+    window: (i8, i8),
+}
+
+/// Grip lost per °C outside the compound's window
+const TEMPERATURE_LOSS_PER_C: f32 = 0.005;
+/// Track temperature the wear rates are given for
+const REFERENCE_TEMPERATURE: f32 = 30.0;
+/// Wear rate change per °C away from the reference temperature
+const WEAR_PER_C: f32 = 0.02;
+
+fn compound_model(compound: &str) -> Option<CompoundModel> {
+    let compound = compound.to_lowercase();
+    let model = if compound.contains("soft") {
+        CompoundModel { base_grip: 1.0, wear_per_lap: 0.004, window: (25, 35) }
+    } else if compound.contains("medium") {
+        CompoundModel { base_grip: 0.97, wear_per_lap: 0.0025, window: (30, 40) }
+    } else if compound.contains("hard") {
+        CompoundModel { base_grip: 0.94, wear_per_lap: 0.0015, window: (35, 45) }
+    } else if compound.contains("inter") {
+        CompoundModel { base_grip: 0.9, wear_per_lap: 0.003, window: (15, 25) }
+    } else if compound.contains("wet") {
+        CompoundModel { base_grip: 0.85, wear_per_lap: 0.003, window: (10, 20) }
+    } else {
+        return None;
+    };
+    Some(model)
+}
+
+/// Heuristic grip of a set of tyres relative to a new soft, `None` for an unknown compound or age
+pub(crate) fn estimate(compound: &str, age_laps: Option<u8>, track_temperature: i8) -> Option<f32> {
+    let model = compound_model(compound)?;
+    let age_laps = age_laps? as f32;
+
+    let (low, high) = model.window;
+    let temperature = track_temperature as f32;
+    let outside_window = (low as f32 - temperature).max(temperature - high as f32).max(0.0);
+    let wear_factor = (1.0 + (temperature - REFERENCE_TEMPERATURE) * WEAR_PER_C).max(0.5);
+
+    Some(model.base_grip - age_laps * model.wear_per_lap * wear_factor - outside_window * TEMPERATURE_LOSS_PER_C)
+}
+
+/// Heuristic grip advantage of the overtaker's tyres over the overtakee's in percent, `None` if either is
+/// unknown
+pub(crate) fn advantage(
+    overtaker: (&str, Option<u8>),
+    overtakee: (&str, Option<u8>),
+    track_temperature: i8,
+) -> Option<f32> {
+    let overtaker = estimate(overtaker.0, overtaker.1, track_temperature)?;
+    let overtakee = estimate(overtakee.0, overtakee.1, track_temperature)?;
+    Some((overtaker - overtakee) * 100.0)
+}
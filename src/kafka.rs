@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+
+use crate::timeline::TimelineEntry;
+
+/// Events kept while the producer's own queue is full, the oldest are dropped beyond this
+const MAX_PENDING_EVENTS: usize = 10_000;
+
+/// How long the producer keeps retrying an event while the brokers are unavailable
+#[cfg(feature = "kafka")]
+const MESSAGE_TIMEOUT_MS: &str = "300000";
+
+/// Time given to deliver the remaining events on shutdown
+#[cfg(feature = "kafka")]
+const SHUTDOWN_FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub(crate) struct KafkaConfig {
+    /// Comma separated bootstrap brokers, e.g. "localhost:9092"
+    pub(crate) brokers: String,
+    pub(crate) topic: String,
+}
+
+/// Checks that the logger was built with the `kafka` feature, which needs librdkafka
+pub(crate) fn ensure_supported() -> Result<(), String> {
+    if cfg!(feature = "kafka") {
+        Ok(())
+    } else {
+        Err("Kafka output is not available, build with --features kafka".to_string())
+    }
+}
+
+/// Publishes every event as a JSON message keyed by the session UID, so a session's events share a partition
+/// and stay in order. Delivery and its retries run on the producer's own thread, so an unavailable broker
+/// doesn't hold up the packet handling. Events that don't fit into the producer's queue are kept and sent
+/// with the next event.
+pub(crate) struct KafkaSink {
+    #[cfg(feature = "kafka")]
+    producer: rdkafka::producer::ThreadedProducer<rdkafka::producer::DefaultProducerContext>,
+    topic: String,
+    pending: VecDeque<(String, String)>,
+    dropped: u64,
+}
+
+impl KafkaSink {
+    #[cfg(feature = "kafka")]
+    pub(crate) fn new(config: &KafkaConfig) -> Result<Self, String> {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", &config.brokers)
+            .set("message.timeout.ms", MESSAGE_TIMEOUT_MS)
+            .create()
+            .map_err(|err| format!("Failed to create the Kafka producer: {}", err))?;
+
+        Ok(Self { producer, topic: config.topic.clone(), pending: VecDeque::new(), dropped: 0 })
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    pub(crate) fn new(_config: &KafkaConfig) -> Result<Self, String> {
+        ensure_supported().map(|()| Self { topic: String::new(), pending: VecDeque::new(), dropped: 0 })
+    }
+
+    pub(crate) fn publish(&mut self, session_uid: u64, entry: &TimelineEntry) {
+        let payload = entry.to_json(session_uid);
+
+        if self.pending.len() == MAX_PENDING_EVENTS {
+            self.pending.pop_front();
+            self.dropped += 1;
+            if self.dropped == 1 || self.dropped % 1000 == 0 {
+                println!("Kafka queue full, {} events dropped so far", self.dropped);
+            }
+        }
+        self.pending.push_back((session_uid.to_string(), payload.to_string()));
+        self.send_pending();
+    }
+
+    /// Hands the pending events to the producer in order, until its queue is full
+    fn send_pending(&mut self) {
+        while let Some((key, payload)) = self.pending.front() {
+            if !self.send(key, payload) {
+                break;
+            }
+            self.pending.pop_front();
+        }
+    }
+
+    #[cfg(feature = "kafka")]
+    fn send(&self, key: &str, payload: &str) -> bool {
+        let record = rdkafka::producer::BaseRecord::to(&self.topic).key(key).payload(payload);
+        match self.producer.send(record) {
+            Ok(()) => true,
+            Err((rdkafka::error::KafkaError::MessageProduction(rdkafka::types::RDKafkaErrorCode::QueueFull), _)) => {
+                false
+            },
+            Err((err, _)) => {
+                // Not retryable, e.g. an oversized message, so it is dropped instead of blocking the queue
+                println!("Failed to publish event to Kafka topic {}: {}", self.topic, err);
+                true
+            },
+        }
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    fn send(&self, _key: &str, _payload: &str) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl Drop for KafkaSink {
+    fn drop(&mut self) {
+        use rdkafka::producer::Producer;
+
+        self.send_pending();
+        if let Err(err) = self.producer.flush(SHUTDOWN_FLUSH_TIMEOUT) {
+            println!("Failed to deliver all events to Kafka: {}", err);
+        }
+        let undelivered = self.pending.len() + self.producer.in_flight_count().max(0) as usize;
+        if undelivered > 0 {
+            println!("{} events could not be delivered to Kafka", undelivered);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use f1_telemetry::packet::lap::{LapData, PitStatus};
+
+use crate::phases::{LapPhases, Phase};
+
+/// Position change of a single car over one completed lap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LapSnapshot {
+    pub(crate) lap: u8,
+    /// Laps completed by the car furthest into the session when this lap was completed, i.e. the leader in
+    /// races. Unlike `lap` it lines up across cars that joined the session late.
+    pub(crate) session_lap: u8,
+    pub(crate) start_position: u8,
+    pub(crate) end_position: u8,
+    pub(crate) session_time: u32,
+    /// Lap time reported by the game, 0 if unknown
+    pub(crate) lap_time_ms: u32,
+    /// Sector times, `None` if the game didn't report all of them, e.g. after a flashback
+    pub(crate) sectors_ms: Option<[u32; 3]>,
+    /// Race phases covering the lap
+    pub(crate) phases: LapPhases,
+    /// Whether the lap was driven at racing speed, i.e. not the standing start, an in or out lap, or
+    /// outside of green flag conditions
+    pub(crate) clean: bool,
+}
+
+impl LapSnapshot {
+    pub(crate) fn positions_gained(&self) -> i16 {
+        self.start_position as i16 - self.end_position as i16
+    }
+}
+
+/// Holder of the session's fastest lap, from the fastest lap events or else the completed laps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FastestLapHolder {
+    pub(crate) car_idx: u8,
+    pub(crate) lap_time_ms: u32,
+    /// Whether a fastest lap event named the holder. Completed laps can't tell invalidated laps apart, so
+    /// a holder taken from them is replaced by the next event.
+    pub(crate) confirmed: bool,
+}
+
+/// Mean and spread of a car's clean lap times
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct LapTimeStats {
+    pub(crate) clean_laps: usize,
+    pub(crate) mean_ms: f64,
+    /// Sample standard deviation, `None` with fewer than [`MIN_CLEAN_LAPS`] clean laps
+    pub(crate) std_dev_ms: Option<f64>,
+}
+
+/// Minimum number of clean laps for a meaningful standard deviation
+const MIN_CLEAN_LAPS: usize = 3;
+
+/// Per-car history of completed laps, shared by every lap based output
+#[derive(Debug, Default)]
+pub(crate) struct LapHistory {
+    start_positions: Vec<u8>,
+    /// Whether the car's current lap was disturbed by a pit visit so far
+    in_pits: Vec<bool>,
+    current_phases: Vec<LapPhases>,
+    last_update_time: Option<u32>,
+    laps: Vec<Vec<LapSnapshot>>,
+}
+
+impl LapHistory {
+    pub(crate) fn clear(&mut self) {
+        self.start_positions.clear();
+        self.in_pits.clear();
+        self.current_phases.clear();
+        self.last_update_time = None;
+        self.laps.clear();
+    }
+
+    /// Records a snapshot for every car that completed a lap between `previous` and `current`
+    /// and returns the indexes of those cars. The time since the last update is attributed to `phase`.
+    pub(crate) fn update(
+        &mut self,
+        previous: &[LapData],
+        current: &[LapData],
+        session_time: u32,
+        phase: Phase,
+    ) -> Vec<usize> {
+        // Cars seen for the first time start their first lap from their current (grid) position
+        for lap in current.iter().skip(self.laps.len()) {
+            self.start_positions.push(lap.car_position);
+            self.in_pits.push(false);
+            self.current_phases.push(LapPhases::default());
+            self.laps.push(Vec::new());
+        }
+
+        let elapsed = self.last_update_time.map_or(0, |last| session_time.saturating_sub(last));
+        self.last_update_time = Some(session_time);
+
+        let session_laps =
+            current.iter().filter(|lap| lap.car_position > 0).map(|lap| lap.current_lap_num.saturating_sub(1)).max();
+
+        let mut completed = Vec::new();
+        for (i, (previous, current)) in previous.iter().zip(current).enumerate() {
+            // A pit stop crosses the line in the pit lane, which marks both the in and the out lap
+            let in_pits = current.pit_status != PitStatus::None;
+            self.in_pits[i] |= in_pits;
+            self.current_phases[i].add(phase, elapsed);
+
+            if current.current_lap_num <= previous.current_lap_num || current.car_position == 0 {
+                continue;
+            }
+
+            self.laps[i].push(LapSnapshot {
+                lap: previous.current_lap_num,
+                session_lap: session_laps.unwrap_or_default().max(previous.current_lap_num),
+                start_position: self.start_positions[i],
+                end_position: current.car_position,
+                session_time,
+                lap_time_ms: current.last_lap_time,
+                sectors_ms: sector_times(previous, current.last_lap_time),
+                phases: self.current_phases[i],
+                clean: previous.current_lap_num > 1
+                    && !self.in_pits[i]
+                    && self.current_phases[i].is_green()
+                    && current.last_lap_time > 0,
+            });
+            self.start_positions[i] = current.car_position;
+            self.in_pits[i] = in_pits;
+            self.current_phases[i] = LapPhases::default();
+            completed.push(i);
+        }
+
+        completed
+    }
+
+    /// Marks the lap every car is currently on as interrupted by a red flag
+    pub(crate) fn interrupt(&mut self) {
+        for phases in &mut self.current_phases {
+            phases.interrupt();
+        }
+    }
+
+    pub(crate) fn laps(&self, car_idx: usize) -> &[LapSnapshot] {
+        self.laps.get(car_idx).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub(crate) fn last_lap(&self, car_idx: usize) -> Option<&LapSnapshot> {
+        self.laps(car_idx).last()
+    }
+
+    /// Highest number of laps completed by any car
+    pub(crate) fn max_laps(&self) -> usize {
+        self.laps.iter().map(Vec::len).max().unwrap_or_default()
+    }
+
+    pub(crate) fn total_positions_gained(&self, car_idx: usize) -> i16 {
+        self.laps(car_idx).iter().map(LapSnapshot::positions_gained).sum()
+    }
+
+    /// The car's fastest lap with a known lap time
+    pub(crate) fn best_lap(&self, car_idx: usize) -> Option<&LapSnapshot> {
+        self.laps(car_idx).iter().filter(|lap| lap.lap_time_ms > 0).min_by_key(|lap| lap.lap_time_ms)
+    }
+
+    /// The fastest lap with a known lap time over all cars, unconfirmed
+    pub(crate) fn fastest_lap(&self) -> Option<FastestLapHolder> {
+        (0..self.laps.len())
+            .filter_map(|i| Some((i, self.best_lap(i)?.lap_time_ms)))
+            .min_by_key(|&(_, lap_time_ms)| lap_time_ms)
+            .map(|(i, lap_time_ms)| FastestLapHolder { car_idx: i as u8, lap_time_ms, confirmed: false })
+    }
+
+    /// The car's best time in each sector, possibly set on different laps
+    pub(crate) fn best_sectors(&self, car_idx: usize) -> Option<[u32; 3]> {
+        self.laps(car_idx)
+            .iter()
+            .filter_map(|lap| lap.sectors_ms)
+            .reduce(|best, sectors| [best[0].min(sectors[0]), best[1].min(sectors[1]), best[2].min(sectors[2])])
+    }
+
+    /// Lap time statistics over the car's clean laps, `None` without any clean lap
+    pub(crate) fn lap_time_stats(&self, car_idx: usize) -> Option<LapTimeStats> {
+        let times: Vec<f64> =
+            self.laps(car_idx).iter().filter(|lap| lap.clean).map(|lap| lap.lap_time_ms as f64).collect();
+        if times.is_empty() {
+            return None;
+        }
+
+        let count = times.len() as f64;
+        let mean_ms = times.iter().sum::<f64>() / count;
+        let std_dev_ms = (times.len() >= MIN_CLEAN_LAPS).then(|| {
+            let variance = times.iter().map(|time| (time - mean_ms).powi(2)).sum::<f64>() / (count - 1.0);
+            variance.sqrt()
+        });
+
+        Some(LapTimeStats { clean_laps: times.len(), mean_ms, std_dev_ms })
+    }
+}
+
+/// Sector times of the lap just completed. The game only reports the first two sectors, taken from the
+/// last lap data before the car crossed the line, and the third one follows from the lap time.
+pub(crate) fn sector_times(last_lap_data: &LapData, lap_time_ms: u32) -> Option<[u32; 3]> {
+    let sector_1 = last_lap_data.sector_1_time as u32;
+    let sector_2 = last_lap_data.sector_2_time as u32;
+    if sector_1 == 0 || sector_2 == 0 || lap_time_ms <= sector_1 + sector_2 {
+        return None;
+    }
+
+    Some([sector_1, sector_2, lap_time_ms - sector_1 - sector_2])
+}
+
+/// Session time since which each car holds its current position on track
+#[derive(Debug, Default)]
+pub(crate) struct PositionHolds {
+    held_since: Vec<Option<u32>>,
+}
+
+impl PositionHolds {
+    pub(crate) fn clear(&mut self) {
+        self.held_since.clear();
+    }
+
+    /// Position changes on track restart the clock immediately. A car in the pit lane doesn't hold its
+    /// position on track, so its clock only restarts once it rejoins with whatever position it has then.
+    pub(crate) fn update(&mut self, previous: &[LapData], current: &[LapData], session_time: u32) {
+        for _ in current.iter().skip(self.held_since.len()) {
+            self.held_since.push(Some(session_time));
+        }
+
+        for (i, (previous, current)) in previous.iter().zip(current).enumerate() {
+            if current.pit_status != PitStatus::None {
+                self.held_since[i] = None;
+            } else if current.car_position != previous.car_position || self.held_since[i].is_none() {
+                self.held_since[i] = Some(session_time);
+            }
+        }
+    }
+
+    pub(crate) fn held_for_ms(&self, car_idx: usize, session_time: u32) -> Option<u32> {
+        let since = self.held_since.get(car_idx).copied().flatten()?;
+        Some(session_time.saturating_sub(since))
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::{fmt, path};
+
+/// Default width of the location bins in metres
+pub(crate) const DEFAULT_BIN_SIZE_M: u16 = 50;
+
+/// Distance to the start/finish line within which an event is flagged as near the line
+const NEAR_LINE_MARGIN_M: f32 = 100.0;
+
+/// Wraps a lap distance into the track's length. Around the start/finish line the game reports distances
+/// beyond the track length before the lap counts up, and negative ones before a car first crosses the line,
+/// putting events on both sides of the line thousands of metres apart. Unchanged if the length is unknown.
+pub(crate) fn normalize_lap_distance(lap_distance: f32, track_length: u16) -> f32 {
+    if track_length == 0 {
+        return lap_distance.max(0.0);
+    }
+    lap_distance.rem_euclid(track_length as f32)
+}
+
+/// Whether a normalized lap distance is within [`NEAR_LINE_MARGIN_M`] of the start/finish line, where the
+/// lap and the track position of an event may belong to either side of the line. `None` if the track
+/// length is unknown.
+pub(crate) fn near_line(lap_distance: f32, track_length: u16) -> Option<bool> {
+    (track_length > 0)
+        .then_some(lap_distance < NEAR_LINE_MARGIN_M || lap_distance > track_length as f32 - NEAR_LINE_MARGIN_M)
+}
+
+/// Lap distance range of fixed width, so events at the same spot of the track get the same location even
+/// though they fire a few frames apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct LocationBin {
+    pub(crate) start_m: u16,
+    pub(crate) end_m: u16,
+}
+
+impl LocationBin {
+    /// The bin containing the lap distance, a size of 0 is treated as 1 m
+    pub(crate) fn new(lap_distance_m: u16, size_m: u16) -> Self {
+        let size_m = size_m.max(1);
+        let start_m = lap_distance_m / size_m * size_m;
+        Self { start_m, end_m: start_m.saturating_add(size_m) }
+    }
+}
+
+impl fmt::Display for LocationBin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start_m, self.end_m)
+    }
+}
+
+/// A stretch of a track, e.g. a DRS zone or a corner, as lap distances in metres. Ranges crossing the finish
+/// line end at a lower distance than they start.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TrackRange {
+    track: String,
+    /// Name given in the file, e.g. "Turn 1", empty where ranges have no names
+    pub(crate) name: String,
+    start_m: f32,
+    end_m: f32,
+}
+
+impl TrackRange {
+    fn is_on(&self, track: &str) -> bool {
+        self.track.eq_ignore_ascii_case(track)
+    }
+
+    fn contains(&self, lap_distance: f32) -> bool {
+        if self.start_m <= self.end_m {
+            (self.start_m..=self.end_m).contains(&lap_distance)
+        } else {
+            lap_distance >= self.start_m || lap_distance <= self.end_m
+        }
+    }
+}
+
+/// Ranges by track, read from a CSV with the columns "Track", an optional name column, "Start [m]" and
+/// "End [m]". Tracks are named like the {track} placeholder of the filename template, e.g. "Melbourne".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct TrackRanges {
+    ranges: Vec<TrackRange>,
+}
+
+impl TrackRanges {
+    /// Reads the ranges, with a name column after the track if `named`. `kind` names the ranges in errors,
+    /// e.g. "corner".
+    pub(crate) fn from_file(path: &path::Path, kind: &str, named: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut ranges = Vec::new();
+        for record in reader.records() {
+            let record = record?;
+            let field = |i: usize| record.get(i).unwrap_or_default().trim();
+            let distance = |i: usize, end: &str| {
+                field(i).parse().map_err(|err| format!("Invalid {} {} '{}': {}", kind, end, field(i), err))
+            };
+            let start = if named { 2 } else { 1 };
+            ranges.push(TrackRange {
+                track: field(0).to_string(),
+                name: if named { field(1).to_string() } else { String::new() },
+                start_m: distance(start, "start")?,
+                end_m: distance(start + 1, "end")?,
+            });
+        }
+
+        Ok(Self { ranges })
+    }
+
+    pub(crate) fn has_track(&self, track: &str) -> bool {
+        self.ranges.iter().any(|range| range.is_on(track))
+    }
+
+    /// The track's range containing the lap distance, as index into the ranges
+    pub(crate) fn find(&self, track: &str, lap_distance: f32) -> Option<usize> {
+        self.ranges.iter().position(|range| range.is_on(track) && range.contains(lap_distance))
+    }
+
+    pub(crate) fn get(&self, i: usize) -> Option<&TrackRange> {
+        self.ranges.get(i)
+    }
+}
+
+/// Counts events per location bin for the summary
+#[derive(Debug, Default)]
+pub(crate) struct LocationCounts {
+    counts: BTreeMap<LocationBin, usize>,
+}
+
+impl LocationCounts {
+    pub(crate) fn clear(&mut self) {
+        self.counts.clear();
+    }
+
+    pub(crate) fn push(&mut self, bin: LocationBin) {
+        *self.counts.entry(bin).or_default() += 1;
+    }
+
+    /// Bins with their counts, the most events first and bins with equal counts in lap order
+    pub(crate) fn ranked(&self) -> Vec<(LocationBin, usize)> {
+        let mut ranked: Vec<(LocationBin, usize)> = self.counts.iter().map(|(&bin, &count)| (bin, count)).collect();
+        ranked.sort_by_key(|&(bin, count)| (std::cmp::Reverse(count), bin));
+        ranked
+    }
+}
+
+/// Lap distances of the session's overtakes, counted per bin over the whole lap for a heatmap
+#[derive(Debug, Default)]
+pub(crate) struct Heatmap {
+    positions: Vec<u16>,
+}
+
+impl Heatmap {
+    pub(crate) fn clear(&mut self) {
+        self.positions.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Adds an overtake at a lap distance normalized with [`normalize_lap_distance`]
+    pub(crate) fn push(&mut self, lap_distance_m: u16) {
+        self.positions.push(lap_distance_m);
+    }
+
+    /// Counts per bin from the start/finish line to the end of the lap in lap order, including bins without
+    /// overtakes. The last bin ends at the track length, or at the furthest overtake if the length is unknown.
+    /// A size of 0 is treated as 1 m.
+    pub(crate) fn bins(&self, size_m: u16, track_length: u16) -> Vec<(LocationBin, usize)> {
+        let size_m = size_m.max(1);
+        let end_m = match track_length {
+            0 => self.positions.iter().max().map_or(0, |&furthest| furthest.saturating_add(1)),
+            length => length,
+        };
+
+        let mut counts = vec![0; (end_m as usize).div_ceil(size_m as usize)];
+        for &position in &self.positions {
+            if let Some(count) = counts.get_mut((position / size_m) as usize) {
+                *count += 1;
+            }
+        }
+
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let start_m = i as u16 * size_m;
+                (LocationBin { start_m, end_m: start_m.saturating_add(size_m).min(end_m) }, count)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(track: &str, start_m: f32, end_m: f32) -> TrackRange {
+        TrackRange { track: track.to_string(), name: String::new(), start_m, end_m }
+    }
+
+    #[test]
+    fn finds_ranges_of_the_track() {
+        let ranges = TrackRanges { ranges: vec![range("Monza", 100.0, 200.0), range("Spa", 150.0, 250.0)] };
+
+        assert_eq!(ranges.find("monza", 150.0), Some(0));
+        assert_eq!(ranges.find("Spa", 150.0), Some(1));
+        assert_eq!(ranges.find("Monza", 250.0), None);
+        assert!(!ranges.has_track("Imola"));
+    }
+
+    #[test]
+    fn ranges_cross_the_finish_line() {
+        let ranges = TrackRanges { ranges: vec![range("Melbourne", 5200.0, 100.0)] };
+
+        assert_eq!(ranges.find("Melbourne", 5250.0), Some(0));
+        assert_eq!(ranges.find("Melbourne", 50.0), Some(0));
+        assert_eq!(ranges.find("Melbourne", 2000.0), None);
+    }
+}
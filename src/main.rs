@@ -1,14 +1,135 @@
-use crate::session::SessionState;
-use clap::Parser;
+use std::collections::VecDeque;
+use std::{fs, io, net, path, time};
 
-use f1_telemetry::packet::Packet;
-use f1_telemetry::Stream;
+use crate::capture::{CaptureIndex, CaptureReader, CaptureWriter};
+use crate::columns::ColumnSelection;
+use crate::corners::Corners;
+use crate::drs::DrsZones;
+use crate::filenames::DEFAULT_FILENAME_TEMPLATE;
+use crate::kafka::KafkaConfig;
+use crate::locations::DEFAULT_BIN_SIZE_M;
+use crate::ordering::EventOrder;
+use crate::pipeline::PacketPipeline;
+use crate::points::PointsScheme;
+use crate::positions::PositionsFormat;
+use crate::retention::{RetentionMode, RetentionPolicy};
+use crate::session::SessionOptions;
+use crate::teams::{DriverIds, DriverSelection, EmptyNames, FollowCar, IdentifyBy};
+use crate::vod::{VodConfig, VodFormat, VodStart};
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+/// Console output of the whole crate clears the progress line first, see [`display::print_progress`]. Defined
+/// ahead of the modules, so it takes the place of the standard macro in all of them.
+macro_rules! println {
+    ($($arg:tt)*) => {{
+        $crate::display::clear_progress();
+        ::std::println!($($arg)*);
+    }};
+}
+
+mod amendments;
+mod analyze;
+mod anonymize;
+mod capture;
+mod columns;
+mod controls;
+mod corners;
+mod dedup;
+mod display;
+mod drops;
+mod drs;
+mod faults;
+mod filenames;
+mod forecast;
+mod gaps;
+mod grip;
+mod kafka;
+mod laps;
+mod locations;
+mod metadata;
+mod metadatahttp;
+mod notify;
+mod ordering;
+mod pairs;
+mod penalties;
+mod performance;
+mod phases;
+mod pipeline;
+mod pits;
+mod points;
+mod positions;
+mod rates;
+mod rawevents;
+mod replaydb;
+mod report;
+mod retention;
+mod rolling;
+mod runs;
+mod schema;
+mod selftest;
 mod session;
+mod sessions;
+mod sinks;
+mod statsd;
+mod status;
+mod style;
+mod summary;
+mod teams;
+mod timeline;
+mod trackers;
+mod unixsocket;
+mod vod;
+mod weekend;
+
+/// Receive buffer size, above the largest packet any supported game sends
+const MAX_DATAGRAM_SIZE: usize = 2048;
+
+/// Interval of the retention cleanup while listening, for rigs where sessions rarely end
+const RETENTION_INTERVAL: time::Duration = time::Duration::from_secs(60 * 60);
+
+/// Output files of `--auto`: one directory per session, named by its start time, track and type
+const AUTO_FILENAME_TEMPLATE: &str = "{date}_{time} {track} {session}/{type}";
+
+/// Idle timeout of `--auto`, long enough for the game's menus between sessions
+const AUTO_IDLE_TIMEOUT_SECS: u64 = 10 * 60;
+
+/// Status interval of `--auto`
+const AUTO_STATUS_INTERVAL_SECS: u64 = 5 * 60;
 
 #[derive(Parser)]
-#[command(author, version, about, propagate_version = true)]
+#[command(author, version, about, propagate_version = true, args_conflicts_with_subcommands = true)]
 struct AppArgs {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Options of the `listen` subcommand, which runs when no subcommand is given
+    #[command(flatten, next_help_heading = "Listen Options")]
+    listen: ListenArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Log the telemetry received from the game (default)
+    Listen(ListenArgs),
+    /// Log a capture recorded with `listen --capture` as if it was received live
+    Replay(ReplayArgs),
+    /// Print the packet counts, sessions and duration of a capture
+    Analyze(AnalyzeArgs),
+    /// Write an environment file with all listen options and their defaults
+    Init(InitArgs),
+    /// Convert a trace written with `--positions-format compact` to CSV
+    ExportPositions(ExportPositionsArgs),
+    /// Log a short synthetic race into a temporary directory and check the files written, printing PASS or
+    /// FAIL per check
+    Selftest(SelftestArgs),
+}
+
+#[derive(Args)]
+struct ListenArgs {
     /// Host to bind on for the UDP packet listener
     #[clap(long, default_value = "127.0.0.1", env)]
     listener_host: String,
@@ -16,51 +137,945 @@ struct AppArgs {
     /// Port to bind on for the UDP packet listener
     #[clap(long, default_value = "20777", env)]
     listener_port: u16,
+
+    /// Receive buffer size of the UDP socket, large enough to absorb the game's packet bursts.
+    /// The OS may cap it, e.g. at net.core.rmem_max on Linux.
+    #[clap(long, default_value = "4194304", env)]
+    recv_buffer_bytes: usize,
+
+    /// Record all received datagrams to a capture file for the replay and analyze subcommands
+    #[clap(long, env)]
+    capture: Option<path::PathBuf>,
+
+    /// Print a status line every this many seconds, with the packets per second by type, the events written,
+    /// the leader and the time since the last packet, to check an unattended logger is still receiving
+    #[clap(long, env, value_parser = clap::value_parser!(u64).range(1..))]
+    status_interval: Option<u64>,
+
+    /// Close the session's files after this many seconds without packets, e.g. when the game was quit, so
+    /// they are complete even if the logger keeps running. A returning game starts a new session.
+    #[clap(long, env, value_parser = clap::value_parser!(u64).range(1..))]
+    idle_timeout: Option<u64>,
+
+    /// Preset for unattended rigs. Unless given otherwise, it writes each session into its own directory
+    /// named "{date}_{time} {track} {session}", closes the files after 10 minutes without packets, prints a
+    /// status line every 5 minutes and syncs the files to disk when a session ends or on Ctrl+C.
+    #[clap(long, env)]
+    auto: bool,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+impl ListenArgs {
+    /// Applies the defaults of `--auto` to the options that weren't changed from their defaults
+    fn apply_auto_preset(&mut self) {
+        if self.output.filename_template == DEFAULT_FILENAME_TEMPLATE {
+            self.output.filename_template = AUTO_FILENAME_TEMPLATE.to_string();
+        }
+        let idle_timeout = *self.idle_timeout.get_or_insert(AUTO_IDLE_TIMEOUT_SECS);
+        let status_interval = *self.status_interval.get_or_insert(AUTO_STATUS_INTERVAL_SECS);
+        self.output.fsync = true;
+
+        println!(
+            "Auto mode: writing to \"{}\", idle timeout {} s, status every {} s, syncing files to disk",
+            self.output.filename_template, idle_timeout, status_interval
+        );
+    }
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// Capture file to replay
+    capture: path::PathBuf,
+
+    /// Send the datagrams to a running listener at this address instead of logging them, e.g. to stress test it
+    #[clap(long)]
+    send_to: Option<String>,
+
+    /// Replay speed relative to the recording when sending, 0 sends as fast as possible
+    #[clap(long, default_value = "1", requires = "send_to")]
+    speed: f64,
+
+    /// Only replay this session, found with the capture's index. The session starts with the first packet
+    /// recorded for it, or with the lap given by --from-lap.
+    #[clap(long, conflicts_with = "send_to")]
+    session: Option<u64>,
+
+    /// Start the replay at this lap of the leader, the session and participants are picked up from the
+    /// next packets sent by the game
+    #[clap(long, requires = "session")]
+    from_lap: Option<u8>,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    /// Capture file to analyze
+    capture: path::PathBuf,
+}
+
+#[derive(Args)]
+struct ExportPositionsArgs {
+    /// Compact positions file to convert
+    file: path::PathBuf,
+
+    /// Write the positions to a CSV file next to the input, currently the only export format
+    #[clap(long)]
+    csv: bool,
+}
+
+#[derive(Args)]
+struct SelftestArgs {
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args)]
+struct InitArgs {
+    /// File to write the configuration to
+    #[clap(default_value = "f1-eventlogger.env")]
+    path: path::PathBuf,
+
+    /// Overwrite the file if it already exists
+    #[clap(long)]
+    force: bool,
+}
+
+/// Options for the logged output, shared by listening live and replaying captures
+#[derive(Args)]
+struct OutputArgs {
+    /// Export the player's world position to a "Trace" CSV for plotting the racing line
+    #[clap(long, env)]
+    player_trace: bool,
+
+    /// Minimum interval between two player trace points in milliseconds
+    #[clap(long, default_value = "100", env)]
+    player_trace_interval_ms: u32,
+
+    /// Format of the player trace: "csv" or "compact", a delta-encoded binary format a fraction of the size
+    /// for long sessions, convertible to CSV with the export-positions subcommand
+    #[clap(long, default_value = "csv", env)]
+    positions_format: PositionsFormat,
+
+    /// Maximum time in seconds between an overtake and a collision of the same two cars to cross-reference them
+    #[clap(long, default_value = "5", env)]
+    cross_reference_window_secs: u32,
+
+    /// Add a points column to the classification: "f1-2024", "sprint" or comma separated points per position
+    #[clap(long, env)]
+    points_scheme: Option<PointsScheme>,
+
+    /// Read a custom points scheme (comma separated points per position) from a file
+    #[clap(long, env, conflicts_with = "points_scheme")]
+    points_scheme_file: Option<std::path::PathBuf>,
+
+    /// Bonus points for the fastest lap within the points positions, overriding the scheme's default
+    #[clap(long, env)]
+    fastest_lap_points: Option<u16>,
+
+    /// Template for output filenames (without extension).
+    /// Placeholders: {date}, {time}, {track}, {session}, {type}, {uid}
+    #[clap(long, default_value = DEFAULT_FILENAME_TEMPLATE, env)]
+    filename_template: String,
+
+    /// Log events without dedicated handling to an "UnknownEvents" CSV, to help support new game versions
+    #[clap(long, env)]
+    log_unknown_events: bool,
+
+    /// Log every event packet as read from the datagram to a "RawEvents" CSV, with the event code, the vehicle
+    /// indexes where known and the payload in hex. Covers event codes the logger can't parse.
+    #[clap(long, env)]
+    log_raw_events: bool,
+
+    /// Columns of the "Laps" CSV after driver, team, lap and session lap, as comma separated keys: position,
+    /// positions_gained, run, session_time, lap_time, phase, session_uid, started_at, game_year, track,
+    /// session_type, game_mode, ai_difficulty, network_game, compound and stint. By default races get the
+    /// positions, practice and qualifying sessions the run, time trials only the session time. The session and
+    /// tyre columns make the rows self-describing when combining the laps of sessions.
+    #[clap(long, env)]
+    lap_columns: Option<ColumnSelection>,
+
+    /// Also append the laps of every session to this CSV, e.g. to collect a whole evening of sessions in one
+    /// file. Each row carries its session's UID, start, track, type, game mode, AI difficulty and whether it
+    /// was online, and the lap's position, phase, compound and stint. An existing file is continued if it has the same columns, otherwise the logger refuses to
+    /// start rather than mixing columns, and the file is never removed by the retention.
+    #[clap(long, env)]
+    rolling_laps: Option<path::PathBuf>,
+
+    /// Columns of the "Results" CSV after position, driver and team, as comma separated keys: grid_position,
+    /// fastest_lap, finish_time, laps, pit_stops, penalties, penalty_time, adjusted_time, status, best_lap,
+    /// best_s1, best_s2, best_s3 and theoretical_best. By default all columns are written.
+    #[clap(long, env)]
+    classification_columns: Option<ColumnSelection>,
+
+    /// Order of events the game sends in the same frame: "sorted" handles them by type, i.e. lights out and
+    /// red flags, collisions, overtakes, penalties, retirements and fastest laps, other events and finally the
+    /// chequered flag and session end, then by vehicle index, so replays reproduce the live output.
+    /// "receipt" handles them as they arrive, without holding them back until the frame is complete.
+    #[clap(long, default_value = "sorted", env)]
+    event_order: EventOrder,
+
+    /// Check the columns of every CSV against the schema of this release before writing it, and add the
+    /// schema version to the filenames, e.g. "Events_v2", so downstream tools notice column changes
+    #[clap(long, env)]
+    schema_check: bool,
+
+    /// Write all events into one chronological "Timeline" CSV when the session ends
+    #[clap(long, env)]
+    timeline: bool,
+
+    /// Publish every event as JSON to Kafka, keyed by the session uid, e.g. "broker1:9092,broker2:9092".
+    /// Needs a build with the "kafka" feature.
+    #[clap(long, env, requires = "kafka_topic")]
+    kafka_brokers: Option<String>,
+
+    /// Kafka topic the events are published to
+    #[clap(long, env, requires = "kafka_brokers")]
+    kafka_topic: Option<String>,
+
+    /// Stream every event as a line of JSON to the processes connected to a Unix domain socket created at
+    /// this path, with the payload published to Kafka. A stale socket file of a previous run is replaced.
+    /// Not available on Windows.
+    #[clap(long, env)]
+    unix_socket: Option<path::PathBuf>,
+
+    /// Push counters and timers to a StatsD endpoint over UDP, e.g. "localhost:8125": the events by type, the
+    /// overtakes by driver and the lap times by driver
+    #[clap(long, env)]
+    statsd_addr: Option<String>,
+
+    /// Serve the metadata of the current session as JSON over HTTP at this address, e.g. "127.0.0.1:8090",
+    /// at GET /metadata, and its live state like the fastest lap holder at GET /session. Requests are answered
+    /// while telemetry arrives.
+    #[clap(long, env)]
+    metadata_http: Option<net::SocketAddr>,
+
+    /// Also write the sessions into an SQLite database for replaying and querying them, with the state of every
+    /// car per frame, the events and the classification. Needs a build with the "sqlite" feature.
+    #[clap(long, env)]
+    replay_db: Option<path::PathBuf>,
+
+    /// Log fuel mix and ERS mode changes of all cars instead of only the player's, e.g. to analyse the AI
+    #[clap(long, env)]
+    all_cars: bool,
+
+    /// Discard the files of sessions with fewer completed laps when they end
+    #[clap(long, default_value = "0", env)]
+    min_session_laps: usize,
+
+    /// Discard the files of sessions shorter than this many seconds when they end
+    #[clap(long, default_value = "0", env)]
+    min_session_secs: u32,
+
+    /// Report the classification's penalty time in milliseconds like the other times instead of seconds
+    #[clap(long, env)]
+    penalty_time_ms: bool,
+
+    /// Sync the session's files to disk when it ends or on shutdown, so they survive a power loss right after
+    #[clap(long, env)]
+    fsync: bool,
+
+    /// Read the last row of each CSV back from disk after every flush and warn if it differs from the row
+    /// written, to detect flaky storage. Costs an extra read per file and flush.
+    #[clap(long, env)]
+    verify_after_write: bool,
+
+    /// Link consecutive sessions on the same track within a few hours as one race weekend, e.g. of a career,
+    /// whose sessions have different UIDs. The race's summary then lists the cars starting behind their
+    /// qualifying position, the penalties file logs them as inferred grid penalties unless the game sent one,
+    /// and the metadata records the linked session. Off by default, so one-off lobbies
+    /// on the same track don't inherit stale data.
+    #[clap(long, env)]
+    link_weekend: bool,
+
+    /// Only log overtakes for this position or better, e.g. 10 for the top 10.
+    /// Combines with the other overtake filters, a row must pass all of them.
+    #[clap(long, env)]
+    min_position: Option<u8>,
+
+    /// Only log overtakes where the cars' speeds differed by at least this many km/h, to drop queue shuffles.
+    /// Combines with the other overtake filters, a row must pass all of them.
+    #[clap(long, env)]
+    min_speed_delta: Option<u16>,
+
+    /// Leave overtakes where either car was in the pit lane out of the events, keeping only the passes on
+    /// track. Logged overtakes mark these in the "Pit-Related" column otherwise.
+    #[clap(long, env)]
+    exclude_pit_overtakes: bool,
+
+    /// Write a "Markers" CSV with the time of each logged overtake since the lights out, to import as markers
+    /// into a video editor timeline synced to the race recording
+    #[clap(long, env)]
+    markers: bool,
+
+    /// Map the significant events onto a race recording when the session ends: overtakes passing the filters,
+    /// collisions, penalties and lead changes. Given as the wall-clock time the recording started, e.g.
+    /// "14:03:27" or an RFC 3339 timestamp, or as the lights out in the recording, e.g. "+4:12", which is also
+    /// what works for replayed captures.
+    #[clap(long, env)]
+    vod_start: Option<VodStart>,
+
+    /// Length of the race recording, e.g. "1:45:00", events after its end are dropped
+    #[clap(long, env, requires = "vod_start")]
+    vod_duration: Option<String>,
+
+    /// File format of the recording markers: a "VodMarkers" CSV, or "Chapters" to paste into a YouTube video
+    /// description
+    #[clap(long, default_value = "csv", env, requires = "vod_start")]
+    vod_format: VodFormat,
+
+    /// Show a desktop notification for each overtake involving the player that passes the overtake filters,
+    /// e.g. as a recap while replaying a capture
+    #[clap(long, env)]
+    notify: bool,
+
+    /// How to identify drivers in all outputs: "name", "number" (e.g. "#44") or "both", which adds the race
+    /// number to the team (e.g. "Mercedes (44)")
+    #[clap(long, default_value = "both", env)]
+    identify_by: IdentifyBy,
+
+    /// Name of drivers the game sends without one, as happens in some online lobbies: "car" for the car index
+    /// (e.g. "Car 5"), "number" for the race number (e.g. "#44") or "blank" to leave them empty
+    #[clap(long, default_value = "car", env)]
+    empty_names: EmptyNames,
+
+    /// Maximum time in seconds between two overtakes of the same cars in opposite roles to flag the second
+    /// one as reversed, i.e. the position was given back
+    #[clap(long, default_value = "20", env)]
+    reversal_window_secs: u32,
+
+    /// Log the weather and temperatures to a "Conditions" CSV whenever they change and at least every
+    /// this many seconds, to follow how the conditions evolve
+    #[clap(long, env)]
+    conditions_interval_secs: Option<u32>,
+
+    /// Show live sector and lap times with their deltas on the console in time trial
+    #[clap(long, env, conflicts_with = "progress")]
+    live_display: bool,
+
+    /// Keep a single progress line on the console, updated in place with the leader's lap, the leader and the
+    /// overtakes logged, as a lightweight alternative to the status interval. Ignored when the output isn't a
+    /// terminal.
+    #[clap(long, env)]
+    progress: bool,
+
+    /// Write a Markdown "Report" of the race with the podium, fastest lap, notable overtakes, retirements and
+    /// penalties when it ends, e.g. for league write-ups
+    #[clap(long, env)]
+    report_md: bool,
+
+    /// Car to follow, by index (e.g. 3) or race number (e.g. #44): its events are highlighted on the console
+    /// and it leads the summary. All cars are still logged, and player-specific outputs still follow the player.
+    #[clap(long, env)]
+    follow_car: Option<FollowCar>,
+
+    /// While spectating, follow the car the camera follows unless --follow-car is given
+    #[clap(long, env)]
+    follow_spectated: bool,
+
+    /// Only log the events involving these drivers, by race number or name, e.g. "#44,Leclerc". Session-wide
+    /// events and outputs not built from events, like laps and results, still cover all cars.
+    #[clap(long, env)]
+    drivers: Option<DriverSelection>,
+
+    /// Remove output files older than this many days, at startup, whenever a session ends and hourly while
+    /// listening. Only files matching the filename template in its directory are touched.
+    #[clap(long, env, visible_alias = "retain-days")]
+    retention_days: Option<u32>,
+
+    /// Keep only this many of the newest output files, counting every file of a session
+    #[clap(long, env)]
+    retention_max_files: Option<usize>,
+
+    /// What to do with old output files: "delete" or "archive", which moves them to an "archive" subdirectory
+    #[clap(long, default_value = "delete", env)]
+    retention_mode: RetentionMode,
+
+    /// Only print which files the retention would remove
+    #[clap(long, env)]
+    retention_dry_run: bool,
+
+    /// CSV of DRS activation zones with the columns "Track", "Start [m]" and "End [m]" in lap distance, for a
+    /// "DRS Zone" column on overtakes. The column stays blank on tracks without zones in the file.
+    #[clap(long, env)]
+    drs_zones: Option<path::PathBuf>,
+
+    /// CSV of corners with the columns "Track", "Corner", "Start [m]" and "End [m]" in lap distance, for a
+    /// "CornerSpeeds" CSV with the player's minimum speed in each corner of every lap. Tracks without corners
+    /// in the file get no rows.
+    #[clap(long, env)]
+    corner_speeds: Option<path::PathBuf>,
+
+    /// Write a "Style" CSV with each car's gear changes, hard braking applications and share of full throttle
+    /// per lap, from the telemetry at whatever rate it arrives. Laps with gaps of over a second in the
+    /// telemetry are flagged as unreliable, cars with restricted telemetry get empty cells.
+    #[clap(long, env)]
+    driving_style: bool,
+
+    /// Width in metres of the "Location Bin" of overtakes, which rounds the lap distance so overtakes at the
+    /// same corner share a location. The summary counts the overtakes per bin.
+    #[clap(long, default_value_t = DEFAULT_BIN_SIZE_M, env)]
+    location_bin_size: u16,
+
+    /// Write a "Heatmap" CSV when the session ends, counting the overtakes in bins of this many metres over
+    /// the whole lap, with the bins also given in percent of the track length to compare tracks
+    #[clap(long, env)]
+    heatmap_bin_size: Option<u16>,
+
+    /// Add a "Grip Advantage" column to the overtakes: a rough estimate in percent of how much more grip the
+    /// overtaker's tyres have, from the compounds, tyre ages and track temperature. A heuristic for spotting
+    /// tyre-driven passes, not data from the game; blank when a tyre is unknown.
+    #[clap(long, env)]
+    grip_advantage: bool,
+
+    /// Add each car's "Final Compound" and "Final Tyre Age" in laps to the results, from its last car status
+    /// while racing, so cars that retired keep the tyres they had when they stopped
+    #[clap(long, env)]
+    final_tyres: bool,
+
+    /// Add driver ids that stay the same across sessions to the events and results, for joining several
+    /// sessions: the id mapped by --driver-id-map, the game's driver for AI cars or else the name
+    #[clap(long, env)]
+    stable_driver_ids: bool,
+
+    /// CSV with the columns "Name" and "ID" mapping online names to stable driver ids, e.g. league member ids
+    #[clap(long, env, requires = "stable_driver_ids")]
+    driver_id_map: Option<path::PathBuf>,
+
+    /// Replace all driver names with pseudonyms like "Driver 01" for publishing the outputs, numbered by
+    /// vehicle index and kept across a weekend linked by --link-weekend. The real names are only written to a
+    /// separate "Mapping" CSV, to leave out when publishing. Team names are kept.
+    #[clap(long, env, conflicts_with = "driver_id_map")]
+    anonymize: bool,
 }
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = AppArgs::parse();
+impl OutputArgs {
+    /// Rejects combinations of options that can't work together, or where an option would have no effect,
+    /// before anything is logged. All problems are reported at once.
+    fn validate(&self) -> std::result::Result<(), String> {
+        let mut problems = Vec::new();
+        if !self.filename_template.contains("{type}") {
+            problems.push("--filename-template needs a {type} placeholder, or all outputs share one file".to_string());
+        }
+        if self.positions_format == PositionsFormat::Compact && !self.player_trace {
+            problems.push("--positions-format only applies to the trace of --player-trace".to_string());
+        }
+        if self.fastest_lap_points.is_some() && self.points_scheme.is_none() && self.points_scheme_file.is_none() {
+            problems.push("--fastest-lap-points needs --points-scheme or --points-scheme-file".to_string());
+        }
+        if self.penalty_time_ms
+            && self.classification_columns.as_ref().is_some_and(|columns| !columns.contains("penalty_time"))
+        {
+            problems.push("--penalty-time-ms needs the penalty_time column in --classification-columns".to_string());
+        }
+        if self.retention_dry_run && self.retention_days.is_none() && self.retention_max_files.is_none() {
+            problems.push("--retention-dry-run needs --retention-days or --retention-max-files".to_string());
+        }
+        if self.min_position == Some(0) {
+            problems.push("--min-position 0 would filter out every overtake, the leader's position is 1".to_string());
+        }
+
+        match problems.len() {
+            0 => Ok(()),
+            1 => Err(problems.remove(0)),
+            _ => Err(format!("Conflicting options:\n  {}", problems.join("\n  "))),
+        }
+    }
+
+    fn session_options(self) -> Result<SessionOptions> {
+        self.validate()?;
+
+        let points_scheme = match (self.points_scheme, self.points_scheme_file) {
+            (Some(scheme), _) => Some(scheme),
+            (None, Some(path)) => Some(fs::read_to_string(path)?.parse::<PointsScheme>()?),
+            (None, None) => None,
+        }
+        .map(|scheme| match self.fastest_lap_points {
+            Some(bonus) => scheme.with_fastest_lap_bonus(bonus),
+            None => scheme,
+        });
+
+        let drs_zones = match &self.drs_zones {
+            Some(path) => DrsZones::from_file(path)?,
+            None => DrsZones::default(),
+        };
+
+        let corners = self.corner_speeds.as_deref().map(Corners::from_file).transpose()?;
+
+        let driver_ids = match (self.stable_driver_ids, &self.driver_id_map) {
+            (true, Some(path)) => Some(DriverIds::from_file(path)?),
+            (true, None) => Some(DriverIds::default()),
+            (false, _) => None,
+        };
+
+        let kafka = match (self.kafka_brokers, self.kafka_topic) {
+            (Some(brokers), Some(topic)) => {
+                kafka::ensure_supported()?;
+                Some(KafkaConfig { brokers, topic })
+            },
+            _ => None,
+        };
+
+        if self.replay_db.is_some() {
+            replaydb::ensure_supported()?;
+        }
+        if self.unix_socket.is_some() {
+            unixsocket::ensure_supported()?;
+        }
+        let statsd = self.statsd_addr.as_deref().map(statsd::resolve).transpose()?;
+        let vod_duration_ms = self.vod_duration.as_deref().map(vod::parse_duration).transpose()?;
+        let vod =
+            self.vod_start.map(|start| VodConfig { start, duration_ms: vod_duration_ms, format: self.vod_format });
+
+        if let Some(lap_columns) = &self.lap_columns {
+            lap_columns.validate(&session::LAP_COLUMNS)?;
+        }
+        if let Some(classification_columns) = &self.classification_columns {
+            classification_columns.validate(&session::CLASSIFICATION_COLUMNS)?;
+        }
+
+        Ok(SessionOptions {
+            player_trace_interval_ms: self.player_trace.then_some(self.player_trace_interval_ms),
+            positions_format: self.positions_format,
+            cross_reference_window_ms: self.cross_reference_window_secs * 1000,
+            points_scheme,
+            filename_template: self.filename_template,
+            log_unknown_events: self.log_unknown_events,
+            log_raw_events: self.log_raw_events,
+            lap_columns: self.lap_columns,
+            rolling_laps: self.rolling_laps,
+            classification_columns: self.classification_columns,
+            event_order: self.event_order,
+            schema_check: self.schema_check,
+            timeline: self.timeline,
+            kafka,
+            replay_db: self.replay_db,
+            unix_socket: self.unix_socket,
+            statsd,
+            metadata_http: self.metadata_http,
+            controls_all_cars: self.all_cars,
+            min_session_laps: self.min_session_laps,
+            min_session_secs: self.min_session_secs,
+            penalty_time_ms: self.penalty_time_ms,
+            fsync: self.fsync,
+            verify_after_write: self.verify_after_write,
+            link_weekend: self.link_weekend,
+            overtake_min_position: self.min_position,
+            overtake_min_speed_delta: self.min_speed_delta,
+            exclude_pit_overtakes: self.exclude_pit_overtakes,
+            markers: self.markers,
+            notify: self.notify,
+            identify_by: self.identify_by,
+            empty_names: self.empty_names,
+            reversal_window_ms: self.reversal_window_secs * 1000,
+            conditions_interval_ms: self.conditions_interval_secs.map(|secs| secs * 1000),
+            live_display: self.live_display,
+            // Redrawing a line in place only works on a terminal, not when the output is piped or redirected
+            progress: self.progress && io::IsTerminal::is_terminal(&io::stdout()),
+            report_md: self.report_md,
+            follow_car: self.follow_car,
+            follow_spectated: self.follow_spectated,
+            drivers: self.drivers,
+            retention: RetentionPolicy {
+                max_age_days: self.retention_days,
+                max_files: self.retention_max_files,
+                mode: self.retention_mode,
+                dry_run: self.retention_dry_run,
+            },
+            drs_zones,
+            corners,
+            driving_style: self.driving_style,
+            location_bin_m: self.location_bin_size,
+            heatmap_bin_m: self.heatmap_bin_size,
+            vod,
+            grip_advantage: self.grip_advantage,
+            final_tyres: self.final_tyres,
+            driver_ids,
+            anonymize: self.anonymize,
+        })
+    }
+}
+
+/// The session options, cleaning up old output files before any new ones are written
+fn startup_session_options(output: OutputArgs) -> Result<SessionOptions> {
+    let options = output.session_options()?;
+    if options.schema_check {
+        schema::verify_registry(&session::OUTPUT_SCHEMAS)?;
+    }
+    if let Some(path) = &options.rolling_laps {
+        rolling::check_header(path, &session::ROLLING_LAP_CSV_HEADERS)?;
+    }
+    let in_use: Vec<path::PathBuf> = options.rolling_laps.iter().cloned().collect();
+    if let Err(err) = options.retention.apply(&options.filename_template, &in_use) {
+        println!("Failed to clean up old output files: {}", err);
+    }
+    Ok(options)
+}
+
+/// Name of the subcommand accepting the given flag, e.g. `listen` for `--listener-port=20778`
+fn subcommand_for_flag(flag: &str) -> Option<String> {
+    let long = flag.strip_prefix("--")?.split('=').next()?;
+
+    AppArgs::command()
+        .get_subcommands()
+        .find(|cmd| cmd.get_arguments().any(|arg| arg.get_long() == Some(long)))
+        .map(|cmd| cmd.get_name().to_string())
+}
+
+fn parse_args() -> AppArgs {
+    AppArgs::try_parse().unwrap_or_else(|err| {
+        if err.kind() == ErrorKind::UnknownArgument {
+            if let Some(ContextValue::String(flag)) = err.get(ContextKind::InvalidArg) {
+                if let Some(subcommand) = subcommand_for_flag(flag) {
+                    eprintln!("note: '{}' is an option of the '{}' subcommand", flag, subcommand);
+                }
+            }
+        }
+        err.exit()
+    })
+}
+
+/// Binds the listener socket with the given receive buffer size
+async fn bind_socket(addr: &str, recv_buffer_bytes: usize) -> Result<UdpSocket> {
+    let addr = tokio::net::lookup_host(addr)
+        .await?
+        .next()
+        .ok_or_else(|| Box::<dyn std::error::Error>::from(format!("Cannot resolve {}", addr)))?;
+
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_recv_buffer_size(recv_buffer_bytes)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+
+    let actual = socket.recv_buffer_size()?;
+    if actual < recv_buffer_bytes {
+        println!("Receive buffer limited to {} bytes by the OS, packet bursts may be dropped", actual);
+    }
+
+    Ok(UdpSocket::from_std(net::UdpSocket::from(socket))?)
+}
+
+async fn listen(mut args: ListenArgs) -> Result<()> {
+    if args.auto {
+        args.apply_auto_preset();
+    }
+
     let telemetry_addr = format!("{}:{}", args.listener_host, args.listener_port);
-    let packet_stream = Stream::new(&telemetry_addr).await?;
+    let socket = bind_socket(&telemetry_addr, args.recv_buffer_bytes).await?;
 
     println!("Collecting telemetry from: {}", telemetry_addr);
 
-    let mut session_state = SessionState::new();
+    let mut capture = args.capture.as_deref().map(CaptureWriter::create).transpose()?;
+    let options = startup_session_options(args.output)?;
+    let (retention, filename_template) = (options.retention.clone(), options.filename_template.clone());
+    let mut pipeline = PacketPipeline::new(options.clone());
+    let mut buf = [0; MAX_DATAGRAM_SIZE];
+    let mut queue = VecDeque::new();
+
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    let mut status_interval = args.status_interval.map(|secs| {
+        let period = time::Duration::from_secs(secs);
+        tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+    });
+    let mut retention_interval = retention
+        .is_enabled()
+        .then(|| tokio::time::interval_at(tokio::time::Instant::now() + RETENTION_INTERVAL, RETENTION_INTERVAL));
+    let idle_timeout = args.idle_timeout.map(time::Duration::from_secs);
+    let mut last_received = None;
 
     loop {
-        match packet_stream.next().await {
-            Ok(p) => match p {
-                Packet::Session(sp) => {
-                    session_state.update_session(sp)?;
-                },
-                Packet::Participants(pp) => {
-                    session_state.cars = pp.participants;
-                },
-                Packet::Event(event) => {
-                    if session_state.is_logging_enabled() {
-                        session_state.handle_overtake(&event)?;
-                    }
-                },
-                Packet::CarTelemetry(ctp) => {
-                    session_state.update_car_speeds(&ctp.car_telemetry_data);
-                },
-                Packet::CarStatus(cs) => {
-                    session_state.car_status = cs.car_status_data;
-                },
-                Packet::LapData(lp) => {
-                    session_state.lap_data = lp.lap_data;
-                },
-                Packet::FinalClassification(fc) => {
-                    session_state.write_final_classification(fc)?;
-                },
-                _ => {},
+        let received = tokio::select! {
+            received = socket.recv(&mut buf) => received,
+            _ = next_tick(&mut status_interval) => {
+                println!("{}", pipeline.status_line());
+                continue;
+            },
+            _ = next_tick(&mut retention_interval) => {
+                if let Err(err) = retention.apply(&filename_template, pipeline.open_files()) {
+                    println!("Failed to clean up old output files: {}", err);
+                }
+                continue;
+            },
+            _ = idle_deadline(last_received, idle_timeout) => {
+                println!("No packets for {} s, closing the session", idle_timeout.unwrap_or_default().as_secs());
+                pipeline.close()?;
+                // Dropped first to release what it holds open, e.g. the event socket
+                drop(pipeline);
+                pipeline = PacketPipeline::new(options.clone());
+                last_received = None;
+                continue;
+            },
+            _ = &mut shutdown => break,
+        };
+
+        match received {
+            Ok(len) => {
+                queue.push_back(buf[..len].to_vec());
+                last_received = Some(tokio::time::Instant::now());
             },
             Err(err) => {
                 println!("{:?}", err);
+                continue;
             },
         }
+
+        // Empty the socket buffer before the comparatively slow processing, so a burst doesn't overflow it
+        loop {
+            match socket.try_recv(&mut buf) {
+                Ok(len) => queue.push_back(buf[..len].to_vec()),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    println!("{:?}", err);
+                    break;
+                },
+            }
+        }
+
+        while let Some(datagram) = queue.pop_front() {
+            let Some(capture) = capture.as_mut() else {
+                pipeline.handle_datagram(&datagram)?;
+                continue;
+            };
+
+            let offset = capture.write(&datagram)?;
+            if let Some(packet) = pipeline.parse_datagram(&datagram) {
+                if let Some(header) = pipeline::packet_header(&packet) {
+                    capture.index(offset, header, &packet);
+                }
+                pipeline.handle_packet(packet)?;
+            }
+        }
+    }
+
+    println!("Shutting down");
+    if let Some(capture) = capture.as_mut() {
+        capture.flush()?;
+    }
+    pipeline.close()
+}
+
+/// Waits until the idle timeout has passed since the last packet, forever without a timeout or while idle
+async fn idle_deadline(last_received: Option<tokio::time::Instant>, timeout: Option<time::Duration>) {
+    match last_received.zip(timeout) {
+        Some((last_received, timeout)) => tokio::time::sleep_until(last_received + timeout).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Waits for the next tick of an optional interval, forever without an interval
+async fn next_tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        },
+        None => std::future::pending().await,
+    }
+}
+
+async fn replay(args: ReplayArgs) -> Result<()> {
+    if let Some(target) = &args.send_to {
+        return send_capture(&args.capture, target, args.speed).await;
+    }
+
+    let mut pipeline = PacketPipeline::new(startup_session_options(args.output)?);
+    let mut reader = CaptureReader::open(&args.capture)?;
+
+    // Without a session, the whole capture is replayed
+    let Some(session_uid) = args.session else {
+        for datagram in reader {
+            pipeline.handle_datagram(&datagram?.data)?;
+        }
+        return pipeline.close();
+    };
+
+    let index = match CaptureIndex::read_current(&args.capture)? {
+        Some(index) => index,
+        None => rebuild_capture_index(&args.capture)?,
+    };
+    let offset = index.offset(session_uid, args.from_lap).ok_or("Session or lap not found in the capture index")?;
+    reader.seek(offset)?;
+
+    for datagram in reader {
+        let Some(packet) = pipeline.parse_datagram(&datagram?.data) else {
+            continue;
+        };
+        if pipeline::packet_header(&packet).is_some_and(|header| header.session_uid != session_uid) {
+            break;
+        }
+        pipeline.handle_packet(packet)?;
+    }
+
+    pipeline.close()
+}
+
+/// Indexes the sessions and laps of a capture from its datagrams and writes the index sidecar, for captures
+/// whose recording ended without writing it
+fn rebuild_capture_index(capture: &path::Path) -> Result<CaptureIndex> {
+    println!("Rebuilding the index of {}", capture.display());
+    let mut index = CaptureIndex::default();
+    let mut reader = CaptureReader::open(capture)?;
+    loop {
+        let offset = reader.position()?;
+        let Some(datagram) = reader.next().transpose()? else {
+            break;
+        };
+        let Some(packet) = pipeline::parse_datagram(&datagram.data) else {
+            continue;
+        };
+        if let Some(header) = pipeline::packet_header(&packet) {
+            index.record(offset, header, &packet);
+        }
+    }
+
+    index.write(capture)?;
+    Ok(index)
+}
+
+/// Sends the datagrams of a capture to a listener, keeping their original spacing scaled by `speed`
+async fn send_capture(capture: &path::Path, target: &str, speed: f64) -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(target).await?;
+
+    let started = time::Instant::now();
+    let mut sent = 0u64;
+    for datagram in CaptureReader::open(capture)? {
+        let datagram = datagram?;
+        if speed > 0.0 {
+            let due = time::Duration::from_micros((datagram.received_us as f64 / speed) as u64);
+            tokio::time::sleep_until((started + due).into()).await;
+        }
+
+        socket.send(&datagram.data).await?;
+        sent += 1;
+    }
+
+    println!("Sent {} datagrams to {} in {:.1} s", sent, target, started.elapsed().as_secs_f64());
+    Ok(())
+}
+
+/// Converts a compact positions file to a CSV file with the same name
+fn export_positions(args: ExportPositionsArgs) -> Result<()> {
+    if !args.csv {
+        return Err("No export format given, use --csv".into());
+    }
+
+    let output = args.file.with_extension("csv");
+    let points = positions::export_csv(&args.file, &output)?;
+    println!("Exported {} positions to {}", points, output.display());
+    Ok(())
+}
+
+/// Writes every listen option that can be set from the environment, with its default where it has one
+fn init(args: InitArgs) -> Result<()> {
+    if args.path.exists() && !args.force {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists, use --force to overwrite it", args.path.display()),
+        )));
+    }
+
+    let mut config = format!("# f1-eventlogger configuration, load with e.g. `set -a; . {}`\n", args.path.display());
+    let command = AppArgs::command();
+    let listen = command.find_subcommand("listen").expect("listen subcommand");
+    for arg in listen.get_arguments() {
+        let Some(env) = arg.get_env() else {
+            continue;
+        };
+
+        config.push('\n');
+        if let Some(help) = arg.get_help() {
+            for line in help.to_string().lines() {
+                config.push_str(&format!("# {}\n", line));
+            }
+        }
+
+        let env = env.to_string_lossy();
+        match arg.get_default_values().first() {
+            Some(default) => config.push_str(&format!("{}={}\n", env, default.to_string_lossy())),
+            None => config.push_str(&format!("# {}=\n", env)),
+        }
+    }
+
+    fs::write(&args.path, config)?;
+    println!("Wrote configuration to {}", args.path.display());
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = parse_args();
+
+    match args.command.unwrap_or(Command::Listen(args.listen)) {
+        Command::Listen(args) => listen(args).await,
+        Command::Replay(args) => replay(args).await,
+        Command::Analyze(args) => analyze::analyze_capture(&args.capture),
+        Command::Init(args) => init(args),
+        Command::ExportPositions(args) => export_positions(args),
+        Command::Selftest(args) => selftest::run(args.output.session_options()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[clap(flatten)]
+        output: OutputArgs,
+    }
+
+    fn validate(args: &[&str]) -> std::result::Result<(), String> {
+        let cli = TestCli::try_parse_from(std::iter::once("f1-eventlogger").chain(args.iter().copied()))
+            .unwrap_or_else(|err| panic!("{:?} should parse: {}", args, err));
+        cli.output.validate()
+    }
+
+    #[test]
+    fn accepts_the_defaults_and_working_combinations() {
+        assert_eq!(validate(&[]), Ok(()));
+        assert_eq!(validate(&["--player-trace", "--positions-format", "compact"]), Ok(()));
+        assert_eq!(validate(&["--points-scheme", "f1-2024", "--fastest-lap-points", "1"]), Ok(()));
+        assert_eq!(
+            validate(&["--penalty-time-ms", "--classification-columns", "position,driver,penalty_time"]),
+            Ok(())
+        );
+        assert_eq!(validate(&["--retention-dry-run", "--retention-days", "30"]), Ok(()));
+        assert_eq!(validate(&["--min-position", "1"]), Ok(()));
+    }
+
+    #[test]
+    fn rejects_each_conflicting_combination() {
+        let rejected = [
+            (&["--filename-template", "race"][..], "--filename-template"),
+            (&["--positions-format", "compact"], "--positions-format"),
+            (&["--fastest-lap-points", "1"], "--fastest-lap-points"),
+            (&["--penalty-time-ms", "--classification-columns", "position,driver"], "--penalty-time-ms"),
+            (&["--retention-dry-run"], "--retention-dry-run"),
+            (&["--min-position", "0"], "--min-position"),
+        ];
+
+        for (args, option) in rejected {
+            let err = validate(args).expect_err(option);
+            assert!(err.starts_with(option), "{:?}: {}", args, err);
+        }
+    }
+
+    #[test]
+    fn reports_all_problems_at_once() {
+        let err = validate(&["--retention-dry-run", "--min-position", "0"]).unwrap_err();
+        assert_eq!(
+            err,
+            "Conflicting options:\n  --retention-dry-run needs --retention-days or --retention-max-files\n  \
+             --min-position 0 would filter out every overtake, the leader's position is 1"
+        );
     }
 }
@@ -0,0 +1,60 @@
+use f1_telemetry::packet::session::PacketSessionData;
+use serde::Serialize;
+
+use crate::sessions;
+
+/// First packet format with the AI difficulty in the session packet
+const AI_DIFFICULTY_SINCE: u16 = 2021;
+
+/// First packet format with the online flag in the session packet
+const NETWORK_GAME_SINCE: u16 = 2018;
+
+/// Session context written to the metadata sidecar next to the CSV files.
+///
+/// Fields missing from older packet formats are omitted from the JSON rather than zero-filled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct SessionMetadata {
+    pub(crate) session_uid: u64,
+    pub(crate) started_at: String,
+    pub(crate) game_year: u16,
+    pub(crate) track: String,
+    pub(crate) session_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rule_set: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) game_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) ai_difficulty: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) network_game: Option<bool>,
+    /// Set once the classification is written, `true` if the lap tracking missed laps of any car
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) lap_history_incomplete: Option<bool>,
+    /// UID of the previous session of the race weekend, with `--link-weekend`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) linked_session_uid: Option<u64>,
+    /// Car telemetry packets per second, measured at the session start and again after gaps in the data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) telemetry_rate_hz: Option<u32>,
+}
+
+impl SessionMetadata {
+    pub(crate) fn new(session_data: &PacketSessionData, started_at: String) -> Self {
+        // The telemetry crate zero-fills fields the packet format lacks
+        let packet_format = session_data.header.packet_format;
+        Self {
+            session_uid: session_data.header.session_uid,
+            started_at,
+            game_year: packet_format,
+            track: session_data.track.name().to_string(),
+            session_type: sessions::session_name(&session_data.session_type).to_string(),
+            rule_set: session_data.rule_set.map(|rule_set| format!("{:?}", rule_set)),
+            game_mode: session_data.game_mode.map(|game_mode| format!("{:?}", game_mode)),
+            ai_difficulty: (packet_format >= AI_DIFFICULTY_SINCE).then_some(session_data.ai_difficulty),
+            network_game: (packet_format >= NETWORK_GAME_SINCE).then_some(session_data.network_game),
+            lap_history_incomplete: None,
+            linked_session_uid: None,
+            telemetry_rate_hz: None,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::metadata::SessionMetadata;
+
+/// Path the metadata is served at
+const METADATA_PATH: &str = "/metadata";
+
+/// Path the live state of the session is served at
+const SESSION_PATH: &str = "/session";
+
+/// How long a client may take to send its request or read the answer, as requests are answered in between
+/// the packets
+const CLIENT_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Live state of the current session, changing while it runs unlike the metadata
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub(crate) struct LiveSession {
+    pub(crate) fastest_lap: Option<LiveFastestLap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct LiveFastestLap {
+    pub(crate) driver: String,
+    pub(crate) team: String,
+    pub(crate) lap_time_ms: u32,
+    /// Whether the holder currently runs in a points paying position, `None` while unknown
+    pub(crate) in_points: Option<bool>,
+    /// Whether a fastest lap event named the holder, rather than only the completed laps
+    pub(crate) confirmed: bool,
+}
+
+/// Serves the metadata of the current session as JSON at `GET /metadata`, e.g. `curl localhost:8090/metadata`,
+/// with the fields of the sidecar, and its live state like the fastest lap holder at `GET /session`. Requests
+/// are answered as packets arrive, so the packet handling needs no thread of its own, but requests wait while
+/// no telemetry is received. Without a session the answer is a 404.
+pub(crate) struct MetadataServer {
+    listener: TcpListener,
+}
+
+impl MetadataServer {
+    pub(crate) fn bind(addr: SocketAddr) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr)
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(|err| format!("Failed to serve the session metadata on {}: {}", addr, err))?;
+        println!("Serving the session metadata at http://{}{}", addr, METADATA_PATH);
+
+        Ok(Self { listener })
+    }
+
+    /// Answers the pending requests
+    pub(crate) fn serve(&mut self, metadata: Option<&SessionMetadata>, live: Option<&LiveSession>) {
+        loop {
+            match self.listener.accept() {
+                Ok((client, _)) => {
+                    if let Err(err) = respond(client, metadata, live) {
+                        println!("Failed to answer a metadata request: {}", err);
+                    }
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    println!("Failed to accept a metadata request: {}", err);
+                    break;
+                },
+            }
+        }
+    }
+}
+
+fn respond(mut client: TcpStream, metadata: Option<&SessionMetadata>, live: Option<&LiveSession>) -> io::Result<()> {
+    client.set_nonblocking(false)?;
+    client.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    client.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    // Only the request line is looked at, e.g. "GET /metadata HTTP/1.1"
+    let mut request = [0u8; 1024];
+    let len = client.read(&mut request)?;
+    let request = String::from_utf8_lossy(&request[..len]);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+
+    let no_session = || ("404 Not Found", r#"{"error":"no session"}"#.to_string());
+    let (status, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(METADATA_PATH)) => match metadata {
+            Some(metadata) => ("200 OK", serde_json::to_string_pretty(metadata)?),
+            None => no_session(),
+        },
+        (Some("GET"), Some(SESSION_PATH)) => match live {
+            Some(live) => ("200 OK", serde_json::to_string_pretty(live)?),
+            None => no_session(),
+        },
+        (Some("GET"), _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => ("405 Method Not Allowed", r#"{"error":"method not allowed"}"#.to_string()),
+    };
+
+    write!(
+        client,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    client.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(server: &mut MetadataServer, path: &str, live: Option<&LiveSession>) -> String {
+        let mut client = TcpStream::connect(server.listener.local_addr().unwrap()).unwrap();
+        write!(client, "GET {} HTTP/1.1\r\n\r\n", path).unwrap();
+        server.serve(None, live);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_live_session() {
+        let mut server = MetadataServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let live = LiveSession {
+            fastest_lap: Some(LiveFastestLap {
+                driver: "Leclerc".to_string(),
+                team: "Ferrari".to_string(),
+                lap_time_ms: 91_234,
+                in_points: Some(true),
+                confirmed: true,
+            }),
+        };
+
+        let response = get(&mut server, SESSION_PATH, Some(&live));
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#""lap_time_ms": 91234"#));
+
+        assert!(get(&mut server, SESSION_PATH, None).starts_with("HTTP/1.1 404 Not Found"));
+        assert!(get(&mut server, METADATA_PATH, Some(&live)).contains("no session"));
+    }
+}
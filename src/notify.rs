@@ -0,0 +1,26 @@
+/// Desktop notifications for the player's overtakes. Platforms or sessions without a notification service,
+/// e.g. a headless Linux box, get a single warning after which notifications are skipped.
+#[derive(Debug)]
+pub(crate) struct DesktopNotifier {
+    available: bool,
+}
+
+impl Default for DesktopNotifier {
+    fn default() -> Self {
+        Self { available: true }
+    }
+}
+
+impl DesktopNotifier {
+    pub(crate) fn notify(&mut self, summary: &str, body: &str) {
+        if !self.available {
+            return;
+        }
+
+        let result = notify_rust::Notification::new().appname("F1 Event Logger").summary(summary).body(body).show();
+        if let Err(err) = result {
+            println!("WARNING: Desktop notifications are not available, disabling them: {}", err);
+            self.available = false;
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::str::FromStr;
+
+use f1_telemetry::packet::event::{Event, PacketEventData};
+use f1_telemetry::packet::header::PacketHeader;
+
+/// Order in which the events of one frame are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum EventOrder {
+    /// By [`event_priority`], then by vehicle index, so replays and live sessions write the same output
+    #[default]
+    Sorted,
+    /// As received, which can differ between runs when the game sends several events for a frame
+    Receipt,
+}
+
+impl FromStr for EventOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "sorted" => Ok(Self::Sorted),
+            "receipt" => Ok(Self::Receipt),
+            other => Err(format!("Invalid event order '{}', expected sorted or receipt", other)),
+        }
+    }
+}
+
+/// Rank of an event within its frame, lowest first:
+///
+/// 0. lights out and red flags, so the other events of the frame see the new session phase
+/// 1. collisions, so an overtake in the same frame can reference them
+/// 2. overtakes
+/// 3. penalties and served penalties
+/// 4. retirements and fastest laps
+/// 5. any other event
+/// 6. chequered flag and session end, which close off the frame
+fn event_priority(event: &Event) -> u8 {
+    match event {
+        Event::LightsOut | Event::RedFlag => 0,
+        Event::Collision(_) => 1,
+        Event::Overtake(_) => 2,
+        Event::Penalty(_) | Event::DriveThroughServed(_) | Event::StopGoServed(_) => 3,
+        Event::Retirement(_) | Event::FastestLap(_) => 4,
+        Event::ChequeredFlag | Event::SessionEnded => 6,
+        _ => 5,
+    }
+}
+
+/// Vehicle the event is about, the first one for events involving two cars
+fn event_vehicle(event: &Event) -> Option<u8> {
+    match event {
+        Event::Collision(collision) => Some(collision.vehicle_1_idx),
+        Event::Overtake(overtake) => Some(overtake.overtaking_vehicle_idx),
+        Event::Penalty(penalty) => Some(penalty.vehicle_idx),
+        Event::DriveThroughServed(served) => Some(served.vehicle_idx),
+        Event::StopGoServed(served) => Some(served.vehicle_idx),
+        Event::Retirement(retirement) => Some(retirement.vehicle_idx),
+        Event::FastestLap(fastest_lap) => Some(fastest_lap.vehicle_idx),
+        _ => None,
+    }
+}
+
+/// Holds back the events of the current frame until a packet of another frame arrives, then hands them
+/// out in sorted order. Events without a vehicle sort before those with one of the same priority, events
+/// with equal keys keep their receipt order.
+#[derive(Debug, Default)]
+pub(crate) struct FrameEventBuffer {
+    frame: Option<(u64, u32)>,
+    events: Vec<PacketEventData>,
+}
+
+impl FrameEventBuffer {
+    pub(crate) fn push(&mut self, event: PacketEventData) {
+        self.frame = Some((event.header.session_uid, event.header.frame_identifier));
+        self.events.push(event);
+    }
+
+    /// The buffered events if the header belongs to another frame, otherwise none
+    pub(crate) fn take_completed(&mut self, header: &PacketHeader) -> Vec<PacketEventData> {
+        match self.frame {
+            Some(frame) if frame != (header.session_uid, header.frame_identifier) => self.take_all(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// All buffered events, e.g. before the final classification or when the session closes
+    pub(crate) fn take_all(&mut self) -> Vec<PacketEventData> {
+        self.frame = None;
+        let mut events = std::mem::take(&mut self.events);
+        events.sort_by_key(|event| (event_priority(&event.event), event_vehicle(&event.event)));
+        events
+    }
+}
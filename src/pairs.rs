@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+
+/// Short-lived memory of events between two cars, matched on the unordered pair of car indexes unless
+/// created with [`RecentPairEvents::ordered`]
+#[derive(Debug)]
+pub(crate) struct RecentPairEvents {
+    window_ms: u32,
+    ordered: bool,
+    events: VecDeque<((u8, u8), u32)>,
+}
+
+impl RecentPairEvents {
+    pub(crate) fn new(window_ms: u32) -> Self {
+        Self { window_ms, ordered: false, events: VecDeque::new() }
+    }
+
+    /// Matches events only between the cars in the same roles, e.g. the same overtaker and overtakee
+    pub(crate) fn ordered(window_ms: u32) -> Self {
+        Self { window_ms, ordered: true, events: VecDeque::new() }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub(crate) fn push(&mut self, car_a: u8, car_b: u8, session_time: u32) {
+        self.prune(session_time);
+        self.events.push_back((self.pair(car_a, car_b), session_time));
+    }
+
+    /// Session time of the latest event between both cars within the window before `session_time`
+    pub(crate) fn find(&self, car_a: u8, car_b: u8, session_time: u32) -> Option<u32> {
+        let pair = self.pair(car_a, car_b);
+        self.events
+            .iter()
+            .rev()
+            .take_while(|(_, time)| session_time.saturating_sub(*time) <= self.window_ms)
+            .find(|(other, _)| *other == pair)
+            .map(|(_, time)| *time)
+    }
+
+    fn pair(&self, car_a: u8, car_b: u8) -> (u8, u8) {
+        if self.ordered {
+            (car_a, car_b)
+        } else {
+            (car_a.min(car_b), car_a.max(car_b))
+        }
+    }
+
+    fn prune(&mut self, session_time: u32) {
+        while self.events.front().is_some_and(|(_, time)| session_time.saturating_sub(*time) > self.window_ms) {
+            self.events.pop_front();
+        }
+    }
+}
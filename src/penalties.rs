@@ -0,0 +1,126 @@
+use f1_telemetry::packet::event::PenaltyType;
+
+/// Penalties issued to and served by one car during the race
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct CarPenalties {
+    issued: u32,
+    /// Seconds of the time penalties issued
+    time_secs: u32,
+    /// Drive throughs and stop-go penalties issued
+    in_race: u32,
+    served: u32,
+}
+
+/// Comparison of the logged penalties of a car with its classification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Reconciliation {
+    pub(crate) issued: u32,
+    pub(crate) classified: u8,
+    pub(crate) time_issued_secs: u32,
+    pub(crate) served: u32,
+    pub(crate) unserved: u32,
+    pub(crate) time_added_secs: u8,
+}
+
+impl Reconciliation {
+    /// Why the classification doesn't add up with the logged penalties, usually because a penalty event was
+    /// missed. Time penalties may have been served at a pit stop, so less added time than issued is fine,
+    /// while unserved drive throughs and stop-go penalties are converted to time.
+    pub(crate) fn mismatch(&self) -> Option<&'static str> {
+        if self.classified as u32 > self.issued {
+            Some("more penalties classified than logged")
+        } else if self.time_added_secs as u32 > self.time_issued_secs && self.unserved == 0 {
+            Some("time added without a logged penalty")
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.issued == 0 && self.classified == 0 && self.served == 0 && self.time_added_secs == 0
+    }
+}
+
+/// Tracks the penalties of all cars, to reconcile them with the penalties the classification reports
+#[derive(Debug, Default)]
+pub(crate) struct PenaltyLedger {
+    cars: Vec<CarPenalties>,
+}
+
+impl PenaltyLedger {
+    pub(crate) fn clear(&mut self) {
+        self.cars.clear();
+    }
+
+    fn car_mut(&mut self, car_idx: usize) -> &mut CarPenalties {
+        if self.cars.len() <= car_idx {
+            self.cars.resize(car_idx + 1, CarPenalties::default());
+        }
+        &mut self.cars[car_idx]
+    }
+
+    /// Records an issued penalty. Only drive throughs, stop-go and time penalties count, as only those are
+    /// reported by the classification.
+    pub(crate) fn record_issued(&mut self, car_idx: usize, penalty_type: &PenaltyType, time_secs: Option<u8>) {
+        let car = self.car_mut(car_idx);
+        match penalty_type {
+            PenaltyType::DriveThrough | PenaltyType::StopGo => {
+                car.issued += 1;
+                car.in_race += 1;
+            },
+            PenaltyType::TimePenalty => {
+                car.issued += 1;
+                car.time_secs += time_secs.unwrap_or_default() as u32;
+            },
+            _ => {},
+        }
+    }
+
+    pub(crate) fn record_served(&mut self, car_idx: usize) {
+        self.car_mut(car_idx).served += 1;
+    }
+
+    pub(crate) fn reconcile(&self, car_idx: usize, classified: u8, time_added_secs: u8) -> Reconciliation {
+        let car = self.cars.get(car_idx).copied().unwrap_or_default();
+        Reconciliation {
+            issued: car.issued,
+            classified,
+            time_issued_secs: car.time_secs,
+            served: car.served,
+            unserved: car.in_race.saturating_sub(car.served),
+            time_added_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_penalties_the_classification_reports() {
+        let mut ledger = PenaltyLedger::default();
+        ledger.record_issued(1, &PenaltyType::DriveThrough, None);
+        ledger.record_issued(1, &PenaltyType::StopGo, Some(10));
+        ledger.record_issued(1, &PenaltyType::TimePenalty, Some(5));
+        ledger.record_served(1);
+
+        let reconciliation = ledger.reconcile(1, 3, 5);
+        assert_eq!(reconciliation.issued, 3);
+        assert_eq!(reconciliation.time_issued_secs, 5);
+        assert_eq!(reconciliation.unserved, 1);
+        assert_eq!(reconciliation.mismatch(), None);
+    }
+
+    #[test]
+    fn ignores_other_penalty_types() {
+        let mut ledger = PenaltyLedger::default();
+        // Their names contain "Time" but they add no time to the result
+        ledger.record_issued(0, &PenaltyType::ParkedTooLongTimer, Some(3));
+        ledger.record_issued(0, &PenaltyType::BlackFlagTimer, Some(3));
+        ledger.record_issued(0, &PenaltyType::Warning, None);
+        ledger.record_issued(0, &PenaltyType::GridPenalty, Some(3));
+
+        assert!(ledger.reconcile(0, 0, 0).is_empty());
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+
+/// Performance figures of one car over the session, `None` where it has no data
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CarPerformance {
+    pub(crate) best_sectors: Option<[u32; 3]>,
+    pub(crate) top_speed: Option<u16>,
+    /// Average time of the clean laps
+    pub(crate) race_pace_ms: Option<f64>,
+}
+
+/// Averages over the cars of a team, for checking the parity of balanced multiclass lobbies
+#[derive(Debug, Clone)]
+pub(crate) struct TeamPerformance {
+    pub(crate) team: String,
+    pub(crate) drivers: usize,
+    pub(crate) best_sectors_ms: [Option<f64>; 3],
+    pub(crate) top_speed: Option<f64>,
+    pub(crate) race_pace_ms: Option<f64>,
+}
+
+/// Averages the cars' figures by team key, each over the cars that have it, ordered by the team key
+pub(crate) fn by_team(cars: impl IntoIterator<Item = (String, CarPerformance)>) -> Vec<TeamPerformance> {
+    let mut teams: BTreeMap<String, Vec<CarPerformance>> = BTreeMap::new();
+    for (team, car) in cars {
+        teams.entry(team).or_default().push(car);
+    }
+
+    teams
+        .into_iter()
+        .map(|(team, cars)| TeamPerformance {
+            drivers: cars.len(),
+            best_sectors_ms: [0, 1, 2].map(|sector| {
+                average(cars.iter().filter_map(|car| car.best_sectors.map(|sectors| sectors[sector] as f64)))
+            }),
+            top_speed: average(cars.iter().filter_map(|car| car.top_speed.map(f64::from))),
+            race_pace_ms: average(cars.iter().filter_map(|car| car.race_pace_ms)),
+            team,
+        })
+        .collect()
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
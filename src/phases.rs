@@ -0,0 +1,72 @@
+use f1_telemetry::packet::session::SafetyCarStatus;
+
+/// Race phase as given by the safety car status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    Green,
+    SafetyCar,
+    VirtualSafetyCar,
+    FormationLap,
+}
+
+const PHASES: [Phase; 4] = [Phase::Green, Phase::SafetyCar, Phase::VirtualSafetyCar, Phase::FormationLap];
+
+impl Phase {
+    pub(crate) fn from_safety_car_status(status: &SafetyCarStatus) -> Self {
+        match status {
+            SafetyCarStatus::NoSafetyCar => Phase::Green,
+            SafetyCarStatus::FullSafetyCar => Phase::SafetyCar,
+            SafetyCarStatus::VirtualSafetyCar => Phase::VirtualSafetyCar,
+            SafetyCarStatus::FormationLap => Phase::FormationLap,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Phase::Green => "Green",
+            Phase::SafetyCar => "SC",
+            Phase::VirtualSafetyCar => "VSC",
+            Phase::FormationLap => "Formation",
+        }
+    }
+}
+
+/// Time a car spent in each phase during one lap, and whether a red flag interrupted the lap
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LapPhases {
+    durations_ms: [u32; PHASES.len()],
+    red_flag: bool,
+}
+
+impl LapPhases {
+    pub(crate) fn add(&mut self, phase: Phase, duration_ms: u32) {
+        let i = PHASES.iter().position(|&other| other == phase).unwrap_or_default();
+        self.durations_ms[i] += duration_ms;
+    }
+
+    pub(crate) fn interrupt(&mut self) {
+        self.red_flag = true;
+    }
+
+    /// Whether the whole lap was run under green flag conditions, the basis of all pace statistics
+    pub(crate) fn is_green(&self) -> bool {
+        !self.red_flag && self.durations_ms.iter().skip(1).all(|&duration| duration == 0)
+    }
+
+    /// The phases covering the lap by decreasing duration, e.g. "Green/SC" for a lap mostly run under
+    /// green flag before the safety car was deployed
+    pub(crate) fn label(&self) -> String {
+        if self.red_flag {
+            return "Red-interrupted".to_string();
+        }
+
+        let mut phases: Vec<(Phase, u32)> =
+            PHASES.iter().copied().zip(self.durations_ms).filter(|&(_, duration)| duration > 0).collect();
+        phases.sort_by_key(|&(_, duration)| std::cmp::Reverse(duration));
+
+        if phases.is_empty() {
+            return Phase::Green.label().to_string();
+        }
+        phases.iter().map(|(phase, _)| phase.label()).collect::<Vec<_>>().join("/")
+    }
+}
@@ -0,0 +1,179 @@
+use std::path;
+
+use f1_telemetry::packet::event::PacketEventData;
+use f1_telemetry::packet::header::PacketHeader;
+use f1_telemetry::packet::{parse_packet, Packet};
+
+use crate::dedup::EventDeduplicator;
+use crate::drops::DropTracker;
+use crate::ordering::{EventOrder, FrameEventBuffer};
+use crate::rawevents::RawEvent;
+use crate::session::{SessionOptions, SessionState};
+use crate::status::PacketCounters;
+use crate::Result;
+
+pub(crate) fn packet_header(packet: &Packet) -> Option<&PacketHeader> {
+    match packet {
+        Packet::Motion(p) => Some(&p.header),
+        Packet::Session(p) => Some(&p.header),
+        Packet::LapData(p) => Some(&p.header),
+        Packet::Event(p) => Some(&p.header),
+        Packet::Participants(p) => Some(&p.header),
+        Packet::CarTelemetry(p) => Some(&p.header),
+        Packet::CarStatus(p) => Some(&p.header),
+        Packet::FinalClassification(p) => Some(&p.header),
+        _ => None,
+    }
+}
+
+/// Parses a raw datagram, reporting malformed datagrams
+pub(crate) fn parse_datagram(data: &[u8]) -> Option<Packet> {
+    match parse_packet(data.len(), data) {
+        Ok(packet) => Some(packet),
+        Err(err) => {
+            println!("{:?}", err);
+            None
+        },
+    }
+}
+
+/// Feeds packets into the session state, shared by live listening and replaying captures
+pub(crate) struct PacketPipeline {
+    session_state: SessionState,
+    event_deduplicator: EventDeduplicator,
+    /// Frame identifier and code of the latest event datagram, for deduplicating the typed event parsed from it
+    raw_event_code: Option<(u32, String)>,
+    drops: DropTracker,
+    event_order: EventOrder,
+    frame_events: FrameEventBuffer,
+    counters: PacketCounters,
+}
+
+impl PacketPipeline {
+    pub(crate) fn new(options: SessionOptions) -> Self {
+        Self {
+            event_order: options.event_order,
+            session_state: SessionState::new(options),
+            event_deduplicator: EventDeduplicator::default(),
+            raw_event_code: None,
+            drops: DropTracker::default(),
+            frame_events: FrameEventBuffer::default(),
+            counters: PacketCounters::default(),
+        }
+    }
+
+    /// Parses a raw datagram and handles the packet. Malformed datagrams are reported and skipped.
+    pub(crate) fn handle_datagram(&mut self, data: &[u8]) -> Result<()> {
+        match self.parse_datagram(data) {
+            Some(packet) => self.handle_packet(packet),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses a raw datagram like [`parse_datagram`], logging event packets from their bytes first, so event
+    /// codes the telemetry crate can't parse are logged as well
+    pub(crate) fn parse_datagram(&mut self, data: &[u8]) -> Option<Packet> {
+        self.raw_event_code = None;
+        if let Some(event) = RawEvent::parse(data) {
+            self.session_state.log_raw_event(&event);
+            self.raw_event_code = Some((event.frame_identifier, event.code));
+        }
+        parse_datagram(data)
+    }
+
+    pub(crate) fn handle_packet(&mut self, packet: Packet) -> Result<()> {
+        // The events of a frame are complete once a packet of another frame arrives
+        if let Some(header) = packet_header(&packet) {
+            let events = self.frame_events.take_completed(header);
+            self.handle_events(events)?;
+        }
+        if let Packet::FinalClassification(_) = packet {
+            let events = self.frame_events.take_all();
+            self.handle_events(events)?;
+        }
+
+        let session_state = &mut self.session_state;
+
+        if let Some(header) = packet_header(&packet) {
+            session_state.update_from_header(header);
+            self.drops.record(header);
+            self.counters.record(header);
+        }
+        session_state.observe(&packet);
+        session_state.serve_metadata();
+
+        match packet {
+            Packet::Motion(mp) => {
+                session_state.update_player_trace(&mp)?;
+            },
+            Packet::Session(sp) => {
+                session_state.update_session(sp)?;
+            },
+            Packet::Participants(pp) => {
+                session_state.update_participants(pp);
+            },
+            Packet::Event(event) => {
+                let raw_code = self.raw_event_code.take().filter(|(frame, _)| *frame == event.header.frame_identifier);
+                if !self.event_deduplicator.is_duplicate(&event, raw_code.as_ref().map(|(_, code)| code.as_str())) {
+                    match self.event_order {
+                        EventOrder::Sorted => self.frame_events.push(event),
+                        EventOrder::Receipt => self.handle_events(vec![event])?,
+                    }
+                }
+            },
+            Packet::CarTelemetry(ctp) => {
+                session_state.update_car_speeds(&ctp.car_telemetry_data);
+                session_state.update_corner_speeds()?;
+                session_state.update_telemetry_rate(ctp.header.session_time)?;
+            },
+            Packet::CarStatus(cs) => {
+                session_state.update_car_status(cs)?;
+            },
+            Packet::LapData(lp) => {
+                session_state.update_lap_data(lp)?;
+            },
+            Packet::FinalClassification(fc) if session_state.is_repeated_classification(&fc) => {
+                println!("Ignoring a repeated final classification");
+            },
+            Packet::FinalClassification(fc) => {
+                session_state.write_race_report(&fc)?;
+                session_state.write_final_classification(fc)?;
+                session_state.write_pit_stops()?;
+                session_state.write_consistency()?;
+                session_state.write_performance()?;
+                session_state.write_lap_positions()?;
+                session_state.write_session_summary()?;
+            },
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    fn handle_events(&mut self, events: Vec<PacketEventData>) -> Result<()> {
+        for event in events {
+            if self.session_state.is_logging_enabled() {
+                self.session_state.handle_event(&event)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Single line heartbeat with the packet rates since the previous status line and the logging progress
+    pub(crate) fn status_line(&mut self) -> String {
+        format!("Status: {} | {}", self.counters.take_status(), self.session_state.status())
+    }
+
+    /// Files written by the current session, which the retention must leave alone
+    pub(crate) fn open_files(&self) -> &[path::PathBuf] {
+        self.session_state.open_files()
+    }
+
+    /// Finishes the current session, e.g. on shutdown or at the end of a replay
+    pub(crate) fn close(&mut self) -> Result<()> {
+        let events = self.frame_events.take_all();
+        self.handle_events(events)?;
+        println!("{}", self.drops.report());
+        Ok(self.session_state.shutdown()?)
+    }
+}
@@ -0,0 +1,236 @@
+use f1_telemetry::packet::car_status::CarStatusData;
+use f1_telemetry::packet::lap::{LapData, PitStatus};
+
+use crate::laps::LapSnapshot;
+
+/// Laps averaged on either side of a pit stop to compare the pace before and after it
+const PACE_WINDOW_LAPS: u8 = 2;
+
+/// Tyres fitted to a car at some point, from its car status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TyreSet {
+    pub(crate) compound: String,
+    pub(crate) age_laps: Option<u8>,
+}
+
+impl TyreSet {
+    pub(crate) fn from_status(status: &CarStatusData) -> Self {
+        Self { compound: status.visual_tyre_compound.name().to_string(), age_laps: status.tyre_age_laps }
+    }
+}
+
+/// Pace of a car around one of its pit stops: the laps before the in lap against the first flying laps after
+/// the out lap. The in and out laps themselves are only counted in the pit loss.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct StopPace {
+    pub(crate) before_ms: f64,
+    pub(crate) after_ms: f64,
+    /// Time of the in and out laps over the same number of laps at the pace before the stop
+    pub(crate) pit_loss_ms: i64,
+    /// Time gained on the rival over the compared laps after the stop, from the change of both cars' pace.
+    /// `None` without a rival, or if its laps weren't clean, e.g. because it pitted as well.
+    pub(crate) net_swing_ms: Option<f64>,
+}
+
+/// Outcome of comparing the pace around a pit stop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PaceWindow {
+    Quantified(StopPace),
+    /// A lap of the window wasn't fully run under green flag, so the pace says nothing about the stop
+    Neutralised,
+    /// A lap of the window is missing or wasn't clean, e.g. the session ended or the car stopped again
+    Incomplete,
+}
+
+fn find_lap(laps: &[LapSnapshot], lap: u8) -> Option<&LapSnapshot> {
+    laps.iter().find(|snapshot| snapshot.lap == lap)
+}
+
+/// A single visit of a car to the pit lane
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PitStop {
+    pub(crate) car_idx: usize,
+    pub(crate) lap: u8,
+    pub(crate) entry_time: u32,
+    pub(crate) exit_time: u32,
+    pub(crate) stationary_ms: u32,
+    /// Whether a drive-through or stop-go penalty was served during the visit
+    pub(crate) penalty: bool,
+    /// Tyres on the car when it entered the pit lane, i.e. the ones discarded if they were changed
+    pub(crate) tyres_before: Option<TyreSet>,
+    /// Tyres on the car when it left the pit lane
+    pub(crate) tyres_after: Option<TyreSet>,
+    entry_distance: f32,
+    exit_distance: f32,
+    /// Last lap time before entering the pit lane, used as reference for the car's racing speed
+    reference_lap_time_ms: u32,
+    stationary_since: Option<u32>,
+}
+
+impl PitStop {
+    pub(crate) fn pit_lane_ms(&self) -> u32 {
+        self.exit_time.saturating_sub(self.entry_time)
+    }
+
+    /// Time lost compared to a car covering the same lap distance span at racing speed
+    pub(crate) fn time_loss_ms(&self, track_length: u16) -> Option<u32> {
+        if self.reference_lap_time_ms == 0 || track_length == 0 {
+            return None;
+        }
+
+        // The pit lane usually crosses the start/finish line, wrapping the lap distance
+        let mut distance = self.exit_distance - self.entry_distance;
+        if distance < 0.0 {
+            distance += track_length as f32;
+        }
+
+        let racing_ms = distance / track_length as f32 * self.reference_lap_time_ms as f32;
+        Some(self.pit_lane_ms().saturating_sub(racing_ms as u32))
+    }
+
+    /// Compares the car's pace around the stop, and the change of pace against the rival's laps over the same
+    /// lap numbers, typically the car ahead when undercutting it
+    pub(crate) fn pace_window(&self, laps: &[LapSnapshot], rival_laps: Option<&[LapSnapshot]>) -> PaceWindow {
+        let (Some(first), Some(last)) =
+            (self.lap.checked_sub(PACE_WINDOW_LAPS), self.lap.checked_add(PACE_WINDOW_LAPS + 1))
+        else {
+            return PaceWindow::Incomplete;
+        };
+
+        if (first..=last).filter_map(|lap| find_lap(laps, lap)).any(|snapshot| !snapshot.phases.is_green()) {
+            return PaceWindow::Neutralised;
+        }
+
+        let out_lap = self.lap + 1;
+        let average = |laps: &[LapSnapshot], from: u8| {
+            let times: Option<Vec<u32>> = (from..from + PACE_WINDOW_LAPS)
+                .map(|lap| find_lap(laps, lap).filter(|snapshot| snapshot.clean).map(|snapshot| snapshot.lap_time_ms))
+                .collect();
+            times.map(|times| times.iter().map(|&time| time as f64).sum::<f64>() / times.len() as f64)
+        };
+        // The in and out lap, against as many laps at the pace before the stop
+        let pit_laps = [self.lap, out_lap];
+        let pit_laps_ms: Option<u32> = pit_laps
+            .iter()
+            .map(|&lap| find_lap(laps, lap).map(|snapshot| snapshot.lap_time_ms).filter(|&time| time > 0))
+            .sum();
+
+        let (Some(before_ms), Some(after_ms), Some(pit_laps_ms)) =
+            (average(laps, first), average(laps, out_lap + 1), pit_laps_ms)
+        else {
+            return PaceWindow::Incomplete;
+        };
+
+        let net_swing_ms = rival_laps.and_then(|rival_laps| {
+            let rival_before_ms = average(rival_laps, first)?;
+            let rival_after_ms = average(rival_laps, out_lap + 1)?;
+            Some(((rival_after_ms - rival_before_ms) - (after_ms - before_ms)) * PACE_WINDOW_LAPS as f64)
+        });
+
+        PaceWindow::Quantified(StopPace {
+            before_ms,
+            after_ms,
+            pit_loss_ms: pit_laps_ms as i64 - (before_ms * pit_laps.len() as f64).round() as i64,
+            net_swing_ms,
+        })
+    }
+
+    /// Whether new tyres were fitted, i.e. the compound changed or the tyres got younger. `false` for drive
+    /// throughs, served stop-go penalties and boxing without a tyre change, `None` without car status.
+    pub(crate) fn tyres_changed(&self) -> Option<bool> {
+        let (before, after) = (self.tyres_before.as_ref()?, self.tyres_after.as_ref()?);
+        let younger = matches!((before.age_laps, after.age_laps), (Some(before), Some(after)) if after < before);
+        Some(before.compound != after.compound || younger)
+    }
+}
+
+/// Follows the pit status of every car and collects completed pit stops
+#[derive(Debug, Default)]
+pub(crate) struct PitStopTracker {
+    open: Vec<Option<PitStop>>,
+    completed: Vec<PitStop>,
+}
+
+impl PitStopTracker {
+    pub(crate) fn clear(&mut self) {
+        self.open.clear();
+        self.completed.clear();
+    }
+
+    /// Updates the pit stop state of all cars, moving stops to the completed list once the car leaves the pits.
+    /// `car_status` is the latest status, which still shows the old tyres when the car enters the pit lane.
+    pub(crate) fn update(
+        &mut self,
+        previous: &[LapData],
+        current: &[LapData],
+        car_status: &[CarStatusData],
+        session_time: u32,
+    ) {
+        if self.open.len() < current.len() {
+            self.open.resize(current.len(), None);
+        }
+
+        for (i, (previous, current)) in previous.iter().zip(current).enumerate() {
+            let was_in_pits = previous.pit_status != PitStatus::None;
+            let is_in_pits = current.pit_status != PitStatus::None;
+
+            if !was_in_pits && is_in_pits {
+                self.open[i] = Some(PitStop {
+                    car_idx: i,
+                    lap: current.current_lap_num,
+                    entry_time: session_time,
+                    exit_time: session_time,
+                    stationary_ms: 0,
+                    penalty: false,
+                    tyres_before: car_status.get(i).map(TyreSet::from_status),
+                    tyres_after: None,
+                    entry_distance: current.lap_distance,
+                    exit_distance: current.lap_distance,
+                    reference_lap_time_ms: previous.last_lap_time,
+                    stationary_since: None,
+                });
+            }
+
+            let Some(stop) = self.open[i].as_mut() else {
+                continue;
+            };
+
+            let was_stationary = previous.pit_status == PitStatus::InPitArea;
+            let is_stationary = current.pit_status == PitStatus::InPitArea;
+            if !was_stationary && is_stationary {
+                stop.stationary_since = Some(session_time);
+            } else if was_stationary && !is_stationary {
+                if let Some(since) = stop.stationary_since.take() {
+                    stop.stationary_ms += session_time.saturating_sub(since);
+                }
+            }
+
+            if was_in_pits && !is_in_pits {
+                stop.exit_time = session_time;
+                stop.exit_distance = current.lap_distance;
+                stop.tyres_after = car_status.get(i).map(TyreSet::from_status);
+                if let Some(stop) = self.open[i].take() {
+                    self.completed.push(stop);
+                }
+            }
+        }
+    }
+
+    /// Marks the car's current (or just completed) pit stop as a penalty stop
+    pub(crate) fn mark_penalty(&mut self, car_idx: usize) {
+        if let Some(Some(stop)) = self.open.get_mut(car_idx) {
+            stop.penalty = true;
+        } else if let Some(stop) = self.completed.iter_mut().rev().find(|stop| stop.car_idx == car_idx) {
+            stop.penalty = true;
+        }
+    }
+
+    pub(crate) fn completed(&self) -> &[PitStop] {
+        &self.completed
+    }
+
+    /// Stint of the car, counting from 1 with every completed stop starting the next one
+    pub(crate) fn stint(&self, car_idx: usize) -> usize {
+        self.completed.iter().filter(|stop| stop.car_idx == car_idx).count() + 1
+    }
+}
@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+const F1_2024_RACE_POINTS: [u16; 10] = [25, 18, 15, 12, 10, 8, 6, 4, 2, 1];
+const F1_2024_SPRINT_POINTS: [u16; 8] = [8, 7, 6, 5, 4, 3, 2, 1];
+
+/// Championship points awarded per finishing position, plus an optional fastest lap bonus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PointsScheme {
+    positions: Vec<u16>,
+    fastest_lap_bonus: u16,
+}
+
+impl PointsScheme {
+    pub(crate) fn with_fastest_lap_bonus(mut self, fastest_lap_bonus: u16) -> Self {
+        self.fastest_lap_bonus = fastest_lap_bonus;
+        self
+    }
+
+    /// Number of positions scoring points, which the fastest lap bonus requires
+    pub(crate) fn paying_positions(&self) -> usize {
+        self.positions.iter().take_while(|&&points| points > 0).count()
+    }
+
+    /// Points for a classified car. Cars that did not finish score nothing, and the fastest lap bonus
+    /// only counts when the car also finished in a points paying position.
+    pub(crate) fn points(&self, position: u8, finished: bool, fastest_lap: bool) -> u16 {
+        if !finished || position == 0 {
+            return 0;
+        }
+
+        match self.positions.get(position as usize - 1) {
+            Some(&points) if fastest_lap => points + self.fastest_lap_bonus,
+            Some(&points) => points,
+            None => 0,
+        }
+    }
+}
+
+impl FromStr for PointsScheme {
+    type Err = String;
+
+    /// Parses either a named scheme (`f1-2024`, `sprint`) or a comma separated list of points per position
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "f1-2024" => Ok(Self { positions: F1_2024_RACE_POINTS.to_vec(), fastest_lap_bonus: 1 }),
+            "sprint" => Ok(Self { positions: F1_2024_SPRINT_POINTS.to_vec(), fastest_lap_bonus: 0 }),
+            custom => {
+                let positions = custom
+                    .split(',')
+                    .map(|points| points.trim().parse::<u16>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| format!("Invalid points scheme '{}': {}", custom, err))?;
+
+                Ok(Self { positions, fastest_lap_bonus: 0 })
+            },
+        }
+    }
+}
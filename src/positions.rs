@@ -0,0 +1,217 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+use std::{fs, path};
+
+/// Identifies compact positions files and their format version
+const POSITIONS_MAGIC: &[u8; 8] = b"F1EVPOS1";
+
+/// Points buffered per car before they are written as one chunk
+const CHUNK_POINTS: usize = 256;
+
+/// Positions are stored as whole millimetres
+const POSITION_SCALE: f32 = 1000.0;
+
+pub(crate) const POSITIONS_CSV_HEADERS: [&str; 5] = ["Car", "Sessiontime [ms]", "Lap", "X", "Z"];
+
+/// File format of the position trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PositionsFormat {
+    Csv,
+    /// Delta-encoded binary chunks, a fraction of the CSV size for long sessions
+    Compact,
+}
+
+impl FromStr for PositionsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(PositionsFormat::Csv),
+            "compact" => Ok(PositionsFormat::Compact),
+            other => Err(format!("Invalid positions format '{}', expected csv or compact", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PositionPoint {
+    pub(crate) session_time: u32,
+    pub(crate) lap: u8,
+    pub(crate) x: f32,
+    pub(crate) z: f32,
+}
+
+/// Writes positions in chunks of up to [`CHUNK_POINTS`] points of one car. A chunk starts with the car
+/// index and the number of points, followed by one column per field holding the zigzag varint encoded
+/// difference to the previous point, so slowly changing values take a byte or two.
+pub(crate) struct CompactPositionsWriter<W: Write> {
+    writer: W,
+    pending: Vec<Vec<PositionPoint>>,
+}
+
+impl CompactPositionsWriter<BufWriter<fs::File>> {
+    pub(crate) fn create(path: &path::Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(fs::File::create(path)?))
+    }
+}
+
+impl<W: Write> CompactPositionsWriter<W> {
+    pub(crate) fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(POSITIONS_MAGIC)?;
+        Ok(Self { writer, pending: Vec::new() })
+    }
+
+    pub(crate) fn push(&mut self, car_idx: u8, point: PositionPoint) -> io::Result<()> {
+        let i = car_idx as usize;
+        if self.pending.len() <= i {
+            self.pending.resize_with(i + 1, Vec::new);
+        }
+
+        self.pending[i].push(point);
+        if self.pending[i].len() >= CHUNK_POINTS {
+            let points = std::mem::take(&mut self.pending[i]);
+            write_chunk(&mut self.writer, car_idx, &points)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the chunks written so far. Pending points stay buffered so chunks don't shrink with the
+    /// flush interval.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Writes the pending points of all cars, to be called once all points have been pushed
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        for (i, points) in std::mem::take(&mut self.pending).into_iter().enumerate() {
+            if !points.is_empty() {
+                write_chunk(&mut self.writer, i as u8, &points)?;
+            }
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn write_chunk(writer: &mut impl Write, car_idx: u8, points: &[PositionPoint]) -> io::Result<()> {
+    let mut chunk = vec![car_idx];
+    chunk.extend_from_slice(&(points.len() as u16).to_le_bytes());
+
+    for column in columns(points) {
+        let mut previous = 0;
+        for value in column {
+            write_varint(&mut chunk, zigzag(value - previous));
+            previous = value;
+        }
+    }
+
+    writer.write_all(&chunk)
+}
+
+/// The fields of the points as integer columns, positions in millimetres
+fn columns(points: &[PositionPoint]) -> [Vec<i64>; 4] {
+    [
+        points.iter().map(|point| point.session_time as i64).collect(),
+        points.iter().map(|point| point.lap as i64).collect(),
+        points.iter().map(|point| (point.x * POSITION_SCALE).round() as i64).collect(),
+        points.iter().map(|point| (point.z * POSITION_SCALE).round() as i64).collect(),
+    ]
+}
+
+/// Reads all points of a compact positions file as pairs of car index and point, in the order written
+pub(crate) fn read_compact_positions(path: &path::Path) -> io::Result<Vec<(u8, PositionPoint)>> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+
+    let mut magic = [0; POSITIONS_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != POSITIONS_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a compact positions file"));
+    }
+
+    let mut points = Vec::new();
+    loop {
+        let mut chunk_header = [0; 3];
+        match reader.read_exact(&mut chunk_header) {
+            Ok(()) => {},
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        }
+        let car_idx = chunk_header[0];
+        let count = u16::from_le_bytes([chunk_header[1], chunk_header[2]]) as usize;
+
+        let mut columns = [vec![0; count], vec![0; count], vec![0; count], vec![0; count]];
+        for column in columns.iter_mut() {
+            let mut previous = 0;
+            for value in column.iter_mut() {
+                *value = previous + unzigzag(read_varint(&mut reader)?);
+                previous = *value;
+            }
+        }
+
+        let [session_times, laps, xs, zs] = columns;
+        for i in 0..count {
+            points.push((
+                car_idx,
+                PositionPoint {
+                    session_time: session_times[i] as u32,
+                    lap: laps[i] as u8,
+                    x: xs[i] as f32 / POSITION_SCALE,
+                    z: zs[i] as f32 / POSITION_SCALE,
+                },
+            ));
+        }
+    }
+
+    Ok(points)
+}
+
+/// Converts a compact positions file to CSV, ordered by session time
+pub(crate) fn export_csv(path: &path::Path, output: &path::Path) -> io::Result<usize> {
+    let mut points = read_compact_positions(path)?;
+    points.sort_by_key(|(car_idx, point)| (point.session_time, *car_idx));
+
+    let mut writer = csv::Writer::from_path(output)?;
+    writer.write_record(POSITIONS_CSV_HEADERS)?;
+    for (car_idx, point) in &points {
+        writer.write_record([
+            car_idx.to_string(),
+            point.session_time.to_string(),
+            point.lap.to_string(),
+            point.x.to_string(),
+            point.z.to_string(),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(points.len())
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "Varint too long"))
+}
@@ -0,0 +1,81 @@
+use std::time;
+
+/// Session time over which the telemetry send rate is measured
+const MEASUREMENT_WINDOW_MS: u32 = 10_000;
+
+/// Time without telemetry after which the rate is measured again, as the game's settings may have changed,
+/// e.g. while the game was paused
+const MAX_PACKET_GAP: time::Duration = time::Duration::from_secs(3);
+
+/// Lowest rate at which the speeds and gaps of overtakes are sampled finely enough
+const MIN_OVERTAKE_RATE_HZ: u32 = 20;
+
+/// Measures the rate of the car telemetry packets, i.e. the send rate configured in the game's telemetry
+/// settings, over the first seconds of the session and again after gaps in the data
+#[derive(Debug, Default)]
+pub(crate) struct RateMeter {
+    /// Session time the running measurement started at, `None` between measurements
+    window_start: Option<u32>,
+    packets: u32,
+    last_received: Option<time::Instant>,
+    measured_hz: Option<u32>,
+}
+
+impl RateMeter {
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(crate) fn measured_hz(&self) -> Option<u32> {
+        self.measured_hz
+    }
+
+    /// Counts a telemetry packet, returning the rate in Hz when a measurement completes
+    pub(crate) fn record(&mut self, session_time: u32) -> Option<u32> {
+        let now = time::Instant::now();
+        let after_gap = self.last_received.is_some_and(|last| now.duration_since(last) > MAX_PACKET_GAP);
+        self.last_received = Some(now);
+
+        // A flashback moves the session time back, restarting the window
+        let first_measurement = self.measured_hz.is_none() && self.window_start.is_none();
+        if first_measurement || after_gap || self.window_start.is_some_and(|start| session_time < start) {
+            self.window_start = Some(session_time);
+            self.packets = 0;
+            return None;
+        }
+
+        let start = self.window_start?;
+        self.packets += 1;
+        let elapsed_ms = session_time - start;
+        if elapsed_ms < MEASUREMENT_WINDOW_MS {
+            return None;
+        }
+
+        let hz = (self.packets as f64 * 1000.0 / elapsed_ms as f64).round() as u32;
+        self.window_start = None;
+        self.measured_hz = Some(hz);
+        Some(hz)
+    }
+}
+
+/// Warnings for the enabled features whose results the measured send rate makes coarse
+pub(crate) fn rate_warnings(hz: u32, player_trace_interval_ms: Option<u32>, logs_overtakes: bool) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let packet_interval_ms = 1000 / hz.max(1);
+
+    if let Some(interval_ms) = player_trace_interval_ms.filter(|&interval_ms| interval_ms < packet_interval_ms) {
+        warnings.push(format!(
+            "Player trace requested every {} ms but telemetry is arriving at {} Hz - the points will be {} ms apart",
+            interval_ms, hz, packet_interval_ms
+        ));
+    }
+    if logs_overtakes && hz < MIN_OVERTAKE_RATE_HZ {
+        warnings.push(format!(
+            "Telemetry is arriving at {} Hz - overtake speeds and gaps will be coarse, raise the send rate in the \
+             game's telemetry settings",
+            hz
+        ));
+    }
+
+    warnings
+}
@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use f1_telemetry::packet::event::Event;
+
+/// Packet id of the event packets in all game versions
+const EVENT_PACKET_ID: u8 = 3;
+
+/// Codes of the events with dedicated handling
+const HANDLED_EVENT_CODES: [&str; 11] =
+    ["OVTK", "COLL", "PENA", "RTMT", "FTLP", "LGOT", "RDFL", "CHQF", "SEND", "DTSV", "SGSV"];
+
+/// Unhandled events remembered until their typed event is handled, beyond the few events of a frame to
+/// cover codes the telemetry crate can't parse, which never get handled
+const MAX_UNHANDLED_EVENTS: usize = 64;
+
+/// Offsets of the header fields read for raw events, which moved as fields were added over the game versions
+struct HeaderLayout {
+    packet_id: usize,
+    session_uid: usize,
+    session_time: usize,
+    frame_identifier: usize,
+    len: usize,
+}
+
+fn header_layout(packet_format: u16) -> Option<HeaderLayout> {
+    match packet_format {
+        2018 => Some(HeaderLayout { packet_id: 3, session_uid: 4, session_time: 12, frame_identifier: 16, len: 21 }),
+        2019 => Some(HeaderLayout { packet_id: 5, session_uid: 6, session_time: 14, frame_identifier: 18, len: 23 }),
+        2020..=2022 => {
+            Some(HeaderLayout { packet_id: 5, session_uid: 6, session_time: 14, frame_identifier: 18, len: 24 })
+        },
+        2023.. => Some(HeaderLayout { packet_id: 6, session_uid: 7, session_time: 15, frame_identifier: 19, len: 29 }),
+        _ => None,
+    }
+}
+
+/// An event packet read straight from the datagram rather than through the telemetry crate, so event codes
+/// it doesn't know, e.g. of newer game versions, are kept as well
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RawEvent<'a> {
+    pub(crate) session_uid: u64,
+    pub(crate) session_time_ms: u32,
+    pub(crate) frame_identifier: u32,
+    /// Four character event code, e.g. "OVTK"
+    pub(crate) code: String,
+    /// Vehicle indexes of the event codes known to have them
+    pub(crate) vehicles: [Option<u8>; 2],
+    /// The event details union following the code, padded by the game
+    pub(crate) payload: &'a [u8],
+}
+
+impl<'a> RawEvent<'a> {
+    /// Reads an event packet, `None` for other packets and datagrams too short or of unknown game versions
+    pub(crate) fn parse(data: &'a [u8]) -> Option<Self> {
+        let packet_format = u16::from_le_bytes(data.get(0..2)?.try_into().ok()?);
+        let layout = header_layout(packet_format)?;
+        if *data.get(layout.packet_id)? != EVENT_PACKET_ID {
+            return None;
+        }
+
+        let session_uid = u64::from_le_bytes(data.get(layout.session_uid..layout.session_uid + 8)?.try_into().ok()?);
+        let session_time = f32::from_le_bytes(data.get(layout.session_time..layout.session_time + 4)?.try_into().ok()?);
+        let frame_identifier =
+            u32::from_le_bytes(data.get(layout.frame_identifier..layout.frame_identifier + 4)?.try_into().ok()?);
+        let code = String::from_utf8_lossy(data.get(layout.len..layout.len + 4)?).into_owned();
+        let payload = &data[layout.len + 4..];
+
+        let vehicle = |offset: usize| payload.get(offset).copied();
+        let vehicles = match code.as_str() {
+            "FTLP" | "RTMT" | "TMPT" | "RCWN" | "SPTP" | "DTSV" | "SGSV" => [vehicle(0), None],
+            "OVTK" | "COLL" => [vehicle(0), vehicle(1)],
+            // Penalty and infringement types come first
+            "PENA" => [vehicle(2), vehicle(3)],
+            _ => [None, None],
+        };
+
+        Some(Self {
+            session_uid,
+            session_time_ms: (session_time * 1000.0) as u32,
+            frame_identifier,
+            code,
+            vehicles,
+            payload,
+        })
+    }
+
+    /// The payload as lowercase hex, two digits per byte
+    pub(crate) fn payload_hex(&self) -> String {
+        self.payload.iter().fold(String::with_capacity(self.payload.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+    }
+}
+
+/// Code of a typed event with dedicated handling, `None` for the other events
+pub(crate) fn event_code(event: &Event) -> Option<&'static str> {
+    match event {
+        Event::Overtake(_) => Some("OVTK"),
+        Event::Collision(_) => Some("COLL"),
+        Event::Penalty(_) => Some("PENA"),
+        Event::Retirement(_) => Some("RTMT"),
+        Event::FastestLap(_) => Some("FTLP"),
+        Event::LightsOut => Some("LGOT"),
+        Event::RedFlag => Some("RDFL"),
+        Event::ChequeredFlag => Some("CHQF"),
+        Event::SessionEnded => Some("SEND"),
+        Event::DriveThroughServed(_) => Some("DTSV"),
+        Event::StopGoServed(_) => Some("SGSV"),
+        _ => None,
+    }
+}
+
+/// Vehicle indexes of a typed event, like [`RawEvent::vehicles`] for the events with dedicated handling
+pub(crate) fn event_vehicles(event: &Event) -> [Option<u8>; 2] {
+    match event {
+        Event::Overtake(overtake) => {
+            [Some(overtake.overtaking_vehicle_idx), Some(overtake.being_overtaken_vehicle_idx)]
+        },
+        Event::Collision(collision) => [Some(collision.vehicle_1_idx), Some(collision.vehicle_2_idx)],
+        Event::Penalty(penalty) => [Some(penalty.vehicle_idx), Some(penalty.other_vehicle_idx)],
+        Event::Retirement(retirement) => [Some(retirement.vehicle_idx), None],
+        Event::FastestLap(fastest_lap) => [Some(fastest_lap.vehicle_idx), None],
+        Event::DriveThroughServed(served) => [Some(served.vehicle_idx), None],
+        Event::StopGoServed(served) => [Some(served.vehicle_idx), None],
+        _ => [None, None],
+    }
+}
+
+/// Code and payload of an event without dedicated handling, as read from its datagram
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnhandledEvent {
+    session_uid: u64,
+    frame_identifier: u32,
+    pub(crate) code: String,
+    pub(crate) payload_hex: String,
+}
+
+/// The recent unhandled events in receipt order, so the typed event can be logged with the code and bytes
+/// of its datagram. The events of a frame keep their receipt order when sorted, so they are taken in order.
+#[derive(Debug, Default)]
+pub(crate) struct UnhandledEvents {
+    recent: VecDeque<UnhandledEvent>,
+}
+
+impl UnhandledEvents {
+    pub(crate) fn push(&mut self, event: &RawEvent) {
+        if HANDLED_EVENT_CODES.contains(&event.code.as_str()) {
+            return;
+        }
+        if self.recent.len() == MAX_UNHANDLED_EVENTS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(UnhandledEvent {
+            session_uid: event.session_uid,
+            frame_identifier: event.frame_identifier,
+            code: event.code.clone(),
+            payload_hex: event.payload_hex(),
+        });
+    }
+
+    /// Takes the first unhandled event of the frame
+    pub(crate) fn take(&mut self, session_uid: u64, frame_identifier: u32) -> Option<UnhandledEvent> {
+        let i = self
+            .recent
+            .iter()
+            .position(|event| event.session_uid == session_uid && event.frame_identifier == frame_identifier)?;
+        self.recent.remove(i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Event datagram with the packet id at `packet_id`, the session uid, time and frame following it, and the
+    /// event code at `header_len`
+    fn datagram(packet_format: u16, packet_id: usize, header_len: usize, code: &str, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; header_len];
+        data[0..2].copy_from_slice(&packet_format.to_le_bytes());
+        data[packet_id] = EVENT_PACKET_ID;
+        data[packet_id + 1..packet_id + 9].copy_from_slice(&42u64.to_le_bytes());
+        data[packet_id + 9..packet_id + 13].copy_from_slice(&12.5f32.to_le_bytes());
+        data[packet_id + 13..packet_id + 17].copy_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(code.as_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn parses_every_header_layout() {
+        for (packet_format, packet_id, header_len) in [(2018, 3, 21), (2019, 5, 23), (2021, 5, 24), (2023, 6, 29)] {
+            let data = datagram(packet_format, packet_id, header_len, "OVTK", &[3, 9, 0, 0]);
+            let event = RawEvent::parse(&data).unwrap_or_else(|| panic!("{} not parsed", packet_format));
+
+            assert_eq!(event.session_uid, 42, "{}", packet_format);
+            assert_eq!(event.session_time_ms, 12_500, "{}", packet_format);
+            assert_eq!(event.frame_identifier, 7, "{}", packet_format);
+            assert_eq!(event.code, "OVTK", "{}", packet_format);
+            assert_eq!(event.vehicles, [Some(3), Some(9)], "{}", packet_format);
+            assert_eq!(event.payload_hex(), "03090000", "{}", packet_format);
+        }
+    }
+
+    #[test]
+    fn reads_vehicles_by_event_code() {
+        let penalty = datagram(2023, 6, 29, "PENA", &[4, 5, 11, 12, 255]);
+        assert_eq!(RawEvent::parse(&penalty).unwrap().vehicles, [Some(11), Some(12)]);
+
+        let unknown = datagram(2023, 6, 29, "XXXX", &[1, 2]);
+        assert_eq!(RawEvent::parse(&unknown).unwrap().vehicles, [None, None]);
+    }
+
+    #[test]
+    fn skips_other_datagrams() {
+        let mut other_packet = datagram(2023, 6, 29, "OVTK", &[3, 9]);
+        other_packet[6] = 2;
+        assert_eq!(RawEvent::parse(&other_packet), None);
+
+        assert_eq!(RawEvent::parse(&datagram(2017, 3, 21, "OVTK", &[3, 9])), None);
+        assert_eq!(RawEvent::parse(&datagram(2023, 6, 29, "OVTK", &[])[..31]), None);
+    }
+}
@@ -0,0 +1,230 @@
+use std::path;
+
+use crate::timeline::TimelineEntry;
+
+/// Rows buffered between two flushes, written early beyond this so a long interval doesn't exhaust memory
+const MAX_BUFFERED_ROWS: usize = 100_000;
+
+/// Tables of the replay database, every row carries the session UID so sessions can be queried together
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    session_uid INTEGER PRIMARY KEY,
+    track TEXT NOT NULL,
+    session_type TEXT NOT NULL,
+    started_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS frames (
+    session_uid INTEGER NOT NULL,
+    session_time_ms INTEGER NOT NULL,
+    car_idx INTEGER NOT NULL,
+    lap INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    lap_distance_m REAL NOT NULL,
+    speed_kmh INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS frames_session_time ON frames (session_uid, session_time_ms);
+CREATE TABLE IF NOT EXISTS events (
+    session_uid INTEGER NOT NULL,
+    session_time_ms INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    description TEXT NOT NULL,
+    details TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_session_time ON events (session_uid, session_time_ms);
+CREATE TABLE IF NOT EXISTS classification (
+    session_uid INTEGER NOT NULL,
+    car_idx INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    driver TEXT NOT NULL,
+    team TEXT NOT NULL,
+    grid_position INTEGER NOT NULL,
+    laps INTEGER NOT NULL,
+    best_lap_ms INTEGER NOT NULL,
+    race_time_ms INTEGER NOT NULL,
+    penalties_secs INTEGER NOT NULL,
+    result_status TEXT NOT NULL,
+    PRIMARY KEY (session_uid, car_idx)
+);
+";
+
+/// Checks that the logger was built with the `sqlite` feature
+pub(crate) fn ensure_supported() -> Result<(), String> {
+    if cfg!(feature = "sqlite") {
+        Ok(())
+    } else {
+        Err("The replay database is not available, build with --features sqlite".to_string())
+    }
+}
+
+/// State of one car in one frame
+#[derive(Debug, Clone)]
+pub(crate) struct FrameSnapshot {
+    pub(crate) session_time: u32,
+    pub(crate) car_idx: u8,
+    pub(crate) lap: u8,
+    pub(crate) position: u8,
+    pub(crate) lap_distance: f32,
+    pub(crate) speed: u16,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ClassificationRow {
+    pub(crate) car_idx: u8,
+    pub(crate) position: u8,
+    pub(crate) driver: String,
+    pub(crate) team: String,
+    pub(crate) grid_position: u8,
+    pub(crate) laps: u8,
+    pub(crate) best_lap_ms: u32,
+    pub(crate) race_time_ms: u64,
+    pub(crate) penalties_secs: u8,
+    pub(crate) result_status: String,
+}
+
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+#[derive(Debug, Clone)]
+enum Row {
+    Session { session_uid: u64, track: String, session_type: String, started_at: String },
+    Frame(u64, FrameSnapshot),
+    Event { session_uid: u64, session_time: u32, kind: String, description: String, details: String },
+    Classification(u64, ClassificationRow),
+}
+
+/// SQLite database with everything needed to reconstruct and query whole sessions: the per-frame state of
+/// every car, the events and the classification. Rows are buffered and inserted in one transaction per
+/// flush, as a transaction per row couldn't keep up with the frame rate.
+pub(crate) struct ReplayDb {
+    #[cfg(feature = "sqlite")]
+    connection: rusqlite::Connection,
+    rows: Vec<Row>,
+}
+
+impl ReplayDb {
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn open(path: &path::Path) -> Result<Self, String> {
+        let connection = rusqlite::Connection::open(path)
+            .and_then(|connection| connection.execute_batch(SCHEMA).map(|()| connection))
+            .map_err(|err| format!("Failed to open the replay database {:?}: {}", path, err))?;
+        println!("Writing the replay database to {:?}", path);
+
+        Ok(Self { connection, rows: Vec::new() })
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    pub(crate) fn open(_path: &path::Path) -> Result<Self, String> {
+        ensure_supported().map(|()| Self { rows: Vec::new() })
+    }
+
+    pub(crate) fn record_session(&mut self, session_uid: u64, track: &str, session_type: &str, started_at: String) {
+        self.push(Row::Session {
+            session_uid,
+            track: track.to_string(),
+            session_type: session_type.to_string(),
+            started_at,
+        });
+    }
+
+    pub(crate) fn record_frame(&mut self, session_uid: u64, snapshot: FrameSnapshot) {
+        self.push(Row::Frame(session_uid, snapshot));
+    }
+
+    pub(crate) fn record_event(&mut self, session_uid: u64, entry: &TimelineEntry) {
+        self.push(Row::Event {
+            session_uid,
+            session_time: entry.session_time,
+            kind: entry.kind.to_string(),
+            description: entry.description.clone(),
+            details: entry.details.to_string(),
+        });
+    }
+
+    pub(crate) fn record_classification(&mut self, session_uid: u64, row: ClassificationRow) {
+        self.push(Row::Classification(session_uid, row));
+    }
+
+    fn push(&mut self, row: Row) {
+        self.rows.push(row);
+        if self.rows.len() >= MAX_BUFFERED_ROWS {
+            self.flush();
+        }
+    }
+
+    /// Inserts the buffered rows in one transaction. Failed rows are dropped rather than retried, so a broken
+    /// database doesn't grow the buffer without bounds.
+    pub(crate) fn flush(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+
+        let rows = std::mem::take(&mut self.rows);
+        if let Err(err) = self.insert(&rows) {
+            println!("Failed to write {} rows to the replay database: {}", rows.len(), err);
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn insert(&mut self, rows: &[Row]) -> rusqlite::Result<()> {
+        use rusqlite::params;
+
+        let transaction = self.connection.transaction()?;
+        {
+            let mut sessions = transaction.prepare_cached("INSERT OR REPLACE INTO sessions VALUES (?1, ?2, ?3, ?4)")?;
+            let mut frames = transaction.prepare_cached("INSERT INTO frames VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+            let mut events = transaction.prepare_cached("INSERT INTO events VALUES (?1, ?2, ?3, ?4, ?5)")?;
+            let mut classification = transaction.prepare_cached(
+                "INSERT OR REPLACE INTO classification VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+
+            // SQLite integers are signed, session UIDs are stored with their bits reinterpreted
+            for row in rows {
+                match row {
+                    Row::Session { session_uid, track, session_type, started_at } => {
+                        sessions.execute(params![*session_uid as i64, track, session_type, started_at])?;
+                    },
+                    Row::Frame(session_uid, frame) => {
+                        frames.execute(params![
+                            *session_uid as i64,
+                            frame.session_time,
+                            frame.car_idx,
+                            frame.lap,
+                            frame.position,
+                            frame.lap_distance,
+                            frame.speed
+                        ])?;
+                    },
+                    Row::Event { session_uid, session_time, kind, description, details } => {
+                        events.execute(params![*session_uid as i64, session_time, kind, description, details])?;
+                    },
+                    Row::Classification(session_uid, row) => {
+                        classification.execute(params![
+                            *session_uid as i64,
+                            row.car_idx,
+                            row.position,
+                            row.driver,
+                            row.team,
+                            row.grid_position,
+                            row.laps,
+                            row.best_lap_ms,
+                            row.race_time_ms as i64,
+                            row.penalties_secs,
+                            row.result_status
+                        ])?;
+                    },
+                }
+            }
+        }
+        transaction.commit()
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn insert(&mut self, _rows: &[Row]) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Drop for ReplayDb {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
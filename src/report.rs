@@ -0,0 +1,181 @@
+use std::{fs, io, path};
+
+/// Overtakes listed in the report, the most significant first
+const REPORT_OVERTAKES: usize = 10;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReportOvertake {
+    pub(crate) lap: u8,
+    pub(crate) for_pos: u8,
+    pub(crate) overtaker: String,
+    pub(crate) overtakee: String,
+    pub(crate) speed_delta: u16,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReportRetirement {
+    pub(crate) lap: Option<u8>,
+    pub(crate) driver: String,
+    pub(crate) team: String,
+    /// Failed component, if the car status reported one
+    pub(crate) reason: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReportPenalty {
+    pub(crate) lap: u8,
+    pub(crate) driver: String,
+    pub(crate) penalty: String,
+    pub(crate) infringement: String,
+    pub(crate) time_secs: Option<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ReportResult {
+    pub(crate) position: u8,
+    pub(crate) driver: String,
+    pub(crate) team: String,
+    /// Race time including penalties, `None` for cars that didn't finish
+    pub(crate) time_ms: Option<u64>,
+    pub(crate) best_lap_ms: u32,
+}
+
+/// Collects the events of a race for the Markdown report written at the end of the session
+#[derive(Debug, Default)]
+pub(crate) struct RaceReport {
+    overtakes: Vec<ReportOvertake>,
+    retirements: Vec<ReportRetirement>,
+    penalties: Vec<ReportPenalty>,
+}
+
+impl RaceReport {
+    pub(crate) fn clear(&mut self) {
+        self.overtakes.clear();
+        self.retirements.clear();
+        self.penalties.clear();
+    }
+
+    pub(crate) fn push_overtake(&mut self, overtake: ReportOvertake) {
+        self.overtakes.push(overtake);
+    }
+
+    pub(crate) fn push_retirement(&mut self, retirement: ReportRetirement) {
+        self.retirements.push(retirement);
+    }
+
+    pub(crate) fn push_penalty(&mut self, penalty: ReportPenalty) {
+        self.penalties.push(penalty);
+    }
+
+    /// Overtakes ranked by the position fought for, then by the speed difference, so a late braking move
+    /// for the lead ranks above a DRS pass in the midfield
+    fn notable_overtakes(&self) -> Vec<&ReportOvertake> {
+        let mut overtakes: Vec<&ReportOvertake> = self.overtakes.iter().collect();
+        overtakes.sort_by_key(|ot| (ot.for_pos, std::cmp::Reverse(ot.speed_delta)));
+        overtakes.truncate(REPORT_OVERTAKES);
+        overtakes
+    }
+
+    /// Writes the report as GitHub flavoured Markdown, `results` ordered by position
+    pub(crate) fn write(&self, path: &path::Path, title: &str, results: &[ReportResult]) -> io::Result<()> {
+        let mut report = format!("# {}\n", title);
+
+        report.push_str("\n## Podium\n\n");
+        let winner_time = results.first().and_then(|result| result.time_ms);
+        let podium: Vec<Vec<String>> = results
+            .iter()
+            .take(3)
+            .map(|result| {
+                let time = match (result.time_ms, winner_time) {
+                    (Some(time), Some(winner)) if time > winner => format!("+{}", format_ms(time - winner)),
+                    (Some(time), _) => format_ms(time),
+                    (None, _) => "DNF".to_string(),
+                };
+                vec![result.position.to_string(), result.driver.clone(), result.team.clone(), time]
+            })
+            .collect();
+        report.push_str(&table(&["Pos", "Driver", "Team", "Time"], &podium));
+
+        report.push_str("\n## Fastest Lap\n\n");
+        match results.iter().filter(|result| result.best_lap_ms > 0).min_by_key(|result| result.best_lap_ms) {
+            Some(result) => report.push_str(&format!(
+                "{} ({}): {}\n",
+                result.driver,
+                result.team,
+                format_ms(result.best_lap_ms as u64)
+            )),
+            None => report.push_str("No timed laps\n"),
+        }
+
+        report.push_str("\n## Notable Overtakes\n\n");
+        let overtakes: Vec<Vec<String>> = self
+            .notable_overtakes()
+            .iter()
+            .map(|ot| {
+                vec![
+                    ot.lap.to_string(),
+                    format!("P{}", ot.for_pos),
+                    ot.overtaker.clone(),
+                    ot.overtakee.clone(),
+                    ot.speed_delta.to_string(),
+                ]
+            })
+            .collect();
+        report.push_str(&table(&["Lap", "For", "Overtaker", "Overtakee", "Speed Delta [km/h]"], &overtakes));
+
+        report.push_str("\n## Retirements\n\n");
+        let retirements: Vec<Vec<String>> = self
+            .retirements
+            .iter()
+            .map(|retirement| {
+                vec![
+                    retirement.lap.map(|lap| lap.to_string()).unwrap_or_default(),
+                    retirement.driver.clone(),
+                    retirement.team.clone(),
+                    retirement.reason.clone().unwrap_or_default(),
+                ]
+            })
+            .collect();
+        report.push_str(&table(&["Lap", "Driver", "Team", "Reason"], &retirements));
+
+        report.push_str("\n## Penalties\n\n");
+        let penalties: Vec<Vec<String>> = self
+            .penalties
+            .iter()
+            .map(|penalty| {
+                vec![
+                    penalty.lap.to_string(),
+                    penalty.driver.clone(),
+                    penalty.penalty.clone(),
+                    penalty.infringement.clone(),
+                    penalty.time_secs.map(|secs| format!("{} s", secs)).unwrap_or_default(),
+                ]
+            })
+            .collect();
+        report.push_str(&table(&["Lap", "Driver", "Penalty", "Infringement", "Time"], &penalties));
+
+        fs::write(path, report)
+    }
+}
+
+/// A Markdown table, or a note when there are no rows since an empty table doesn't render
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    if rows.is_empty() {
+        return "None\n".to_string();
+    }
+
+    let mut table = format!("| {} |\n", headers.join(" | "));
+    table.push_str(&format!("|{}\n", " --- |".repeat(headers.len())));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        table.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    table
+}
+
+fn format_ms(ms: u64) -> String {
+    match ms / 60_000 {
+        0 => format!("{}.{:03}", ms / 1000, ms % 1000),
+        minutes => format!("{}:{:02}.{:03}", minutes, ms / 1000 % 60, ms % 1000),
+    }
+}
@@ -0,0 +1,220 @@
+use std::str::FromStr;
+use std::{fs, io, path, time};
+
+/// Extensions of the files the logger writes, see `SessionState::output_path`
+const OUTPUT_EXTENSIONS: [&str; 6] = ["csv", "json", "md", "txt", "f1pos", "csv.recovery"];
+
+/// Subdirectory old files are moved to in archive mode
+const ARCHIVE_DIR: &str = "archive";
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum RetentionMode {
+    #[default]
+    Delete,
+    /// Move old files to an archive subdirectory instead of deleting them
+    Archive,
+}
+
+impl FromStr for RetentionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "delete" => Ok(Self::Delete),
+            "archive" => Ok(Self::Archive),
+            other => Err(format!("Invalid retention mode '{}', expected delete or archive", other)),
+        }
+    }
+}
+
+/// Which old output files to remove, applied at startup and whenever a session ends
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RetentionPolicy {
+    /// Remove files last modified more than this many days ago
+    pub(crate) max_age_days: Option<u32>,
+    /// Keep only this many of the newest files
+    pub(crate) max_files: Option<usize>,
+    pub(crate) mode: RetentionMode,
+    /// Only print what would be removed
+    pub(crate) dry_run: bool,
+}
+
+impl RetentionPolicy {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.max_age_days.is_some() || self.max_files.is_some()
+    }
+
+    /// Removes the old files in the output directory of the filename template. Only files whose names match
+    /// the template and the logger's extensions are considered, anything else in the directory is left alone,
+    /// as are the files in use by the current session. A file that cannot be removed is reported and skipped,
+    /// only failing to list the directory is an error.
+    pub(crate) fn apply(&self, filename_template: &str, in_use: &[path::PathBuf]) -> io::Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let template = path::Path::new(filename_template);
+        let directory = template.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(path::Path::new("."));
+        let Some(name_template) = template.file_name().and_then(|name| name.to_str()) else {
+            return Ok(());
+        };
+        if directory.to_string_lossy().contains('{') {
+            println!("Retention skipped, placeholders in the output directory are not supported");
+            return Ok(());
+        }
+
+        let is_in_use = |name: &std::ffi::OsStr| in_use.iter().any(|file| file.file_name() == Some(name));
+        let mut files = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    println!("Retention: failed to read an entry of {:?}: {}", directory, err);
+                    continue;
+                },
+            };
+            let name = entry.file_name();
+            if is_in_use(&name) || !name.to_str().is_some_and(|name| is_output_file(name_template, name)) {
+                continue;
+            }
+            match entry.metadata().and_then(|metadata| Ok((metadata.is_file(), metadata.modified()?))) {
+                Ok((true, modified)) => files.push((modified, entry.path())),
+                Ok((false, _)) => {},
+                Err(err) => println!("Retention: failed to read the metadata of {:?}: {}", entry.path(), err),
+            }
+        }
+        // Newest first, so the files beyond the maximum count are the oldest
+        files.sort_by_key(|file| std::cmp::Reverse(file.0));
+
+        let now = time::SystemTime::now();
+        let max_age = self.max_age_days.map(|days| time::Duration::from_secs(days as u64 * SECS_PER_DAY));
+        for (i, (modified, file)) in files.iter().enumerate() {
+            let too_old = max_age.is_some_and(|max_age| now.duration_since(*modified).unwrap_or_default() > max_age);
+            let too_many = self.max_files.is_some_and(|max_files| i >= max_files);
+            if too_old || too_many {
+                if let Err(err) = self.remove(directory, file) {
+                    println!("Retention: failed to remove {:?}: {}", file, err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove(&self, directory: &path::Path, file: &path::Path) -> io::Result<()> {
+        match (self.mode, self.dry_run) {
+            (RetentionMode::Delete, true) => println!("Retention: would remove {:?}", file),
+            (RetentionMode::Archive, true) => println!("Retention: would archive {:?}", file),
+            (RetentionMode::Delete, false) => {
+                fs::remove_file(file)?;
+                println!("Retention: removed {:?}", file);
+            },
+            (RetentionMode::Archive, false) => {
+                let archive = directory.join(ARCHIVE_DIR);
+                fs::create_dir_all(&archive)?;
+                fs::rename(file, archive.join(file.file_name().unwrap_or_default()))?;
+                println!("Retention: archived {:?}", file);
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Whether the filename could have been rendered from the template: it has one of the output extensions,
+/// contains the template's literal parts and placeholders with a fixed format, like the date, match it
+fn is_output_file(name_template: &str, name: &str) -> bool {
+    OUTPUT_EXTENSIONS.iter().any(|extension| {
+        name.strip_suffix(extension)
+            .and_then(|stem| stem.strip_suffix('.'))
+            .is_some_and(|stem| matches_template(name_template, stem))
+    })
+}
+
+fn matches_template(template: &str, name: &str) -> bool {
+    let Some(start) = template.find('{') else {
+        return template == name;
+    };
+    let Some(end) = template[start..].find('}').map(|end| start + end) else {
+        return template == name;
+    };
+    let Some(rest) = name.strip_prefix(&template[..start]) else {
+        return false;
+    };
+    let remaining_template = &template[end + 1..];
+
+    // Tries every length of the placeholder value the placeholder accepts, shortest first
+    let placeholder = &template[start + 1..end];
+    rest.char_indices()
+        .map(|(i, _)| i)
+        .skip(1)
+        .chain([rest.len()])
+        .filter(|&len| is_placeholder_value(placeholder, &rest[..len]))
+        .any(|len| matches_template(remaining_template, &rest[len..]))
+}
+
+fn is_placeholder_value(placeholder: &str, value: &str) -> bool {
+    let is_digits = |value: &str| !value.is_empty() && value.chars().all(|c| c.is_ascii_digit());
+    match placeholder {
+        // YYYY-MM-DD
+        "date" => value.len() == 10 && value.split('-').map(str::len).eq([4, 2, 2]) && value.split('-').all(is_digits),
+        // HHMM
+        "time" => value.len() == 4 && is_digits(value),
+        "uid" => is_digits(value),
+        _ => !value.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE: &str = crate::filenames::DEFAULT_FILENAME_TEMPLATE;
+
+    #[test]
+    fn matches_rendered_names() {
+        assert!(is_output_file(TEMPLATE, "2024-05-01_1432 Monza Race Overtakes_123456789.csv"));
+        assert!(is_output_file(TEMPLATE, "2024-05-01_1432 Monza Race Session_123456789.json"));
+        assert!(is_output_file(TEMPLATE, "2024-05-01_1432 Monza Race Overtakes_123456789.csv.recovery"));
+        assert!(is_output_file(TEMPLATE, "2024-05-01_1432 Monza Race Chapters_123456789.txt"));
+        assert!(is_output_file("race_{type}", "race_Penalties.csv"));
+    }
+
+    #[test]
+    fn rejects_other_names() {
+        // Wrong extension, malformed date and time, non-numeric uid, missing literal part
+        assert!(!is_output_file(TEMPLATE, "2024-05-01_1432 Monza Race Overtakes_123456789.txt.bak"));
+        assert!(!is_output_file(TEMPLATE, "2024-5-1_1432 Monza Race Overtakes_123456789.csv"));
+        assert!(!is_output_file(TEMPLATE, "2024-05-01_14:32 Monza Race Overtakes_123456789.csv"));
+        assert!(!is_output_file(TEMPLATE, "2024-05-01_1432 Monza Race Overtakes_abc.csv"));
+        assert!(!is_output_file("race_{type}", "qualifying_Penalties.csv"));
+        assert!(!is_output_file("race_{type}", "race_.csv"));
+        assert!(!is_output_file(TEMPLATE, "notes.csv"));
+    }
+
+    #[test]
+    fn literal_template_matches_only_itself() {
+        assert!(matches_template("events", "events"));
+        assert!(!matches_template("events", "events2"));
+        assert!(matches_template("events_{uid", "events_{uid"));
+    }
+
+    #[test]
+    fn keeps_files_in_use() {
+        let directory = std::env::temp_dir().join(format!("f1-eventlogger-retention-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let old = directory.join("race_Laps.csv");
+        let current = directory.join("race_Overtakes.csv");
+        fs::write(&old, "").unwrap();
+        fs::write(&current, "").unwrap();
+
+        let policy = RetentionPolicy { max_files: Some(0), ..Default::default() };
+        let template = directory.join("race_{type}");
+        policy.apply(template.to_str().unwrap(), std::slice::from_ref(&current)).unwrap();
+
+        assert!(!old.exists());
+        assert!(current.exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fs, io, path};
+
+use crate::sinks::CsvSink;
+
+/// Checks that the CSV file at `path`, if it exists and isn't empty, starts with `headers`. Returns whether
+/// the file has them, and an error when it has other ones, e.g. written by an older version, as appending
+/// rows of other columns would make the file unreadable.
+pub(crate) fn check_header(path: &path::Path, headers: &[&str]) -> io::Result<bool> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(file);
+    let mut record = csv::StringRecord::new();
+    if !reader.read_record(&mut record)? {
+        return Ok(false);
+    }
+    if record.iter().eq(headers.iter().copied()) {
+        return Ok(true);
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "The rolling file {:?} has other columns than this version writes, move it away to start a new one",
+            path
+        ),
+    ))
+}
+
+/// Opens the rolling file at `path` to append rows to, writing the headers first when it's new
+pub(crate) fn open(path: &path::Path, headers: &[&str]) -> io::Result<CsvSink> {
+    let has_header = check_header(path, headers)?;
+    let file = fs::OpenOptions::new().append(true).create(true).open(path)?;
+    let mut writer = csv::Writer::from_writer(file);
+    if has_header {
+        println!("Appending laps to {:?}", path);
+    } else {
+        println!("Writing laps to {:?}", path);
+        writer.write_record(headers)?;
+    }
+    Ok(CsvSink::new("RollingLaps", writer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADERS: [&str; 2] = ["Driver", "Lap"];
+
+    fn rolling_file(name: &str) -> path::PathBuf {
+        let directory = std::env::temp_dir().join(format!("f1-eventlogger-rolling-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn append(path: &path::Path, rows: &[[&str; 2]]) {
+        let mut sink = open(path, &HEADERS).unwrap();
+        for row in rows {
+            sink.write_record(row);
+        }
+        assert!(sink.close(false));
+    }
+
+    #[test]
+    fn appends_to_the_file_of_earlier_sessions() {
+        let path = rolling_file("appends.csv");
+        assert!(!check_header(&path, &HEADERS).unwrap());
+
+        append(&path, &[["Leclerc", "1"]]);
+        assert!(check_header(&path, &HEADERS).unwrap());
+        append(&path, &[["Sainz", "1"], ["Sainz", "2"]]);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Driver,Lap\nLeclerc,1\nSainz,1\nSainz,2\n");
+    }
+
+    #[test]
+    fn writes_the_header_into_an_empty_file() {
+        let path = rolling_file("empty.csv");
+        fs::write(&path, "").unwrap();
+
+        append(&path, &[["Leclerc", "1"]]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Driver,Lap\nLeclerc,1\n");
+    }
+
+    #[test]
+    fn rejects_a_file_with_other_columns() {
+        let path = rolling_file("other.csv");
+        fs::write(&path, "Driver,Team,Lap\nLeclerc,Ferrari,1\n").unwrap();
+
+        let err = check_header(&path, &HEADERS).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(open(&path, &HEADERS).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Driver,Team,Lap\nLeclerc,Ferrari,1\n");
+    }
+}
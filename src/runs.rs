@@ -0,0 +1,106 @@
+use f1_telemetry::packet::car_status::CarStatusData;
+use f1_telemetry::packet::lap::{LapData, PitStatus};
+
+use crate::laps::LapSnapshot;
+
+/// A practice run: leaving the pits, timed laps and returning to the pits on one set of tyres
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Run {
+    pub(crate) car_idx: usize,
+    pub(crate) number: u32,
+    pub(crate) compound: String,
+    pub(crate) fuel_at_start: Option<f32>,
+    /// Times of the clean laps, i.e. without the out and in lap
+    pub(crate) lap_times: Vec<u32>,
+    /// Age of the tyres when the run ended, from the last car status before the pit entry
+    pub(crate) end_tyre_age: Option<u8>,
+}
+
+impl Run {
+    pub(crate) fn best_lap_ms(&self) -> Option<u32> {
+        self.lap_times.iter().min().copied()
+    }
+
+    pub(crate) fn average_lap_ms(&self) -> Option<u32> {
+        (!self.lap_times.is_empty()).then(|| self.lap_times.iter().sum::<u32>() / self.lap_times.len() as u32)
+    }
+}
+
+/// Splits each car's session into runs at its pit visits
+#[derive(Debug, Default)]
+pub(crate) struct RunTracker {
+    open: Vec<Option<Run>>,
+    run_counts: Vec<u32>,
+    completed: Vec<Run>,
+}
+
+impl RunTracker {
+    pub(crate) fn clear(&mut self) {
+        self.open.clear();
+        self.run_counts.clear();
+        self.completed.clear();
+    }
+
+    /// Opens a run for cars on track without one and closes the runs of cars that returned to the pits,
+    /// including being teleported to the garage
+    pub(crate) fn update(&mut self, current: &[LapData], car_status: &[CarStatusData]) {
+        if self.open.len() < current.len() {
+            self.open.resize(current.len(), None);
+            self.run_counts.resize(current.len(), 0);
+        }
+
+        for (i, lap) in current.iter().enumerate() {
+            let on_track = lap.pit_status == PitStatus::None;
+            match (on_track, self.open[i].is_some()) {
+                (true, false) => {
+                    self.run_counts[i] += 1;
+                    let status = car_status.get(i);
+                    self.open[i] = Some(Run {
+                        car_idx: i,
+                        number: self.run_counts[i],
+                        compound: status
+                            .map(|status| status.visual_tyre_compound.name().to_string())
+                            .unwrap_or_default(),
+                        fuel_at_start: status.map(|status| status.fuel_in_tank),
+                        lap_times: Vec::new(),
+                        end_tyre_age: None,
+                    });
+                },
+                (false, true) => self.close(i, car_status),
+                _ => {},
+            }
+        }
+    }
+
+    /// Adds a completed lap to the car's open run if it was a timed lap
+    pub(crate) fn record_lap(&mut self, car_idx: usize, lap: &LapSnapshot) {
+        if let Some(Some(run)) = self.open.get_mut(car_idx) {
+            if lap.clean {
+                run.lap_times.push(lap.lap_time_ms);
+            }
+        }
+    }
+
+    /// Closes all open runs with the laps completed so far, e.g. when the session ends or is red flagged
+    pub(crate) fn close_all(&mut self, car_status: &[CarStatusData]) {
+        for i in 0..self.open.len() {
+            self.close(i, car_status);
+        }
+    }
+
+    /// Number of the car's run in progress, `None` while it is in the pits
+    pub(crate) fn current_run(&self, car_idx: usize) -> Option<u32> {
+        self.open.get(car_idx)?.as_ref().map(|run| run.number)
+    }
+
+    pub(crate) fn completed(&self) -> &[Run] {
+        &self.completed
+    }
+
+    fn close(&mut self, car_idx: usize, car_status: &[CarStatusData]) {
+        if let Some(mut run) = self.open[car_idx].take() {
+            run.end_tyre_age = car_status.get(car_idx).and_then(|status| status.tyre_age_laps);
+            self.completed.push(run);
+        }
+    }
+}
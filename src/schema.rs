@@ -0,0 +1,69 @@
+/// Version of the CSV columns, to be bumped whenever a registered column is added, removed, renamed or moved
+pub(crate) const SCHEMA_VERSION: u32 = 23;
+
+/// Fingerprint of the registered columns of [`SCHEMA_VERSION`]. The startup check fails when the columns
+/// change without a new version, printing the fingerprint to record here along with the bumped version.
+const SCHEMA_FINGERPRINT: u64 = 0x34a0_18d9_4736_f1ae;
+
+/// Columns an output starts with. Options may append further columns, which aren't part of the schema.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OutputSchema {
+    pub(crate) output_type: &'static str,
+    pub(crate) columns: &'static [&'static str],
+}
+
+/// FNV-1a hash of the output types and their columns, in registry order
+fn fingerprint(schemas: &[OutputSchema]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+
+    for schema in schemas {
+        feed(schema.output_type.as_bytes());
+        for column in schema.columns {
+            // Unit and record separators keep "a,bc" and "ab,c" apart
+            feed(&[0x1f]);
+            feed(column.as_bytes());
+        }
+        feed(&[0x1e]);
+    }
+    hash
+}
+
+/// Startup self-check that the registered columns are the ones of [`SCHEMA_VERSION`]
+pub(crate) fn verify_registry(schemas: &[OutputSchema]) -> Result<(), String> {
+    let actual = fingerprint(schemas);
+    if actual != SCHEMA_FINGERPRINT {
+        return Err(format!(
+            "The CSV columns don't match schema version {} (fingerprint {:#018x}, expected {:#018x}), \
+             the version needs to be bumped",
+            SCHEMA_VERSION, actual, SCHEMA_FINGERPRINT
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the headers of an output against its registered columns before the file is written
+pub(crate) fn check_headers(schemas: &[OutputSchema], output_type: &str, headers: &[&str]) -> Result<(), String> {
+    let schema = schemas
+        .iter()
+        .find(|schema| schema.output_type == output_type)
+        .ok_or_else(|| format!("No schema registered for {}", output_type))?;
+
+    if !headers.starts_with(schema.columns) {
+        return Err(format!(
+            "The columns of {} don't match schema version {}: expected {:?}, got {:?}",
+            output_type, SCHEMA_VERSION, schema.columns, headers
+        ));
+    }
+    Ok(())
+}
+
+/// Output type with the schema version, used in the filenames so column changes give new file names
+pub(crate) fn versioned_type(output_type: &str) -> String {
+    format!("{}_v{}", output_type, SCHEMA_VERSION)
+}
@@ -0,0 +1,223 @@
+use std::{env, fs, path, process};
+
+use f1_telemetry::packet::car_status::{CarStatusData, PacketCarStatusData};
+use f1_telemetry::packet::car_telemetry::{CarTelemetryData, PacketCarTelemetryData};
+use f1_telemetry::packet::event::{Collision, Event, Overtake, PacketEventData};
+use f1_telemetry::packet::final_classification::{FinalClassificationData, PacketFinalClassificationData};
+use f1_telemetry::packet::header::PacketHeader;
+use f1_telemetry::packet::lap::{LapData, PacketLapData, ResultStatus};
+use f1_telemetry::packet::participants::{PacketParticipantsData, ParticipantData};
+use f1_telemetry::packet::session::{PacketSessionData, RuleSet};
+use f1_telemetry::packet::Packet;
+
+use crate::pipeline::PacketPipeline;
+use crate::retention::RetentionPolicy;
+use crate::session::SessionOptions;
+use crate::Result;
+
+const NUM_CARS: u8 = 4;
+const SESSION_UID: u64 = 1;
+const TRACK_LENGTH: u16 = 5000;
+const LAP_TIME_MS: u32 = 30_000;
+const RACE_LAPS: u32 = 3;
+/// 20 Hz, the game's default send rate
+const FRAME_MS: u32 = 50;
+
+/// Frames of the scripted events: two overtakes with a collision in between
+const OVERTAKE_FRAMES: [(u32, u8, u8); 2] = [(200, 1, 0), (400, 3, 2)];
+const COLLISION_FRAME: u32 = 300;
+
+/// Runs a synthetic race of a few laps through the packet pipeline into a temporary directory and checks the
+/// files written. The packets are built in code rather than parsed, so this covers everything from the
+/// packet handling to the files, and a failure points at the logger rather than the game's settings.
+pub(crate) fn run(mut options: SessionOptions) -> Result<()> {
+    let output_dir = env::temp_dir().join(format!("f1-eventlogger-selftest-{}", process::id()));
+    fs::create_dir_all(&output_dir)?;
+    println!("Running the self-test in {}", output_dir.display());
+
+    options.filename_template = output_dir.join("{type}").to_string_lossy().into_owned();
+    // No side effects outside the temporary directory, and nothing filtering out the scripted events
+    options.notify = false;
+    options.kafka = None;
+    options.replay_db = None;
+    options.rolling_laps = None;
+    options.unix_socket = None;
+    options.statsd = None;
+    options.metadata_http = None;
+    options.retention = RetentionPolicy::default();
+    options.overtake_min_position = None;
+    options.overtake_min_speed_delta = None;
+    options.exclude_pit_overtakes = false;
+    options.drivers = None;
+    options.min_session_laps = 0;
+    options.min_session_secs = 0;
+
+    let mut pipeline = PacketPipeline::new(options);
+    for packet in race_packets() {
+        pipeline.handle_packet(packet)?;
+    }
+    pipeline.close()?;
+
+    let checks = [
+        check_rows(&output_dir, "Events", Some(OVERTAKE_FRAMES.len())),
+        check_rows(&output_dir, "Collisions", Some(1)),
+        check_rows(&output_dir, "Laps", None),
+        check_rows(&output_dir, "Results", Some(NUM_CARS as usize)),
+    ];
+    let failed = checks.iter().filter(|&&passed| !passed).count();
+
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} checks failed, the files are kept in {}",
+            failed,
+            checks.len(),
+            output_dir.display()
+        )
+        .into());
+    }
+
+    println!("All {} checks passed", checks.len());
+    fs::remove_dir_all(&output_dir)?;
+    Ok(())
+}
+
+/// Prints PASS or FAIL for the output's row count, `None` expecting any rows at all
+fn check_rows(output_dir: &path::Path, output_type: &str, expected: Option<usize>) -> bool {
+    let rows = match count_rows(output_dir, output_type) {
+        Ok(rows) => rows,
+        Err(err) => {
+            println!("FAIL {}: {}", output_type, err);
+            return false;
+        },
+    };
+
+    let passed = match expected {
+        Some(expected) => rows == expected,
+        None => rows > 0,
+    };
+    let expected = expected.map_or("at least 1".to_string(), |expected| expected.to_string());
+    println!("{} {}: {} rows, expected {}", if passed { "PASS" } else { "FAIL" }, output_type, rows, expected);
+    passed
+}
+
+/// Rows of the output's CSV, found by its type as the filename may carry a schema version
+fn count_rows(output_dir: &path::Path, output_type: &str) -> Result<usize> {
+    let file = fs::read_dir(output_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|file| {
+            let name = file.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            name.starts_with(output_type) && name.ends_with(".csv")
+        })
+        .ok_or("file not written")?;
+
+    Ok(csv::Reader::from_path(file)?.records().count())
+}
+
+fn header(packet_id: u8, frame: u32) -> PacketHeader {
+    PacketHeader {
+        packet_format: 2024,
+        packet_id,
+        session_uid: SESSION_UID,
+        session_time: frame * FRAME_MS,
+        frame_identifier: frame,
+        player_car_index: 0,
+        ..Default::default()
+    }
+}
+
+pub(crate) fn race_packets() -> Vec<Packet> {
+    let mut packets = vec![
+        Packet::Session(PacketSessionData {
+            header: header(1, 0),
+            rule_set: Some(RuleSet::Race),
+            track_length: TRACK_LENGTH,
+            ..Default::default()
+        }),
+        Packet::Participants(PacketParticipantsData {
+            header: header(4, 0),
+            num_active_cars: NUM_CARS,
+            participants: (0..NUM_CARS)
+                .map(|i| ParticipantData {
+                    name: format!("Driver {}", i + 1),
+                    race_number: i + 1,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }),
+        Packet::CarStatus(PacketCarStatusData {
+            header: header(7, 0),
+            car_status_data: (0..NUM_CARS).map(|_| CarStatusData::default()).collect(),
+            ..Default::default()
+        }),
+    ];
+
+    let last_frame = RACE_LAPS * LAP_TIME_MS / FRAME_MS + 1;
+    for frame in 1..=last_frame {
+        packets.push(Packet::CarTelemetry(PacketCarTelemetryData {
+            header: header(6, frame),
+            car_telemetry_data: (0..NUM_CARS)
+                .map(|i| CarTelemetryData { speed: 280 + i as u16 * 5, ..Default::default() })
+                .collect(),
+            ..Default::default()
+        }));
+        packets.push(Packet::LapData(lap_packet(frame)));
+
+        for (overtake_frame, overtaker, overtakee) in OVERTAKE_FRAMES {
+            if frame == overtake_frame {
+                packets.push(event(
+                    frame,
+                    Event::Overtake(Overtake {
+                        overtaking_vehicle_idx: overtaker,
+                        being_overtaken_vehicle_idx: overtakee,
+                    }),
+                ));
+            }
+        }
+        if frame == COLLISION_FRAME {
+            packets.push(event(frame, Event::Collision(Collision { vehicle_1_idx: 2, vehicle_2_idx: 3 })));
+        }
+    }
+
+    packets.push(Packet::FinalClassification(PacketFinalClassificationData {
+        header: header(8, last_frame),
+        num_cars: NUM_CARS,
+        final_classifications: (0..NUM_CARS)
+            .map(|i| FinalClassificationData {
+                position: i + 1,
+                num_laps: RACE_LAPS as u8,
+                grid_position: i + 1,
+                result_status: ResultStatus::Finished,
+                best_lap_time: LAP_TIME_MS,
+                total_race_time: (RACE_LAPS * LAP_TIME_MS) as f64 / 1000.0 + i as f64,
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }));
+
+    packets
+}
+
+/// All cars running nose to tail in grid order, completing a lap every [`LAP_TIME_MS`]
+fn lap_packet(frame: u32) -> PacketLapData {
+    let session_time = frame * FRAME_MS;
+    let lap = session_time / LAP_TIME_MS + 1;
+    let lap_data = (0..NUM_CARS)
+        .map(|i| LapData {
+            car_position: i + 1,
+            current_lap_num: lap as u8,
+            last_lap_time: if lap > 1 { LAP_TIME_MS } else { 0 },
+            lap_distance: (session_time % LAP_TIME_MS) as f32 / LAP_TIME_MS as f32 * TRACK_LENGTH as f32
+                - i as f32 * 20.0,
+            ..Default::default()
+        })
+        .collect();
+
+    PacketLapData { header: header(2, frame), lap_data, ..Default::default() }
+}
+
+fn event(frame: u32, event: Event) -> Packet {
+    Packet::Event(PacketEventData { header: header(3, frame), event })
+}
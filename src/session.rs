@@ -1,11 +1,67 @@
-use f1_telemetry::packet::car_status::CarStatusData;
+use f1_telemetry::packet::car_status::{CarStatusData, PacketCarStatusData};
 use f1_telemetry::packet::car_telemetry::CarTelemetryData;
-use f1_telemetry::packet::event::{Event, Overtake, PacketEventData};
+use f1_telemetry::packet::event::{
+    Collision, Event, FastestLap, Overtake, PacketEventData, Penalty, PenaltyType, Retirement,
+};
 use f1_telemetry::packet::final_classification::PacketFinalClassificationData;
-use f1_telemetry::packet::lap::LapData;
-use f1_telemetry::packet::participants::ParticipantData;
-use f1_telemetry::packet::session::{PacketSessionData, RuleSet};
-use std::{fs, io, path};
+use f1_telemetry::packet::header::PacketHeader;
+use f1_telemetry::packet::lap::{LapData, PacketLapData, PitStatus, ResultStatus};
+use f1_telemetry::packet::motion::PacketMotionData;
+use f1_telemetry::packet::participants::{PacketParticipantsData, ParticipantData};
+use f1_telemetry::packet::session::{PacketSessionData, SessionType};
+use f1_telemetry::packet::Packet;
+use std::collections::HashMap;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::{env, fmt, fs, io, path};
+
+use chrono::{DateTime, Local};
+
+use crate::amendments::{self, Amendment};
+use crate::anonymize::Anonymizer;
+use crate::columns::{self, Column, ColumnSelection};
+use crate::controls::{ControlChange, ControlModes, ControlTracker};
+use crate::corners::{CornerSpeed, CornerSpeedTracker, Corners};
+use crate::display::{self, TimeTrialDisplay};
+use crate::drs::{DrsZones, DRS_RANGE_MS};
+use crate::faults::{self, Component, FaultTracker};
+use crate::filenames::{self, FilenameContext};
+use crate::forecast::{self, Conditions, ForecastSample};
+use crate::gaps::GapTracker;
+use crate::grip;
+use crate::kafka::{KafkaConfig, KafkaSink};
+use crate::laps::{FastestLapHolder, LapHistory, LapSnapshot, PositionHolds};
+use crate::locations::{self, Heatmap, LocationBin, LocationCounts};
+use crate::metadata::SessionMetadata;
+use crate::metadatahttp::{LiveFastestLap, LiveSession, MetadataServer};
+use crate::notify::DesktopNotifier;
+use crate::ordering::EventOrder;
+use crate::pairs::RecentPairEvents;
+use crate::penalties::PenaltyLedger;
+use crate::performance::{self, CarPerformance};
+use crate::phases::Phase;
+use crate::pits::{PaceWindow, PitStop, PitStopTracker, TyreSet};
+use crate::points::PointsScheme;
+use crate::positions::{CompactPositionsWriter, PositionPoint, PositionsFormat};
+use crate::rates::{self, RateMeter};
+use crate::rawevents::{RawEvent, UnhandledEvents};
+use crate::replaydb::{ClassificationRow, FrameSnapshot, ReplayDb};
+use crate::report::{RaceReport, ReportOvertake, ReportPenalty, ReportResult, ReportRetirement};
+use crate::retention::RetentionPolicy;
+use crate::rolling;
+use crate::runs::RunTracker;
+use crate::schema::{self, OutputSchema};
+use crate::sessions::{self, SessionKind};
+use crate::sinks::{CsvSink, EventSink, RowBuffer};
+use crate::statsd::StatsdSink;
+use crate::style::StyleTracker;
+use crate::summary::{self, SummarySection};
+use crate::teams::{self, DriverIds, DriverSelection, EmptyNames, FollowCar, IdentifyBy};
+use crate::timeline::{Timeline, TimelineEntry, TIMELINE_CSV_HEADERS};
+use crate::trackers::{PacketObserver, SessionContext};
+use crate::unixsocket::UnixSocketSink;
+use crate::vod::{self, VodConfig, VodFormat, VodMarker};
+use crate::weekend::WeekendLink;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct OvertakeEventLog {
@@ -21,11 +77,50 @@ struct OvertakeEventLog {
     overtakee_tyre_age: u8,
     for_pos: u8,
     lap: u8,
+    /// Lap distance of the overtakee, wrapped into the track's length
     track_position: u16,
-    time_secs: u32,
+    /// Whether the overtake happened at the start/finish line, `None` if the track length is unknown
+    near_line: Option<bool>,
+    location_bin: LocationBin,
+    /// Session time from the packet header, which the telemetry crate gives in milliseconds
+    session_time_ms: u32,
+    involves_player: Option<bool>,
+    /// Session time of a collision between both cars shortly before the overtake. Collisions following
+    /// the overtake are only referenced from the collision row, since this row is already written by then.
+    collision_time: Option<u32>,
+    /// How long the overtakee had held its position before the pass
+    defense_duration_ms: Option<u32>,
+    /// Gap of the next car behind the overtakee, not counting the overtaker
+    pressure_from_behind_ms: Option<u32>,
+    /// Last completed lap times, `None` during the first lap
+    overtaker_last_lap_ms: Option<u32>,
+    overtakee_last_lap_ms: Option<u32>,
+    /// Whether the overtaker gives back a position the overtakee took from it shortly before, e.g. after
+    /// an illegal pass. The earlier row is already written by then, so only the giving back is flagged.
+    reversed: bool,
+    /// Gap of the overtaker to the overtakee just before the pass
+    gap_ahead_ms: Option<u32>,
+    /// Gap of the overtaker to the overtakee at the same spot a lap before the pass, whether it followed
+    /// within DRS range rather than closing in on the lap of the pass
+    gap_lap_before_ms: Option<u32>,
+    /// Whether the pass happened within a DRS zone, `None` if the track's zones are unknown
+    in_drs_zone: Option<bool>,
+    /// Whether either car was in the pit lane, i.e. the position changed in the pit cycle rather than on track
+    pit_related: bool,
+    /// Time since the previous overtake between the same two cars, in either direction
+    rematch_after_ms: Option<u32>,
+    /// Overtakes between the same two cars this session, including this one
+    pair_swaps: u32,
+    /// Heuristic grip advantage of the overtaker's tyres in percent, `None` unless enabled or if unknown
+    grip_advantage: Option<f32>,
+    /// Stable driver ids, `None` unless enabled
+    overtaker_id: Option<String>,
+    overtakee_id: Option<String>,
 }
 
-const OVERTAKE_CSV_HEADERS: [&str; 14] = [
+/// The session time is written in seconds with millisecond precision, and again as `mm:ss.mmm` in the
+/// "Session Time" column for reading along with a replay
+const OVERTAKE_CSV_HEADERS: [&str; 30] = [
     "Overtaker",
     "Overtaker Team",
     "Overtaker Speed",
@@ -39,138 +134,3565 @@ const OVERTAKE_CSV_HEADERS: [&str; 14] = [
     "For Position",
     "Lap",
     "Track Position",
+    "Session Time [s]",
+    "Involves Player",
+    "Collision Time [s]",
+    "Defense Duration [s]",
+    "Pressure From Behind [s]",
+    "Overtaker Last Lap [ms]",
+    "Overtakee Last Lap [ms]",
+    "Reversed",
+    "Gap Ahead [s]",
+    "DRS Zone",
+    "Location Bin [m]",
+    "Rematch After [s]",
+    "Pair Swaps",
+    "Pit-Related",
+    "DRS Range",
+    "Session Time",
+    "Near S/F Line",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CollisionEventLog {
+    driver_1_name: String,
+    driver_1_team: String,
+    driver_2_name: String,
+    driver_2_team: String,
+    lap: u8,
+    track_position: u16,
+    time_secs: u32,
+    involves_player: Option<bool>,
+    /// Session time of an overtake between both cars shortly before the collision. Overtakes following
+    /// the collision are only referenced from the overtake row, since this row is already written by then.
+    overtake_time: Option<u32>,
+}
+
+const COLLISION_CSV_HEADERS: [&str; 9] = [
+    "Driver 1",
+    "Driver 1 Team",
+    "Driver 2",
+    "Driver 2 Team",
+    "Lap",
+    "Track Position",
+    "Sessiontime [ms]",
+    "Involves Player",
+    "Associated Overtake [ms]",
+];
+
+/// The finish time excludes penalties, the adjusted time includes them and is only set for classified
+/// finishers. The penalty time header is replaced depending on [`SessionOptions::penalty_time_ms`].
+/// The best lap and sector columns come from the lap tracking, as the classification lacks them.
+pub(crate) const CLASSIFICATION_COLUMNS: [Column; 17] = [
+    Column { key: "position", header: "Position" },
+    Column { key: "driver", header: "Driver" },
+    Column { key: "team", header: "Team" },
+    Column { key: "grid_position", header: "Grid Position" },
+    Column { key: "fastest_lap", header: "Fastest Lap Time [ms]" },
+    Column { key: "finish_time", header: "Finish Time [ms]" },
+    Column { key: "laps", header: "Laps" },
+    Column { key: "pit_stops", header: "Pitstops" },
+    Column { key: "penalties", header: "Penalties" },
+    Column { key: "penalty_time", header: "Penalty Time [s]" },
+    Column { key: "adjusted_time", header: "Adjusted Time [ms]" },
+    Column { key: "status", header: "Status" },
+    Column { key: "best_lap", header: "Best Lap" },
+    Column { key: "best_s1", header: "Best S1 [ms]" },
+    Column { key: "best_s2", header: "Best S2 [ms]" },
+    Column { key: "best_s3", header: "Best S3 [ms]" },
+    Column { key: "theoretical_best", header: "Theoretical Best [ms]" },
+];
+
+/// Position, driver and team are always written, the other columns unless deselected by
+/// [`SessionOptions::classification_columns`]
+const CLASSIFICATION_REQUIRED_COLUMNS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PenaltyEventLog {
+    driver_name: String,
+    driver_team: String,
+    penalty_type: String,
+    infringement_type: String,
+    other_driver_name: Option<String>,
+    time_secs: Option<u8>,
+    lap: u8,
+    places_gained: Option<u8>,
+    /// Places dropped on the grid by a grid penalty
+    grid_places: Option<u8>,
+    session_time: u32,
+    involves_player: Option<bool>,
+    /// "Race" for penalties issued after the start, "Pre-Race" for penalties issued before it
+    source: &'static str,
+}
+
+/// Grid penalties are logged from the penalty events, or inferred with "Inferred" as the source from a car
+/// starting behind its qualifying position when the event is missing, see [`SessionState::grid_drops`]
+const PENALTY_CSV_HEADERS: [&str; 12] = [
+    "Driver",
+    "Team",
+    "Penalty",
+    "Infringement",
+    "Other Driver",
+    "Time [s]",
+    "Lap",
+    "Places Gained",
     "Sessiontime [ms]",
+    "Involves Player",
+    "Grid Places",
+    "Source",
+];
+
+/// Value the game uses for penalty fields that don't apply, e.g. places gained for a warning
+const NOT_APPLICABLE: u8 = 255;
+
+const FINISH_CSV_HEADERS: [&str; 6] = ["Position", "Driver", "Team", "Laps", "Sessiontime [ms]", "Gap To Winner [ms]"];
+
+/// Columns of the laps CSV, of which driver, team, lap and session lap are always written, see
+/// [`LAP_REQUIRED_COLUMNS`]. The session and tyre columns make each row self-describing, e.g. when the laps of
+/// several sessions are combined in a spreadsheet. The compound and stint are the car's when it starts the lap.
+/// The rolling file takes its columns from these too, see [`ROLLING_LAP_CSV_HEADERS`].
+pub(crate) const LAP_COLUMNS: [Column; 20] = [
+    Column { key: "driver", header: "Driver" },
+    Column { key: "team", header: "Team" },
+    Column { key: "lap", header: "Lap" },
+    Column { key: "session_lap", header: "Session Lap" },
+    Column { key: "position", header: "Position" },
+    Column { key: "positions_gained", header: "Positions Gained" },
+    Column { key: "run", header: "Run" },
+    Column { key: "session_time", header: "Sessiontime [ms]" },
+    Column { key: "lap_time", header: "Lap Time [ms]" },
+    Column { key: "phase", header: "Phase" },
+    Column { key: "session_uid", header: "Session UID" },
+    Column { key: "started_at", header: "Started At" },
+    Column { key: "game_year", header: "Game Year" },
+    Column { key: "track", header: "Track" },
+    Column { key: "session_type", header: "Session" },
+    Column { key: "game_mode", header: "Game Mode" },
+    Column { key: "ai_difficulty", header: "AI Difficulty" },
+    Column { key: "network_game", header: "Network Game" },
+    Column { key: "compound", header: "Compound" },
+    Column { key: "stint", header: "Stint" },
 ];
 
-const CLASSIFICATION_CSV_HEADERS: [&str; 11] = [
+const LAP_REQUIRED_COLUMNS: usize = 4;
+
+/// Columns of the rolling laps file, see [`SessionOptions::rolling_laps`], by the headers of their
+/// [`LAP_COLUMNS`]. Every row carries the context of its session from the metadata, so the laps of a whole
+/// evening can be filtered without the sidecar files.
+pub(crate) const ROLLING_LAP_CSV_HEADERS: [&str; 17] = [
+    "Session UID",
+    "Started At",
+    "Game Year",
+    "Track",
+    "Session",
+    "Game Mode",
+    "AI Difficulty",
+    "Network Game",
+    "Driver",
+    "Team",
+    "Lap",
+    "Session Lap",
     "Position",
+    "Lap Time [ms]",
+    "Phase",
+    "Compound",
+    "Stint",
+];
+
+/// Further columns of the laps CSV by session kind, unless selected by [`SessionOptions::lap_columns`]:
+/// positions matter in races, practice runs in the other sessions
+const LAP_COLUMN_PROFILES: [(SessionKind, &[&str]); 6] = [
+    (SessionKind::Race, &["position", "positions_gained", "session_time", "phase"]),
+    (SessionKind::SprintShootout, &["run", "session_time", "phase"]),
+    (SessionKind::Qualifying, &["run", "session_time", "phase"]),
+    (SessionKind::Practice, &["run", "session_time", "phase"]),
+    (SessionKind::TimeTrial, &["session_time"]),
+    (SessionKind::Unknown, &["position", "positions_gained", "run", "session_time", "phase"]),
+];
+
+const POSITIONS_SUMMARY_HEADERS: [&str; 6] =
+    ["Driver", "Team", "Laps", "Positions Gained", "Positions Lost", "Net Positions Gained"];
+
+/// The old tyres are the ones the car entered the pit lane with, the new ones those it left with. Without a
+/// tyre change, e.g. for a drive through, both are the same set.
+const PIT_STOP_CSV_HEADERS: [&str; 20] = [
+    "Driver",
+    "Team",
+    "Lap",
+    "Pit Entry [ms]",
+    "Pit Exit [ms]",
+    "Pit Lane Time [ms]",
+    "Stationary Time [ms]",
+    "Time Loss [ms]",
+    "Penalty",
+    "Old Compound",
+    "Old Tyre Age [laps]",
+    "New Compound",
+    "New Tyre Age [laps]",
+    "Tyres Changed",
+    "Rival",
+    "Pace Before [ms]",
+    "Pace After [ms]",
+    "Pit Loss [ms]",
+    "Net Swing [s]",
+    "Quantifiable",
+];
+
+const PIT_STOP_DRIVER_SUMMARY_HEADERS: [&str; 6] =
+    ["Driver", "Team", "Stops", "Best Stationary [ms]", "Average Stationary [ms]", "Total Time Loss [ms]"];
+
+const LOCATION_SUMMARY_HEADERS: [&str; 2] = ["Location Bin [m]", "Overtakes"];
+
+const HEATMAP_CSV_HEADERS: [&str; 5] = ["Bin Start [m]", "Bin End [m]", "Bin Start [%]", "Bin End [%]", "Overtakes"];
+
+/// Penalties logged during the race against the classification, mismatches usually mean a missed event
+const PENALTY_RECONCILIATION_HEADERS: [&str; 9] = [
+    "Driver",
+    "Team",
+    "Penalties Issued",
+    "Penalties Classified",
+    "Time Penalties [s]",
+    "Served In Race",
+    "Unserved",
+    "Added To Race Time [s]",
+    "Mismatch",
+];
+
+const GRID_DROP_SUMMARY_HEADERS: [&str; 5] = ["Driver", "Team", "Qualified", "Grid", "Places Dropped"];
+
+const PIT_STOP_TEAM_SUMMARY_HEADERS: [&str; 4] = ["Team", "Stops", "Best Stationary [ms]", "Average Stationary [ms]"];
+
+const PERFORMANCE_CSV_HEADERS: [&str; 7] = [
+    "Team",
+    "Drivers",
+    "Avg Best Sector 1 [ms]",
+    "Avg Best Sector 2 [ms]",
+    "Avg Best Sector 3 [ms]",
+    "Avg Top Speed [km/h]",
+    "Avg Race Pace [ms]",
+];
+
+const MAPPING_CSV_HEADERS: [&str; 2] = ["Pseudonym", "Name"];
+
+const AMENDMENT_CSV_HEADERS: [&str; 6] =
+    ["Driver", "Team", "Provisional Position", "Final Position", "Positions Gained", "Penalty Time Added [s]"];
+
+const CONSISTENCY_CSV_HEADERS: [&str; 7] = [
     "Driver",
     "Team",
-    "Grid Position",
+    "Clean Laps",
+    "Average Lap Time [ms]",
+    "Lap Time Std Dev [ms]",
     "Fastest Lap Time [ms]",
-    "Finish Time [ms]",
-    "Laps",
-    "Pitstops",
-    "Penalties",
-    "Penalty Time [s]",
-    "Status",
+    "Stints",
+];
+
+const RUN_CSV_HEADERS: [&str; 9] = [
+    "Driver",
+    "Team",
+    "Run",
+    "Compound",
+    "Timed Laps",
+    "Best Lap [ms]",
+    "Average Lap [ms]",
+    "Fuel At Start [kg]",
+    "Tyre Age At End [laps]",
+];
+
+const FORECAST_CSV_HEADERS: [&str; 7] = [
+    "Sessiontime [ms]",
+    "Session",
+    "Time Offset [min]",
+    "Weather",
+    "Track Temperature [C]",
+    "Air Temperature [C]",
+    "Rain [%]",
+];
+
+const CONDITIONS_CSV_HEADERS: [&str; 5] =
+    ["Sessiontime [ms]", "Weather", "Track Temperature [C]", "Air Temperature [C]", "Rain [%]"];
+
+/// Minimum session time between two logged forecasts, as the session packet repeats constantly
+const FORECAST_LOG_INTERVAL_MS: u32 = 60_000;
+
+/// Look-ahead of the rain probability reported on the console
+const RAIN_LOOKAHEAD_MINUTES: u8 = 30;
+
+const CONTROLS_CSV_HEADERS: [&str; 7] =
+    ["Driver", "Team", "Fuel Mix", "ERS Mode", "Lap", "Track Position", "Sessiontime [ms]"];
+
+const MARKER_CSV_HEADERS: [&str; 3] = ["Offset [s]", "Timecode", "Label"];
+
+const VOD_MARKER_CSV_HEADERS: [&str; 4] = ["Timecode", "Offset [s]", "Type", "Description"];
+
+/// The distance is where the minimum speed was reached. Speed and distance stay blank for passes with too few
+/// samples, and "Clear Minimum" is "No" where the speed was lowest at the corner's entry or exit.
+const CORNER_SPEED_CSV_HEADERS: [&str; 6] =
+    ["Lap", "Corner", "Min Speed [km/h]", "Lap Distance [m]", "Samples", "Clear Minimum"];
+
+const STYLE_CSV_HEADERS: [&str; 7] =
+    ["Driver", "Team", "Lap", "Gear Changes", "Hard Brakings", "Full Throttle [%]", "Reliable"];
+
+const UNKNOWN_EVENT_CSV_HEADERS: [&str; 3] = ["Sessiontime [ms]", "Event", "Payload"];
+
+const RAW_EVENT_CSV_HEADERS: [&str; 5] = ["Sessiontime [ms]", "Code", "Vehicle 1", "Vehicle 2", "Payload"];
+
+/// Raw events kept until the session packet of their session arrives, more than the game sends before it
+const MAX_PENDING_RAW_EVENTS: usize = 64;
+
+const TRACE_CSV_HEADERS: [&str; 4] = ["Sessiontime [ms]", "Lap", "X", "Z"];
+
+/// Registered columns of the CSV outputs, see [`schema::SCHEMA_VERSION`]. Laps and results are registered
+/// with their required columns, as the others can be selected, and the position table's driver columns depend
+/// on the participants.
+pub(crate) const OUTPUT_SCHEMAS: [OutputSchema; 26] = [
+    OutputSchema { output_type: "Events", columns: &OVERTAKE_CSV_HEADERS },
+    OutputSchema { output_type: "Collisions", columns: &COLLISION_CSV_HEADERS },
+    OutputSchema { output_type: "Penalties", columns: &PENALTY_CSV_HEADERS },
+    OutputSchema { output_type: "Results", columns: &["Position", "Driver", "Team"] },
+    OutputSchema { output_type: "Finishes", columns: &FINISH_CSV_HEADERS },
+    OutputSchema { output_type: "Amendments", columns: &AMENDMENT_CSV_HEADERS },
+    OutputSchema { output_type: "Mapping", columns: &MAPPING_CSV_HEADERS },
+    OutputSchema { output_type: "Laps", columns: &["Driver", "Team", "Lap", "Session Lap"] },
+    OutputSchema { output_type: "RollingLaps", columns: &ROLLING_LAP_CSV_HEADERS },
+    OutputSchema { output_type: "Positions", columns: &["Lap"] },
+    OutputSchema { output_type: "PitStops", columns: &PIT_STOP_CSV_HEADERS },
+    OutputSchema { output_type: "Consistency", columns: &CONSISTENCY_CSV_HEADERS },
+    OutputSchema { output_type: "Performance", columns: &PERFORMANCE_CSV_HEADERS },
+    OutputSchema { output_type: "Runs", columns: &RUN_CSV_HEADERS },
+    OutputSchema { output_type: "Forecast", columns: &FORECAST_CSV_HEADERS },
+    OutputSchema { output_type: "Conditions", columns: &CONDITIONS_CSV_HEADERS },
+    OutputSchema { output_type: "Controls", columns: &CONTROLS_CSV_HEADERS },
+    OutputSchema { output_type: "Markers", columns: &MARKER_CSV_HEADERS },
+    OutputSchema { output_type: "VodMarkers", columns: &VOD_MARKER_CSV_HEADERS },
+    OutputSchema { output_type: "CornerSpeeds", columns: &CORNER_SPEED_CSV_HEADERS },
+    OutputSchema { output_type: "Heatmap", columns: &HEATMAP_CSV_HEADERS },
+    OutputSchema { output_type: "Style", columns: &STYLE_CSV_HEADERS },
+    OutputSchema { output_type: "UnknownEvents", columns: &UNKNOWN_EVENT_CSV_HEADERS },
+    OutputSchema { output_type: "RawEvents", columns: &RAW_EVENT_CSV_HEADERS },
+    OutputSchema { output_type: "Trace", columns: &TRACE_CSV_HEADERS },
+    OutputSchema { output_type: "Timeline", columns: &TIMELINE_CSV_HEADERS },
 ];
 
+/// Player index reported in the packet header while spectating
+const NO_PLAYER_INDEX: u8 = 255;
+
+/// Session UID reported by the game outside of an actual session, e.g. in menus
+const NO_SESSION_UID: u64 = 0;
+
+/// Minimum session time between two flushes of the output files
+const FLUSH_INTERVAL_MS: u32 = 1000;
+
+/// Maximum number of cars in a session
+const MAX_CARS: u8 = 22;
+
+/// Points paying positions for the fastest lap bonus without a points scheme
+const DEFAULT_PAYING_POSITIONS: usize = 10;
+
+/// Laps to go from which changes around the fastest lap are announced on the console
+const FINAL_LAPS: u8 = 3;
+
+/// Outputs managed by the [`WriterRegistry`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogCategory {
+    Overtakes,
+    Collisions,
+    Penalties,
+    Laps,
+    Results,
+}
+
+impl LogCategory {
+    /// Output type in the filename
+    fn file_type(&self) -> &'static str {
+        match self {
+            LogCategory::Overtakes => "Events",
+            LogCategory::Collisions => "Collisions",
+            LogCategory::Penalties => "Penalties",
+            LogCategory::Laps => "Laps",
+            LogCategory::Results => "Results",
+        }
+    }
+}
+
+/// A completed lap with its car and the context of its session, written column by column, so the laps CSV and
+/// the rolling file fill the columns they share alike
+struct LapRow<'a> {
+    context: &'a SessionContext<'a>,
+    car: &'a ParticipantData,
+    lap: &'a LapSnapshot,
+    run: Option<u32>,
+    /// Tyres and stint the car started the lap on
+    start_tyres: Option<&'a (TyreSet, usize)>,
+}
+
+impl LapRow<'_> {
+    /// Appends the value of the column of [`LAP_COLUMNS`] with the given key
+    fn write_column(&self, key: &str, identify_by: IdentifyBy, row: &mut RowBuffer) {
+        let (lap, metadata, start_tyres) = (self.lap, self.context.metadata, self.start_tyres);
+        match key {
+            "driver" => row.display(teams::driver_label(self.car, identify_by)),
+            "team" => row.display(teams::team_label(self.car, self.context.game_year, identify_by)),
+            "lap" => row.display(lap.lap),
+            "session_lap" => row.display(lap.session_lap),
+            "position" => row.display(lap.end_position),
+            "positions_gained" => row.display(lap.positions_gained()),
+            "run" => row.display_opt(self.run),
+            "session_time" => row.display(lap.session_time),
+            "lap_time" => row.display_opt((lap.lap_time_ms > 0).then_some(lap.lap_time_ms)),
+            "phase" => row.text(&lap.phases.label()),
+            "session_uid" => row.display_opt(metadata.map(|metadata| metadata.session_uid)),
+            "started_at" => row.text(metadata.map_or("", |metadata| metadata.started_at.as_str())),
+            "game_year" => row.display_opt(metadata.map(|metadata| metadata.game_year)),
+            "track" => row.text(metadata.map_or("", |metadata| metadata.track.as_str())),
+            "session_type" => row.text(metadata.map_or("", |metadata| metadata.session_type.as_str())),
+            "game_mode" => row.text(metadata.and_then(|metadata| metadata.game_mode.as_deref()).unwrap_or_default()),
+            "ai_difficulty" => row.display_opt(metadata.and_then(|metadata| metadata.ai_difficulty)),
+            "network_game" => row.text(bool_label(metadata.and_then(|metadata| metadata.network_game))),
+            "compound" => row.text(start_tyres.map(|(tyres, _)| tyres.compound.as_str()).unwrap_or_default()),
+            "stint" => row.display_opt(start_tyres.map(|&(_, stint)| stint)),
+            key => unreachable!("Unknown lap column {}", key),
+        };
+    }
+}
+
+/// Names of the session's track and type in its output filenames, taken when the session starts
+#[derive(Debug, Default)]
+struct OutputNames {
+    track: String,
+    session: String,
+}
+
+/// Names and creates the files of the current session. Borrowed from the session state field by field, so
+/// files can be created while its writers are borrowed.
+struct OutputFiles<'a> {
+    options: &'a SessionOptions,
+    names: &'a OutputNames,
+    session_uid: u64,
+    started_at: DateTime<Local>,
+}
+
+impl OutputFiles<'_> {
+    /// Path of an output file of the session
+    fn path(&self, event_type: &str, extension: &str) -> path::PathBuf {
+        let context = FilenameContext {
+            track: &self.names.track,
+            session: &self.names.session,
+            session_uid: self.session_uid,
+            started_at: self.started_at,
+        };
+
+        let filename = path::PathBuf::from(format!(
+            "{}.{}",
+            filenames::render_filename(&self.options.filename_template, &context, event_type),
+            extension
+        ));
+        // Templates may put each session into its own directory, a failure surfaces when the file is created
+        if let Some(directory) = filename.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            if let Err(err) = fs::create_dir_all(directory) {
+                println!("Failed to create the output directory {:?}: {}", directory, err);
+            }
+        }
+        filename
+    }
+
+    /// A new CSV file of the session with the headers written, along with its path
+    fn create_csv(&self, event_type: &str, headers: &[&str]) -> io::Result<(path::PathBuf, csv::Writer<fs::File>)> {
+        let filename = if self.options.schema_check {
+            schema::check_headers(&OUTPUT_SCHEMAS, event_type, headers)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            self.path(&schema::versioned_type(event_type), "csv")
+        } else {
+            self.path(event_type, "csv")
+        };
+        println!("Writing {} to {:?}", event_type.to_lowercase(), &filename);
+
+        let mut writer = csv::Writer::from_path(&filename)?;
+        writer.write_record(headers)?;
+        Ok((filename, writer))
+    }
+
+    fn create_sink(&self, event_type: &str, headers: &[&str]) -> io::Result<(path::PathBuf, CsvSink)> {
+        let (filename, writer) = self.create_csv(event_type, headers)?;
+        let sink = CsvSink::new(event_type, writer);
+        let sink = if self.options.verify_after_write { sink.verify_after_write(filename.clone()) } else { sink };
+        Ok((filename, sink))
+    }
+}
+
+/// The session's outputs by category. Categories are enabled when the session starts, but their files are
+/// only created with the first row, unless created right away to always have the file, and all open writers
+/// are flushed and closed together.
+#[derive(Default)]
+struct WriterRegistry {
+    enabled: Vec<LogCategory>,
+    sinks: Vec<(LogCategory, Box<dyn EventSink>)>,
+}
+
+impl WriterRegistry {
+    fn enable(&mut self, category: LogCategory) {
+        if !self.is_enabled(category) {
+            self.enabled.push(category);
+        }
+    }
+
+    fn is_enabled(&self, category: LogCategory) -> bool {
+        self.enabled.contains(&category)
+    }
+
+    fn get(&self, category: LogCategory) -> Option<&dyn EventSink> {
+        self.sinks.iter().find(|(other, _)| *other == category).map(|(_, sink)| sink.as_ref())
+    }
+
+    fn get_mut(&mut self, category: LogCategory) -> Option<&mut dyn EventSink> {
+        self.sinks.iter_mut().find(|(other, _)| *other == category).map(|(_, sink)| sink.as_mut())
+    }
+
+    /// The category's writer, created with `create` on first use. `None` while the category is disabled.
+    fn get_or_create(
+        &mut self,
+        category: LogCategory,
+        create: impl FnOnce() -> io::Result<Box<dyn EventSink>>,
+    ) -> io::Result<Option<&mut dyn EventSink>> {
+        if !self.is_enabled(category) {
+            return Ok(None);
+        }
+
+        let i = match self.sinks.iter().position(|(other, _)| *other == category) {
+            Some(i) => i,
+            None => {
+                self.sinks.push((category, create()?));
+                self.sinks.len() - 1
+            },
+        };
+        Ok(Some(self.sinks[i].1.as_mut()))
+    }
+
+    fn sinks_mut(&mut self) -> impl Iterator<Item = &mut dyn EventSink> {
+        self.sinks.iter_mut().map(|(_, sink)| sink.as_mut())
+    }
+
+    /// Disables the category and hands out its writer for closing
+    fn disable(&mut self, category: LogCategory) -> Option<Box<dyn EventSink>> {
+        self.enabled.retain(|other| *other != category);
+        self.close(category)
+    }
+
+    /// Hands out the category's writer for closing, so the next row creates a new file
+    fn close(&mut self, category: LogCategory) -> Option<Box<dyn EventSink>> {
+        let i = self.sinks.iter().position(|(other, _)| *other == category)?;
+        Some(self.sinks.remove(i).1)
+    }
+
+    /// Hands out all writers for closing and disables all categories until the next session enables them
+    fn close_all(&mut self) -> Vec<Box<dyn EventSink>> {
+        self.enabled.clear();
+        self.sinks.drain(..).map(|(_, sink)| sink).collect()
+    }
+}
+
+/// User configurable behaviour of the session logging
+#[derive(Debug, Clone)]
+pub(crate) struct SessionOptions {
+    /// Minimum interval between two points of the player position trace, `None` disables the trace
+    pub(crate) player_trace_interval_ms: Option<u32>,
+    pub(crate) positions_format: PositionsFormat,
+    /// Maximum time between an overtake and a collision of the same two cars to cross-reference them
+    pub(crate) cross_reference_window_ms: u32,
+    /// Scheme used for the points column of the classification, `None` omits the column
+    pub(crate) points_scheme: Option<PointsScheme>,
+    /// Template for output filenames, see [`filenames::render_filename`]
+    pub(crate) filename_template: String,
+    /// Log events without dedicated handling to an "UnknownEvents" CSV
+    pub(crate) log_unknown_events: bool,
+    /// Log every event packet from its raw bytes, see [`RawEvent`]
+    pub(crate) log_raw_events: bool,
+    /// Columns of the laps CSV instead of the session type's profile, see [`LAP_COLUMN_PROFILES`]
+    pub(crate) lap_columns: Option<ColumnSelection>,
+    /// CSV the laps of every session are appended to, kept across sessions and runs, see [`rolling`]
+    pub(crate) rolling_laps: Option<path::PathBuf>,
+    /// Columns of the results CSV instead of all of [`CLASSIFICATION_COLUMNS`]
+    pub(crate) classification_columns: Option<ColumnSelection>,
+    /// Check the columns of every CSV against [`OUTPUT_SCHEMAS`] and add the schema version to the filenames
+    pub(crate) schema_check: bool,
+    /// Order of the events within a frame, applied by the pipeline
+    pub(crate) event_order: EventOrder,
+    /// Write all events into one chronological "Timeline" CSV when the session ends
+    pub(crate) timeline: bool,
+    /// Publish every event to Kafka
+    pub(crate) kafka: Option<KafkaConfig>,
+    /// SQLite database the sessions are also written to, see [`ReplayDb`]
+    pub(crate) replay_db: Option<path::PathBuf>,
+    /// Unix domain socket every event is streamed to, see [`UnixSocketSink`]
+    pub(crate) unix_socket: Option<path::PathBuf>,
+    /// StatsD endpoint metrics are pushed to, see [`StatsdSink`]
+    pub(crate) statsd: Option<SocketAddr>,
+    /// Address the session metadata is served at, see [`MetadataServer`]
+    pub(crate) metadata_http: Option<SocketAddr>,
+    /// Log fuel mix and ERS mode changes of all cars instead of only the player's
+    pub(crate) controls_all_cars: bool,
+    /// Sessions with fewer completed laps are discarded when they end
+    pub(crate) min_session_laps: usize,
+    /// Sessions shorter than this are discarded when they end
+    pub(crate) min_session_secs: u32,
+    /// Report the classification's penalty time in milliseconds like the other times instead of seconds
+    pub(crate) penalty_time_ms: bool,
+    /// Sync the session's files to disk when it ends, so they survive a power loss right after a race
+    pub(crate) fsync: bool,
+    /// Read the last row of each CSV back after flushing and warn if it differs
+    pub(crate) verify_after_write: bool,
+    /// Carry the qualifying classification to the next sessions of a race weekend on the same track
+    pub(crate) link_weekend: bool,
+    /// Only write overtakes for this position or better to the events CSV
+    pub(crate) overtake_min_position: Option<u8>,
+    /// Only write overtakes with at least this speed difference in km/h to the events CSV
+    pub(crate) overtake_min_speed_delta: Option<u16>,
+    /// Leave overtakes with a car in the pit lane out of the events CSV
+    pub(crate) exclude_pit_overtakes: bool,
+    /// Write a "Markers" CSV with the time of each logged overtake since the race start, for video editors
+    pub(crate) markers: bool,
+    /// Show a desktop notification for each significant overtake involving the player
+    pub(crate) notify: bool,
+    /// How drivers are identified in all outputs
+    pub(crate) identify_by: IdentifyBy,
+    /// Name of the drivers sent without one
+    pub(crate) empty_names: EmptyNames,
+    /// Maximum time between two overtakes of the same cars in opposite roles to flag the second as reversal
+    pub(crate) reversal_window_ms: u32,
+    /// Interval of the "Conditions" CSV rows when the weather doesn't change, `None` disables the file
+    pub(crate) conditions_interval_ms: Option<u32>,
+    /// Show live sector and lap deltas on the console in time trial
+    pub(crate) live_display: bool,
+    /// Keep a progress line on the console, updated in place
+    pub(crate) progress: bool,
+    /// Write a Markdown report of the race when the session ends
+    pub(crate) report_md: bool,
+    /// Car whose events are highlighted on the console and which leads the summary, e.g. for commentary
+    pub(crate) follow_car: Option<FollowCar>,
+    /// Follow the car the camera follows while spectating, if no car is given
+    pub(crate) follow_spectated: bool,
+    /// Drivers whose events are logged, `None` logs all
+    pub(crate) drivers: Option<DriverSelection>,
+    /// Cleanup of old output files, applied whenever a session ends
+    pub(crate) retention: RetentionPolicy,
+    /// DRS zones of the tracks, for flagging overtakes within them
+    pub(crate) drs_zones: DrsZones,
+    /// Corners of the tracks for the player's minimum corner speeds, `None` disables the "CornerSpeeds" CSV
+    pub(crate) corners: Option<Corners>,
+    /// Width of the location bins of overtakes in metres
+    pub(crate) location_bin_m: u16,
+    /// Bin width of the overtake "Heatmap" CSV, `None` disables it
+    pub(crate) heatmap_bin_m: Option<u16>,
+    /// Race recording the significant events are mapped onto when the session ends, `None` disables it
+    pub(crate) vod: Option<VodConfig>,
+    /// Add the heuristic "Grip Advantage" of the overtaker's tyres to the overtakes
+    pub(crate) grip_advantage: bool,
+    /// Add each car's compound and tyre age at the line to the results
+    pub(crate) final_tyres: bool,
+    /// Adds stable driver id columns to the events and results, `None` omits them
+    pub(crate) driver_ids: Option<DriverIds>,
+    /// Replace the driver names with pseudonyms, writing the real names to a "Mapping" CSV only
+    pub(crate) anonymize: bool,
+}
+
 pub(crate) struct SessionState {
+    options: SessionOptions,
+
     session_info: Option<PacketSessionData>,
     session_uid: u64,
+    session_started_at: DateTime<Local>,
+    /// Track and session type naming the session's output files
+    output_names: OutputNames,
+    /// Session time when the session started, relating the session time to the wall clock
+    session_start_time: u32,
+    session_duration_ms: u32,
+    metadata: Option<SessionMetadata>,
+    /// Files written for the current session, deleted again if the session turns out too short
+    created_files: Vec<path::PathBuf>,
+    game_year: u16,
+    last_flush_time: u32,
+    player_car_index: Option<u8>,
+    secondary_player_car_index: Option<u8>,
     pub(crate) cars: Vec<ParticipantData>,
     pub(crate) car_status: Vec<CarStatusData>,
     pub(crate) lap_data: Vec<LapData>,
+    /// Number of cars reported by the participants packet
+    num_active_cars: Option<u8>,
+    /// Last array lengths warned about, so a persisting mismatch is only reported once
+    car_count_mismatch: Option<(usize, usize, usize, u8)>,
+    /// Index of the car given by [`SessionOptions::follow_car`], once resolved from the participants
+    followed_car: Option<u8>,
+    /// Car the camera follows while spectating
+    spectated_car: Option<u8>,
+    /// Indexes of the cars given by [`SessionOptions::drivers`], none before the participants are known
+    selected_cars: Option<Vec<u8>>,
 
     car_speeds: Vec<u16>,
-    csv_writer: Option<csv::Writer<fs::File>>,
+    /// Highest speed of each car this session
+    top_speeds: Vec<u16>,
+    writers: WriterRegistry,
+    recent_overtakes: RecentPairEvents,
+    recent_collisions: RecentPairEvents,
+    /// Overtakes by overtaker and overtakee, for detecting positions given back
+    recent_passes: RecentPairEvents,
+    /// Session time of the latest overtake and the number of overtakes by the unordered pair of cars
+    pair_swaps: HashMap<(u8, u8), (u32, u32)>,
+    /// Overtakes left out of the events CSV by the significance filter
+    filtered_overtakes: u64,
+    /// Indexes into [`LAP_COLUMNS`] of the session's laps CSV
+    lap_columns: Vec<usize>,
+    /// Reused for the rows written most often, the overtakes and laps
+    row: RowBuffer,
+    lap_history: LapHistory,
+    fastest_lap: Option<FastestLapHolder>,
+    /// Progress line last printed, to only redraw it when it changes
+    progress_line: String,
+    /// Whether the fastest lap holder was in the points when last checked, to announce changes late in races
+    fastest_lap_in_points: Option<bool>,
+    position_holds: PositionHolds,
+    gaps: GapTracker,
+    pit_stops: PitStopTracker,
+    runs: RunTracker,
+    time_trial_display: TimeTrialDisplay,
+    notifier: DesktopNotifier,
+    race_report: RaceReport,
+    penalty_ledger: PenaltyLedger,
+    /// Number of penalties and penalty seconds of each car from the final classification
+    classified_penalties: Vec<(u8, u8)>,
+    /// Position and penalty seconds by car index of the session's first classification, which later
+    /// classifications are compared against
+    provisional_classification: Vec<(u8, u8)>,
+    /// Session whose classification was written, with the packet's results, to ignore repetitions of it
+    classification_written_for: Option<u64>,
+    last_classification: String,
+    /// Grid position of each car from the final classification of a race
+    grid_positions: Vec<u8>,
+    /// Cars with a logged grid penalty, from the penalty events or inferred
+    grid_penalised: Vec<usize>,
+    /// Tyres of each car when it was last racing, kept once it finishes or retires
+    final_tyres: Vec<Option<TyreSet>>,
+    /// Tyres and stint of each car when it started its current lap, for the laps CSV
+    lap_start_tyres: Vec<Option<(TyreSet, usize)>>,
+    anonymizer: Anonymizer,
+    /// Whether drivers without a name were reported this session
+    empty_names_reported: bool,
+    /// Whether pseudonyms were assigned since the mapping was last written
+    mapping_pending: bool,
+    /// Position by race number from the final classification of a qualifying
+    qualifying: Vec<(u8, u8)>,
+    /// The previous session of the race weekend, only linked with `--link-weekend`
+    weekend: Option<WeekendLink>,
+    overtake_locations: LocationCounts,
+    overtake_heatmap: Heatmap,
+    /// Significant events within the race recording, see [`SessionOptions::vod`]
+    vod_markers: Vec<VodMarker>,
+    telemetry_rate: RateMeter,
+    faults: FaultTracker,
+
+    trace_writer: Option<CsvSink>,
+    compact_trace_writer: Option<CompactPositionsWriter<BufWriter<fs::File>>>,
+    last_trace_time: Option<u32>,
+    unknown_events_writer: Option<CsvSink>,
+    raw_events_writer: Option<CsvSink>,
+    /// Raw events of a session whose session packet hasn't arrived yet, with their session uid
+    pending_raw_events: Vec<(u64, [String; 5])>,
+    /// Datagrams of events without dedicated handling, for logging them with their code and bytes
+    unhandled_events: UnhandledEvents,
+    controls: ControlTracker,
+    timeline: Timeline,
+    kafka_sink: Option<KafkaSink>,
+    replay_db: Option<ReplayDb>,
+    socket_sink: Option<UnixSocketSink>,
+    statsd_sink: Option<StatsdSink>,
+    metadata_server: Option<MetadataServer>,
+    controls_writer: Option<CsvSink>,
+    /// Session time of the lights out, the time origin of the markers
+    race_start_time: Option<u32>,
+    markers_writer: Option<CsvSink>,
+    corner_speeds: CornerSpeedTracker,
+    corner_speeds_writer: Option<CsvSink>,
+    driving_style: StyleTracker,
+    style_writer: Option<CsvSink>,
+    /// Open for the whole run rather than a session, closed by [`SessionState::shutdown`]
+    rolling_laps_writer: Option<CsvSink>,
+
+    forecast: Vec<ForecastSample>,
+    last_forecast_time: Option<u32>,
+    forecast_writer: Option<CsvSink>,
+    conditions: Option<(Conditions, u32)>,
+    conditions_writer: Option<CsvSink>,
+
+    // Finish line tracking, from the cars' result status and laps as the flag event may arrive after the winner
+    chequered_flag_shown: bool,
+    finished_cars: Vec<bool>,
+    winner_finish_time: Option<u32>,
+    finishes_writer: Option<CsvSink>,
 }
 
 impl SessionState {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(options: SessionOptions) -> Self {
+        let kafka_sink = options.kafka.as_ref().and_then(|config| match KafkaSink::new(config) {
+            Ok(sink) => Some(sink),
+            Err(err) => {
+                println!("ERROR: {}", err);
+                None
+            },
+        });
+
+        let replay_db = options.replay_db.as_deref().and_then(|path| match ReplayDb::open(path) {
+            Ok(db) => Some(db),
+            Err(err) => {
+                println!("ERROR: {}", err);
+                None
+            },
+        });
+
+        let rolling_laps_writer =
+            options.rolling_laps.as_deref().and_then(|path| match rolling::open(path, &ROLLING_LAP_CSV_HEADERS) {
+                Ok(sink) => Some(sink),
+                Err(err) => {
+                    println!("ERROR: Failed to open the rolling laps file {:?}: {}", path, err);
+                    None
+                },
+            });
+
+        let socket_sink = options.unix_socket.as_deref().and_then(|path| match UnixSocketSink::bind(path) {
+            Ok(sink) => Some(sink),
+            Err(err) => {
+                println!("ERROR: {}", err);
+                None
+            },
+        });
+
+        let statsd_sink = options.statsd.and_then(|addr| match StatsdSink::connect(addr) {
+            Ok(sink) => Some(sink),
+            Err(err) => {
+                println!("ERROR: {}", err);
+                None
+            },
+        });
+
+        let metadata_server = options.metadata_http.and_then(|addr| match MetadataServer::bind(addr) {
+            Ok(server) => Some(server),
+            Err(err) => {
+                println!("ERROR: {}", err);
+                None
+            },
+        });
+
         Self {
+            recent_overtakes: RecentPairEvents::new(options.cross_reference_window_ms),
+            recent_collisions: RecentPairEvents::new(options.cross_reference_window_ms),
+            recent_passes: RecentPairEvents::ordered(options.reversal_window_ms),
+            pair_swaps: HashMap::new(),
+            options,
             session_info: None,
             session_uid: u64::MIN,
+            session_started_at: Local::now(),
+            output_names: OutputNames::default(),
+            session_start_time: 0,
+            session_duration_ms: 0,
+            metadata: None,
+            created_files: Vec::new(),
+            game_year: 0,
+            last_flush_time: 0,
+            player_car_index: None,
+            secondary_player_car_index: None,
             cars: Vec::with_capacity(22), // Pre-allocate for max F1 grid size
             car_status: Vec::with_capacity(22),
             lap_data: Vec::with_capacity(22),
+            num_active_cars: None,
+            car_count_mismatch: None,
+            followed_car: None,
+            selected_cars: options.drivers.as_ref().map(|_| Vec::new()),
+            spectated_car: None,
             car_speeds: Vec::with_capacity(22),
-            csv_writer: None,
+            top_speeds: Vec::with_capacity(22),
+            writers: WriterRegistry::default(),
+            filtered_overtakes: 0,
+            lap_columns: Vec::new(),
+            row: RowBuffer::default(),
+            lap_history: LapHistory::default(),
+            fastest_lap: None,
+            fastest_lap_in_points: None,
+            progress_line: String::new(),
+            position_holds: PositionHolds::default(),
+            gaps: GapTracker::default(),
+            pit_stops: PitStopTracker::default(),
+            runs: RunTracker::default(),
+            time_trial_display: TimeTrialDisplay::default(),
+            notifier: DesktopNotifier::default(),
+            race_report: RaceReport::default(),
+            penalty_ledger: PenaltyLedger::default(),
+            classified_penalties: Vec::new(),
+            provisional_classification: Vec::new(),
+            classification_written_for: None,
+            last_classification: String::new(),
+            grid_positions: Vec::new(),
+            grid_penalised: Vec::new(),
+            final_tyres: Vec::new(),
+            lap_start_tyres: Vec::new(),
+            anonymizer: Anonymizer::default(),
+            empty_names_reported: false,
+            mapping_pending: false,
+            qualifying: Vec::new(),
+            weekend: None,
+            overtake_locations: LocationCounts::default(),
+            overtake_heatmap: Heatmap::default(),
+            vod_markers: Vec::new(),
+            telemetry_rate: RateMeter::default(),
+            faults: FaultTracker::default(),
+            trace_writer: None,
+            compact_trace_writer: None,
+            last_trace_time: None,
+            unknown_events_writer: None,
+            raw_events_writer: None,
+            pending_raw_events: Vec::new(),
+            unhandled_events: UnhandledEvents::default(),
+            controls: ControlTracker::default(),
+            timeline: Timeline::default(),
+            kafka_sink,
+            replay_db,
+            socket_sink,
+            statsd_sink,
+            metadata_server,
+            controls_writer: None,
+            race_start_time: None,
+            markers_writer: None,
+            corner_speeds: CornerSpeedTracker::default(),
+            corner_speeds_writer: None,
+            driving_style: StyleTracker::default(),
+            style_writer: None,
+            rolling_laps_writer,
+            forecast: Vec::new(),
+            last_forecast_time: None,
+            forecast_writer: None,
+            conditions: None,
+            conditions_writer: None,
+            chequered_flag_shown: false,
+            finished_cars: Vec::with_capacity(22),
+            winner_finish_time: None,
+            finishes_writer: None,
         }
     }
 
     pub(crate) fn is_logging_enabled(&self) -> bool {
-        self.csv_writer.is_some()
+        self.writers.is_enabled(LogCategory::Overtakes)
+    }
+
+    /// Files created by the current session
+    pub(crate) fn open_files(&self) -> &[path::PathBuf] {
+        &self.created_files
+    }
+
+    /// Events written this session and the current leader with their lap, for the status line
+    pub(crate) fn status(&self) -> String {
+        let events = self.writers.get(LogCategory::Overtakes).map_or(0, |sink| sink.written());
+        let leader = self
+            .lap_data
+            .iter()
+            .enumerate()
+            .find(|(_, lap)| lap.car_position == 1)
+            .and_then(|(i, lap)| Some((self.cars.get(i)?, lap.current_lap_num)));
+
+        let status = match leader {
+            Some((car, lap)) => format!(
+                "events {} | leader {} lap {}",
+                events,
+                teams::format_driver(car, self.options.identify_by),
+                lap
+            ),
+            None => format!("events {} | no leader", events),
+        };
+
+        match self.fastest_lap.and_then(|holder| Some((holder, self.cars.get(holder.car_idx as usize)?))) {
+            Some((holder, car)) => format!(
+                "{} | fastest lap {} {}{}",
+                status,
+                teams::format_driver(car, self.options.identify_by),
+                display::format_time(holder.lap_time_ms),
+                match self.fastest_lap_in_points() {
+                    Some(true) => " (in the points)",
+                    Some(false) => " (outside the points)",
+                    None => "",
+                }
+            ),
+            None => status,
+        }
+    }
+
+    /// Stores the game year and player indexes carried by every packet header, treating invalid player
+    /// indexes as "no player"
+    pub(crate) fn update_from_header(&mut self, header: &PacketHeader) {
+        self.game_year = header.packet_format;
+        if header.session_uid == self.session_uid {
+            self.session_duration_ms = self.session_duration_ms.max(header.session_time);
+        }
+
+        let player_car_index = Some(header.player_car_index).filter(|&idx| idx < MAX_CARS);
+        if player_car_index != self.player_car_index {
+            match player_car_index {
+                Some(idx) => println!("Player car index: {}", idx),
+                None if header.player_car_index == NO_PLAYER_INDEX => {
+                    println!("Spectating - player specific features are disabled")
+                },
+                None => println!(
+                    "Invalid player car index {} - player specific features are disabled",
+                    header.player_car_index
+                ),
+            }
+        }
+
+        self.player_car_index = player_car_index;
+        self.secondary_player_car_index = header.secondary_player_car_index.filter(|&idx| idx < MAX_CARS);
+    }
+
+    /// Whether any of the given cars is controlled by a local player, `None` when there is no player
+    fn involves_player(&self, car_indexes: &[u8]) -> Option<bool> {
+        let player = self.player_car_index?;
+        Some(car_indexes.iter().any(|&idx| idx == player || Some(idx) == self.secondary_player_car_index))
     }
 
     pub(crate) fn update_session(&mut self, session_data: PacketSessionData) -> io::Result<()> {
         // Only flush and update if session has changed
         if self.session_uid != session_data.header.session_uid {
-            if let Some(writer) = self.csv_writer.as_mut() {
-                writer.flush()?;
-            }
+            let previous_session = self.weekend_link().or_else(|| self.weekend.take());
+            self.close_session()?;
+            self.last_flush_time = 0;
+            self.forecast.clear();
+            self.last_forecast_time = None;
+            self.conditions = None;
             self.session_uid = session_data.header.session_uid;
+            self.pending_raw_events.retain(|(session_uid, _)| *session_uid == self.session_uid);
+            self.session_started_at = Local::now();
+            self.output_names = OutputNames {
+                track: session_data.track.name().to_string(),
+                session: sessions::session_name(&session_data.session_type).to_string(),
+            };
+            self.session_start_time = session_data.header.session_time;
+            self.session_duration_ms = 0;
+            self.metadata = None;
+            self.lap_history.clear();
+            self.fastest_lap = None;
+            self.fastest_lap_in_points = None;
+            self.empty_names_reported = false;
+            self.controls.clear();
+            self.timeline.clear();
+            for observer in self.observers_mut() {
+                observer.clear();
+            }
+            self.race_start_time = None;
+            self.runs.clear();
+            self.time_trial_display.clear();
+            self.race_report.clear();
+            self.penalty_ledger.clear();
+            self.classified_penalties.clear();
+            self.provisional_classification.clear();
+            self.grid_positions.clear();
+            self.grid_penalised.clear();
+            self.final_tyres.clear();
+            self.lap_start_tyres.clear();
+            self.qualifying.clear();
+            self.link_weekend(previous_session, &session_data);
+            self.overtake_locations.clear();
+            self.overtake_heatmap.clear();
+            self.vod_markers.clear();
+            self.telemetry_rate.clear();
+            self.faults.clear();
+            self.top_speeds.clear();
+            self.corner_speeds.clear();
+            let track = session_data.track.name();
+            if self.options.corners.as_ref().is_some_and(|corners| !corners.has_track(track)) {
+                println!("No corners defined for {} - skipping the corner speeds", track);
+            }
+            self.recent_overtakes.clear();
+            self.recent_collisions.clear();
+            self.recent_passes.clear();
+            self.pair_swaps.clear();
+            self.reset_finish_tracking()?;
 
-            self.csv_writer = if session_data.rule_set == Some(RuleSet::Race) {
-                Some(self.create_new_csv_writer(&session_data, "Events", &OVERTAKE_CSV_HEADERS)?)
+            if self.session_uid != NO_SESSION_UID {
+                self.writers.enable(LogCategory::Results);
+                if let Some(db) = self.replay_db.as_mut() {
+                    db.record_session(
+                        self.session_uid,
+                        session_data.track.name(),
+                        sessions::session_name(&session_data.session_type),
+                        self.session_started_at.to_rfc3339(),
+                    );
+                }
+            }
+            if self.session_uid == NO_SESSION_UID {
+                println!("No active session - skipping event logging");
+            } else if sessions::is_race(&session_data) {
+                self.enable_event_logging()?;
+            } else if SessionKind::of(&session_data.session_type) == SessionKind::SprintShootout {
+                println!("Sprint shootout - skipping event logging");
             } else {
                 println!("Not a race or sprint session - skipping event logging");
-                None
-            };
+            }
+
+            if self.session_uid != NO_SESSION_UID {
+                let kind = SessionKind::of(&session_data.session_type);
+                let profile = LAP_COLUMN_PROFILES.iter().find(|(other, _)| *other == kind).map_or(&[][..], |p| p.1);
+                self.lap_columns = columns::select_columns(
+                    &LAP_COLUMNS,
+                    LAP_REQUIRED_COLUMNS,
+                    profile,
+                    self.options.lap_columns.as_ref(),
+                );
+                let headers: Vec<&str> = self.lap_columns.iter().map(|&i| LAP_COLUMNS[i].header).collect();
+                self.writers.enable(LogCategory::Laps);
+                self.writer(LogCategory::Laps, &headers)?;
+            }
+
+            // The trace is useful in every session type, e.g. for comparing lines in practice
+            self.last_trace_time = None;
+            if self.options.player_trace_interval_ms.is_some() && self.session_uid != NO_SESSION_UID {
+                match self.options.positions_format {
+                    PositionsFormat::Csv => self.trace_writer = Some(self.create_sink("Trace", &TRACE_CSV_HEADERS)?),
+                    PositionsFormat::Compact => {
+                        let filename = self.output_path("Trace", "f1pos");
+                        println!("Writing trace to {:?}", &filename);
+                        self.compact_trace_writer = Some(CompactPositionsWriter::create(&filename)?);
+                        self.created_files.push(filename);
+                    },
+                }
+            }
+        }
+
+        // The first packets of a session may lack the rule set, the decision made from the session type is
+        // revisited once it arrives
+        let rule_set_arrived = self.session_info.as_ref().is_some_and(|previous| {
+            previous.header.session_uid == session_data.header.session_uid
+                && previous.rule_set.is_none()
+                && session_data.rule_set.is_some()
+        });
+        if rule_set_arrived && self.session_uid != NO_SESSION_UID {
+            let was_race = self.session_info.as_ref().is_some_and(sessions::is_race);
+            match (was_race, sessions::is_race(&session_data)) {
+                (false, true) => {
+                    println!("Rule set received - logging the race events after all");
+                    self.enable_event_logging()?;
+                },
+                (true, false) => {
+                    println!("Rule set received - not a race session, stopping the event logging");
+                    self.disable_event_logging()?;
+                },
+                _ => {},
+            }
         }
 
+        if let Some(previous) = self.session_info.as_ref() {
+            if session_data.header.session_uid == previous.header.session_uid
+                && session_data.safety_car_status != previous.safety_car_status
+            {
+                self.record_timeline(TimelineEntry {
+                    session_time: session_data.header.session_time,
+                    kind: "Safety Car",
+                    description: format!("Safety car status: {:?}", session_data.safety_car_status),
+                    details: serde_json::json!({ "previous": format!("{:?}", previous.safety_car_status) }),
+                });
+            }
+        }
+
+        if self.session_uid != NO_SESSION_UID {
+            self.update_metadata(&session_data)?;
+            self.update_forecast(&session_data)?;
+            self.update_conditions(&session_data)?;
+        }
+        self.update_spectated_car(&session_data);
         self.session_info = Some(session_data);
+        self.write_pending_raw_events();
+        self.print_progress();
 
         Ok(())
     }
 
-    pub(crate) fn handle_overtake(&mut self, event: &PacketEventData) -> Result<(), Box<dyn std::error::Error>> {
-        // Early return if no CSV writer or no car data
-        if self.csv_writer.is_none() || self.cars.is_empty() {
-            return Ok(());
+    /// Redraws the progress line in place when it changed, or after other console output cleared it
+    fn print_progress(&mut self) {
+        if !self.options.progress {
+            return;
         }
 
-        if let Event::Overtake(ot) = event.event {
-            let overtake_event = self.create_overtake_event(&ot, event.header.session_time)?;
-            self.write_overtake_event(&overtake_event)?;
+        let overtakes = self.writers.get(LogCategory::Overtakes).map_or(0, |sink| sink.written());
+        let total_laps = self.session_info.as_ref().map_or(0, |session| session.total_laps);
+        let leader = self
+            .lap_data
+            .iter()
+            .enumerate()
+            .find(|(_, lap)| lap.car_position == 1)
+            .and_then(|(i, lap)| Some((self.cars.get(i)?, lap.current_lap_num)));
+
+        let line = match leader {
+            Some((car, lap)) if total_laps > 0 => format!(
+                "Lap {}/{} | Leader {} | {} overtakes",
+                lap,
+                total_laps,
+                teams::format_driver(car, self.options.identify_by),
+                overtakes
+            ),
+            Some((car, lap)) => format!(
+                "Lap {} | Leader {} | {} overtakes",
+                lap,
+                teams::format_driver(car, self.options.identify_by),
+                overtakes
+            ),
+            None => format!("No leader | {} overtakes", overtakes),
+        };
+        if line == self.progress_line && display::progress_shown() {
+            return;
         }
 
+        display::print_progress(&line);
+        self.progress_line = line;
+    }
+
+    /// Starts logging the race events: overtakes, collisions and penalties
+    fn enable_event_logging(&mut self) -> io::Result<()> {
+        self.writers.enable(LogCategory::Overtakes);
+        // Created right away, so a race without collisions or penalties still has the files
+        self.writers.enable(LogCategory::Collisions);
+        self.writer(LogCategory::Collisions, &COLLISION_CSV_HEADERS)?;
+        self.writers.enable(LogCategory::Penalties);
+        self.writer(LogCategory::Penalties, &PENALTY_CSV_HEADERS)?;
         Ok(())
     }
 
-    pub(crate) fn write_final_classification(
-        &self,
-        fc: PacketFinalClassificationData,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let session_info = self
-            .session_info
-            .as_ref()
-            .ok_or_else(|| Box::<dyn std::error::Error>::from("No session info available"))?;
+    /// Stops logging the race events, keeping the rows written so far
+    fn disable_event_logging(&mut self) -> io::Result<()> {
+        let categories = [LogCategory::Overtakes, LogCategory::Collisions, LogCategory::Penalties];
+        let sinks: Vec<_> = categories.into_iter().filter_map(|category| self.writers.disable(category)).collect();
+        let fsync = self.options.fsync;
+        incomplete_outputs(sinks.into_iter().filter(|sink| !sink.close(fsync)).count())
+    }
 
-        let mut writer = self.create_new_csv_writer(session_info, "Results", &CLASSIFICATION_CSV_HEADERS)?;
+    /// Flushes and closes all writers of the current session, discarding its files if the session was
+    /// shorter than the configured minimum length. Called on session change and on shutdown.
+    pub(crate) fn close_session(&mut self) -> io::Result<()> {
+        if self.filtered_overtakes > 0 {
+            println!("{} overtakes were filtered out of the events file", self.filtered_overtakes);
+            self.filtered_overtakes = 0;
+        }
 
-        for (i, result) in fc.final_classifications.iter().enumerate().take(fc.num_cars as usize) {
-            let car = self.cars.get(i).ok_or_else(|| Box::<dyn std::error::Error>::from("Car data not found"))?;
+        self.finish_corner_speeds()?;
+        self.flush_writers()?;
+        // Every output is closed before a lost row is reported, the error is returned once the session is wrapped up
+        let fsync = self.options.fsync;
+        let mut incomplete = 0;
+        for sink in self.sinks_mut() {
+            if sink.take().is_some_and(|sink| !sink.close(fsync)) {
+                incomplete += 1;
+            }
+        }
+        for sink in self.writers.close_all() {
+            if !sink.close(fsync) {
+                incomplete += 1;
+            }
+        }
+        if let Some(writer) = self.compact_trace_writer.take() {
+            writer.finish()?;
+        }
+        self.write_timeline()?;
+        self.write_runs()?;
+        self.write_heatmap()?;
+        self.write_vod_markers()?;
 
-            writer.write_record(&[
-                result.position.to_string(),
-                car.name.clone(),
-                format!("{} ({})", car.team.name(), car.race_number),
-                result.grid_position.to_string(),
-                result.best_lap_time.to_string(),
-                result.total_race_time.to_string(),
-                result.num_laps.to_string(),
-                result.num_pit_stops.to_string(),
-                result.num_penalties.to_string(),
-                result.penalties_time.to_string(),
-                format!("{:?}", result.result_status),
-            ])?;
+        let files = std::mem::take(&mut self.created_files);
+        let laps = self.lap_history.max_laps();
+        let secs = self.session_duration_ms / 1000;
+        if files.is_empty() || (laps >= self.options.min_session_laps && secs >= self.options.min_session_secs) {
+            if self.options.fsync {
+                sync_files(&files)?;
+            }
+        } else {
+            println!(
+                "Session ended after {} laps and {} s, below the configured minimum - discarding its files",
+                laps, secs
+            );
+            for file in files {
+                match fs::remove_file(&file) {
+                    Ok(()) => println!("Removed {:?}", file),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+                    Err(err) => return Err(err),
+                }
+            }
         }
 
-        writer.flush()?;
-        Ok(())
+        // Cleaning up is housekeeping, failing to do so must not stop the logging
+        let mut in_use = self.created_files.clone();
+        in_use.extend(self.options.rolling_laps.clone());
+        if let Err(err) = self.options.retention.apply(&self.options.filename_template, &in_use) {
+            println!("Failed to clean up old output files: {}", err);
+        }
+        incomplete_outputs(incomplete)
     }
 
-    pub(crate) fn update_car_speeds(&mut self, telemetry: &[CarTelemetryData]) {
-        self.car_speeds.clear();
-        self.car_speeds.extend(telemetry.iter().map(|car| car.speed));
+    /// Closes the session and the outputs kept across sessions, when the logger stops
+    pub(crate) fn shutdown(&mut self) -> io::Result<()> {
+        let closed = self.close_session();
+        // The rolling file is closed even when closing the session failed
+        let rolling_laps_complete = self.rolling_laps_writer.take().map_or(true, |sink| sink.close(self.options.fsync));
+        closed?;
+        incomplete_outputs(usize::from(!rolling_laps_complete))
     }
 
-    fn create_overtake_event(
-        &self,
-        ot: &Overtake,
-        session_time: u32,
-    ) -> Result<OvertakeEventLog, Box<dyn std::error::Error>> {
-        let get_car = |idx: u8| -> Result<&ParticipantData, Box<dyn std::error::Error>> {
-            self.cars.get(idx as usize).ok_or_else(|| Box::from("Car data not found"))
-        };
-        let get_status = |idx: u8| -> Result<&CarStatusData, Box<dyn std::error::Error>> {
-            self.car_status.get(idx as usize).ok_or_else(|| Box::from("Car status not found"))
-        };
-        let get_speed = |idx: u8| -> u16 { self.car_speeds.get(idx as usize).copied().unwrap_or(0) };
-
-        let overtaker = get_car(ot.overtaking_vehicle_idx)?;
+    /// Writes the metadata sidecar when the session starts and whenever its context changes, e.g. when
+    /// fields are only populated by later session packets
+    fn update_metadata(&mut self, session_data: &PacketSessionData) -> io::Result<()> {
+        let started_at = self.session_started_at.to_rfc3339();
+        let mut metadata = SessionMetadata::new(session_data, started_at);
+        if let Some(previous) = self.metadata.as_ref() {
+            metadata.lap_history_incomplete = previous.lap_history_incomplete;
+        }
+        metadata.linked_session_uid = self.weekend.as_ref().map(|link| link.session_uid);
+        metadata.telemetry_rate_hz = self.telemetry_rate.measured_hz();
+        if self.metadata.as_ref() == Some(&metadata) {
+            return Ok(());
+        }
+
+        self.metadata = Some(metadata);
+        self.write_metadata()
+    }
+
+    /// Answers the pending requests for the metadata and the live state, see [`MetadataServer`]
+    pub(crate) fn serve_metadata(&mut self) {
+        if self.metadata_server.is_none() {
+            return;
+        }
+
+        let live = self.session_info.is_some().then(|| self.live_session());
+        if let Some(server) = self.metadata_server.as_mut() {
+            server.serve(self.metadata.as_ref(), live.as_ref());
+        }
+    }
+
+    fn live_session(&self) -> LiveSession {
+        let fastest_lap = self.fastest_lap.and_then(|holder| {
+            let car = self.cars.get(holder.car_idx as usize)?;
+            Some(LiveFastestLap {
+                driver: teams::format_driver(car, self.options.identify_by),
+                team: teams::format_team(car, self.game_year, self.options.identify_by),
+                lap_time_ms: holder.lap_time_ms,
+                in_points: self.fastest_lap_in_points(),
+                confirmed: holder.confirmed,
+            })
+        });
+
+        LiveSession { fastest_lap }
+    }
+
+    fn write_metadata(&mut self) -> io::Result<()> {
+        let Some(metadata) = self.metadata.as_ref() else {
+            return Ok(());
+        };
+
+        let filename = self.output_path("Metadata", "json");
+        fs::write(&filename, serde_json::to_string_pretty(metadata)?)?;
+
+        if !self.created_files.contains(&filename) {
+            self.created_files.push(filename);
+        }
+        Ok(())
+    }
+
+    /// Logs the weather forecast at session start and whenever it changes, at most once per
+    /// [`FORECAST_LOG_INTERVAL_MS`]
+    fn update_forecast(&mut self, session_data: &PacketSessionData) -> io::Result<()> {
+        let session_time = session_data.header.session_time;
+        if self.last_forecast_time.is_some_and(|last| session_time.saturating_sub(last) < FORECAST_LOG_INTERVAL_MS) {
+            return Ok(());
+        }
+
+        let samples = forecast::forecast_samples(session_data);
+        if samples.is_empty() || (self.last_forecast_time.is_some() && samples == self.forecast) {
+            return Ok(());
+        }
+
+        if self.forecast_writer.is_none() {
+            self.forecast_writer = Some(self.create_sink("Forecast", &FORECAST_CSV_HEADERS)?);
+        }
+
+        if let Some(writer) = self.forecast_writer.as_mut() {
+            for sample in &samples {
+                writer.write_record(&[
+                    session_time.to_string(),
+                    sample.session.clone(),
+                    sample.time_offset.to_string(),
+                    sample.weather.clone(),
+                    sample.track_temperature.to_string(),
+                    sample.air_temperature.to_string(),
+                    sample.rain_percentage.to_string(),
+                ]);
+            }
+            writer.flush();
+        }
+
+        let session = session_data.session_type.name();
+        if let Some(rain) = forecast::rain_probability_within(&samples, session, RAIN_LOOKAHEAD_MINUTES) {
+            println!("Rain probability in the next {} minutes: {}%", RAIN_LOOKAHEAD_MINUTES, rain);
+        }
+
+        self.forecast = samples;
+        self.last_forecast_time = Some(session_time);
+        Ok(())
+    }
+
+    /// Logs the current weather whenever it changes, and otherwise once per configured interval
+    fn update_conditions(&mut self, session_data: &PacketSessionData) -> io::Result<()> {
+        let Some(interval) = self.options.conditions_interval_ms else {
+            return Ok(());
+        };
+
+        let session_time = session_data.header.session_time;
+        let conditions = Conditions::new(session_data);
+        if let Some((last, last_time)) = self.conditions.as_ref() {
+            if *last == conditions && session_time.saturating_sub(*last_time) < interval {
+                return Ok(());
+            }
+        }
+
+        if self.conditions_writer.is_none() {
+            self.conditions_writer = Some(self.create_sink("Conditions", &CONDITIONS_CSV_HEADERS)?);
+        }
+        if let Some(writer) = self.conditions_writer.as_mut() {
+            writer.write_record(&[
+                session_time.to_string(),
+                conditions.weather.clone(),
+                conditions.track_temperature.to_string(),
+                conditions.air_temperature.to_string(),
+                conditions.rain_percentage.map(|rain| rain.to_string()).unwrap_or_default(),
+            ]);
+        }
+
+        self.conditions = Some((conditions, session_time));
+        Ok(())
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &PacketEventData) -> Result<(), Box<dyn std::error::Error>> {
+        // The driver selection only narrows what is written, the tracking still sees every car
+        let selected = self.is_selected_event(&event.event);
+        let result = match event.event {
+            Event::Overtake(_) => self.handle_overtake(event, selected),
+            Event::Collision(collision) => self.handle_collision(&collision, event.header.session_time, selected),
+            Event::Penalty(penalty) => self.handle_penalty(&penalty, event.header.session_time, selected),
+            Event::Retirement(retirement) => {
+                if selected {
+                    self.record_retirement(&retirement, event.header.session_time);
+                }
+                Ok(())
+            },
+            Event::FastestLap(fastest_lap) => {
+                self.record_fastest_lap(&fastest_lap, event.header.session_time, selected);
+                Ok(())
+            },
+            Event::LightsOut => {
+                self.race_start_time = Some(event.header.session_time);
+                Ok(())
+            },
+            Event::RedFlag => {
+                self.lap_history.interrupt();
+                Ok(())
+            },
+            Event::ChequeredFlag => self.handle_chequered_flag(),
+            Event::SessionEnded => self.flush_writers().map_err(Into::into),
+            Event::DriveThroughServed(served) => {
+                self.pit_stops.mark_penalty(served.vehicle_idx as usize);
+                self.penalty_ledger.record_served(served.vehicle_idx as usize);
+                Ok(())
+            },
+            Event::StopGoServed(served) => {
+                self.pit_stops.mark_penalty(served.vehicle_idx as usize);
+                self.penalty_ledger.record_served(served.vehicle_idx as usize);
+                Ok(())
+            },
+            _ if self.options.log_unknown_events => self.log_unknown_event(event),
+            _ => Ok(()),
+        };
+        result?;
+
+        self.flush_if_due(event.header.session_time)?;
+        Ok(())
+    }
+
+    /// Whether the event involves a driver of [`SessionOptions::drivers`]. Events not about specific cars,
+    /// like the lights out or the chequered flag, are always handled.
+    fn is_selected_event(&self, event: &Event) -> bool {
+        let cars = match event {
+            Event::Overtake(ot) => [Some(ot.overtaking_vehicle_idx), Some(ot.being_overtaken_vehicle_idx)],
+            Event::Collision(collision) => [Some(collision.vehicle_1_idx), Some(collision.vehicle_2_idx)],
+            Event::Penalty(penalty) => [Some(penalty.vehicle_idx), Some(penalty.other_vehicle_idx)],
+            Event::Retirement(retirement) => [Some(retirement.vehicle_idx), None],
+            Event::FastestLap(fastest_lap) => [Some(fastest_lap.vehicle_idx), None],
+            _ => return true,
+        };
+        self.is_selected(cars.iter().flatten())
+    }
+
+    /// Whether any of the cars is a driver of [`SessionOptions::drivers`], or no drivers were selected
+    fn is_selected<'a>(&self, mut cars: impl Iterator<Item = &'a u8>) -> bool {
+        self.selected_cars.as_ref().is_none_or(|selected| cars.any(|idx| selected.contains(idx)))
+    }
+
+    /// Logs an event packet from its raw bytes, independently of its typed handling. Failures are only
+    /// reported, so they never hold up the packet.
+    pub(crate) fn log_raw_event(&mut self, event: &RawEvent) {
+        if self.options.log_unknown_events {
+            self.unhandled_events.push(event);
+        }
+        if !self.options.log_raw_events || event.session_uid == NO_SESSION_UID {
+            return;
+        }
+
+        let vehicle = |idx: Option<u8>| idx.map(|idx| idx.to_string()).unwrap_or_default();
+        let record = [
+            event.session_time_ms.to_string(),
+            event.code.clone(),
+            vehicle(event.vehicles[0]),
+            vehicle(event.vehicles[1]),
+            event.payload_hex(),
+        ];
+        // The first events of a session can arrive before its session packet, they wait for it rather than
+        // ending up in the file of the previous session
+        if event.session_uid != self.session_uid || self.session_info.is_none() {
+            if self.pending_raw_events.len() < MAX_PENDING_RAW_EVENTS {
+                self.pending_raw_events.push((event.session_uid, record));
+            }
+            return;
+        }
+
+        self.write_raw_event(record);
+    }
+
+    /// Writes the raw events that arrived before the session packet of the current session. Those of the
+    /// next session keep waiting, events of other sessions are dropped when the session changes.
+    fn write_pending_raw_events(&mut self) {
+        for (session_uid, record) in std::mem::take(&mut self.pending_raw_events) {
+            if session_uid == self.session_uid {
+                self.write_raw_event(record);
+            } else {
+                self.pending_raw_events.push((session_uid, record));
+            }
+        }
+    }
+
+    fn write_raw_event(&mut self, record: [String; 5]) {
+        if self.raw_events_writer.is_none() {
+            match self.create_sink("RawEvents", &RAW_EVENT_CSV_HEADERS) {
+                Ok(sink) => self.raw_events_writer = Some(sink),
+                Err(err) => {
+                    println!("Failed to create the raw events file: {}", err);
+                    return;
+                },
+            }
+        }
+
+        if let Some(writer) = self.raw_events_writer.as_mut() {
+            writer.write_record(record);
+        }
+    }
+
+    fn log_unknown_event(&mut self, event: &PacketEventData) -> Result<(), Box<dyn std::error::Error>> {
+        if self.unknown_events_writer.is_none() {
+            if self.session_info.is_none() {
+                return Err("No session info available".into());
+            }
+            self.unknown_events_writer = Some(self.create_sink("UnknownEvents", &UNKNOWN_EVENT_CSV_HEADERS)?);
+        }
+
+        // Events built in code, e.g. by the self-test, come without a datagram and are logged decoded instead
+        let (code, payload) = match self.unhandled_events.take(event.header.session_uid, event.header.frame_identifier)
+        {
+            Some(unhandled) => (unhandled.code, unhandled.payload_hex),
+            None => {
+                let payload = format!("{:?}", event.event);
+                (payload.split(['(', ' ']).next().unwrap_or_default().to_string(), payload)
+            },
+        };
+
+        if let Some(writer) = self.unknown_events_writer.as_mut() {
+            writer.write_record([event.header.session_time.to_string().as_str(), code.as_str(), payload.as_str()]);
+        }
+
+        Ok(())
+    }
+
+    fn handle_overtake(&mut self, event: &PacketEventData, selected: bool) -> Result<(), Box<dyn std::error::Error>> {
+        // Early return if no CSV writer or no car data
+        if !self.writers.is_enabled(LogCategory::Overtakes) || self.cars.is_empty() {
+            return Ok(());
+        }
+
+        if let Event::Overtake(ot) = event.event {
+            let overtake_event = self.create_overtake_event(&ot, event.header.session_time)?;
+            if selected {
+                self.write_overtake_outputs(&ot, &overtake_event)?;
+            }
+            self.recent_overtakes.push(
+                ot.overtaking_vehicle_idx,
+                ot.being_overtaken_vehicle_idx,
+                event.header.session_time,
+            );
+            self.recent_passes.push(
+                ot.overtaking_vehicle_idx,
+                ot.being_overtaken_vehicle_idx,
+                event.header.session_time,
+            );
+            // Pit cycles aren't fights between the pair, unless pit-related overtakes are logged anyway
+            if !(self.options.exclude_pit_overtakes && overtake_event.pit_related) {
+                self.pair_swaps.insert(
+                    swap_pair(ot.overtaking_vehicle_idx, ot.being_overtaken_vehicle_idx),
+                    (event.header.session_time, overtake_event.pair_swaps),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes an overtake of the selected drivers to the events CSV, the timeline and the other outputs
+    fn write_overtake_outputs(&mut self, ot: &Overtake, overtake_event: &OvertakeEventLog) -> io::Result<()> {
+        let significant = self.is_significant_overtake(overtake_event);
+        if significant {
+            self.write_overtake_event(overtake_event)?;
+            if self.options.markers {
+                self.write_overtake_marker(overtake_event)?;
+            }
+            if self.options.notify {
+                self.notify_overtake(ot, overtake_event);
+            }
+        } else {
+            self.filtered_overtakes += 1;
+        }
+        self.overtake_locations.push(overtake_event.location_bin);
+        self.overtake_heatmap.push(overtake_event.track_position);
+        if self.options.report_md {
+            self.race_report.push_overtake(ReportOvertake {
+                lap: overtake_event.lap,
+                for_pos: overtake_event.for_pos,
+                overtaker: overtake_event.overtaker_name.clone(),
+                overtakee: overtake_event.overtakee_name.clone(),
+                speed_delta: overtake_event.overtaker_speed.abs_diff(overtake_event.overtakee_speed),
+            });
+        }
+        let description = format!(
+            "{} overtakes {} for P{}",
+            overtake_event.overtaker_name, overtake_event.overtakee_name, overtake_event.for_pos
+        );
+        self.highlight_followed(&[ot.overtaking_vehicle_idx, ot.being_overtaken_vehicle_idx], &description);
+        if let Some(sink) = self.statsd_sink.as_mut() {
+            sink.record_overtake(&overtake_event.overtaker_name);
+        }
+        if significant {
+            self.push_vod_marker(overtake_event.session_time_ms, "Overtake", &description);
+        }
+        self.record_timeline(TimelineEntry {
+            session_time: overtake_event.session_time_ms,
+            kind: "Overtake",
+            description,
+            details: serde_json::json!({
+                "overtaker_team": overtake_event.overtaker_team,
+                "overtakee_team": overtake_event.overtakee_team,
+                "lap": overtake_event.lap,
+                "track_position": overtake_event.track_position,
+            }),
+        });
+
+        Ok(())
+    }
+
+    /// Writes a marker for video editors at the overtake's time since the lights out. Without a race start,
+    /// e.g. when the logger was started mid-race, the session start is used as origin instead.
+    fn write_overtake_marker(&mut self, event: &OvertakeEventLog) -> io::Result<()> {
+        if self.markers_writer.is_none() {
+            if self.session_info.is_none() {
+                return Ok(());
+            }
+            self.markers_writer = Some(self.create_sink("Markers", &MARKER_CSV_HEADERS)?);
+        }
+
+        let offset_ms = event.session_time_ms.saturating_sub(self.race_start_time.unwrap_or(0));
+        let label = format!("P{}: {} on {}", event.for_pos, event.overtaker_name, event.overtakee_name);
+        if let Some(writer) = self.markers_writer.as_mut() {
+            writer.write_record([format!("{:.3}", offset_ms as f64 / 1000.0), format_timecode(offset_ms), label]);
+        }
+
+        Ok(())
+    }
+
+    /// Notifies about overtakes the player made or suffered, covering both players in split screen
+    fn notify_overtake(&mut self, ot: &Overtake, event: &OvertakeEventLog) {
+        let players = [self.player_car_index, self.secondary_player_car_index];
+        let summary = if players.contains(&Some(ot.overtaking_vehicle_idx)) {
+            format!("Overtook {} for P{}", event.overtakee_name, event.for_pos)
+        } else if players.contains(&Some(ot.being_overtaken_vehicle_idx)) {
+            format!("Overtaken by {} for P{}", event.overtaker_name, event.for_pos)
+        } else {
+            return;
+        };
+
+        let body = format!("Lap {}, {} m into the lap", event.lap, event.track_position);
+        self.notifier.notify(&summary, &body);
+    }
+
+    /// Whether the overtake passes all configured significance filters. Only the events CSV is filtered,
+    /// everything else still sees every overtake.
+    fn is_significant_overtake(&self, event: &OvertakeEventLog) -> bool {
+        let position_ok = self.options.overtake_min_position.is_none_or(|min| event.for_pos <= min);
+        let speed_delta = event.overtaker_speed.abs_diff(event.overtakee_speed);
+        let speed_ok = self.options.overtake_min_speed_delta.is_none_or(|min| speed_delta >= min);
+        let pit_ok = !(self.options.exclude_pit_overtakes && event.pit_related);
+
+        position_ok && speed_ok && pit_ok
+    }
+
+    fn handle_collision(
+        &mut self,
+        collision: &Collision,
+        session_time: u32,
+        selected: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.writers.is_enabled(LogCategory::Collisions) || self.cars.is_empty() {
+            return Ok(());
+        }
+
+        let collision_event = self.create_collision_event(collision, session_time)?;
+        self.recent_collisions.push(collision.vehicle_1_idx, collision.vehicle_2_idx, session_time);
+        if !selected {
+            return Ok(());
+        }
+
+        if let Some(writer) = self.writers.get_mut(LogCategory::Collisions) {
+            writer.write_record([
+                &collision_event.driver_1_name,
+                &collision_event.driver_1_team,
+                &collision_event.driver_2_name,
+                &collision_event.driver_2_team,
+                &collision_event.lap.to_string(),
+                &collision_event.track_position.to_string(),
+                &collision_event.time_secs.to_string(),
+                &format_optional_bool(collision_event.involves_player),
+                &collision_event.overtake_time.map(|time| time.to_string()).unwrap_or_default(),
+            ]);
+        }
+        let description = format!("{} collides with {}", collision_event.driver_1_name, collision_event.driver_2_name);
+        self.highlight_followed(&[collision.vehicle_1_idx, collision.vehicle_2_idx], &description);
+        self.record_timeline(TimelineEntry {
+            session_time,
+            kind: "Collision",
+            description,
+            details: serde_json::json!({
+                "lap": collision_event.lap,
+                "track_position": collision_event.track_position,
+            }),
+        });
+
+        Ok(())
+    }
+
+    fn record_retirement(&mut self, retirement: &Retirement, session_time: u32) {
+        let Some(car) = self.cars.get(retirement.vehicle_idx as usize) else {
+            return;
+        };
+
+        let lap = self.lap_data.get(retirement.vehicle_idx as usize).map(|lap| lap.current_lap_num);
+        // The last status received before the retirement tells a mechanical failure
+        let reason = self
+            .car_status
+            .get(retirement.vehicle_idx as usize)
+            .and_then(faults::retirement_cause)
+            .map(|component| component.name());
+        if self.options.report_md {
+            self.race_report.push_retirement(ReportRetirement {
+                lap,
+                driver: teams::format_driver(car, self.options.identify_by),
+                team: teams::format_team(car, self.game_year, self.options.identify_by),
+                reason: reason.map(str::to_string),
+            });
+        }
+        let description = match reason {
+            Some(component) => {
+                format!("{} retires, {} failure", teams::format_driver(car, self.options.identify_by), component)
+            },
+            None => format!("{} retires", teams::format_driver(car, self.options.identify_by)),
+        };
+        self.highlight_followed(&[retirement.vehicle_idx], &description);
+        let entry = TimelineEntry {
+            session_time,
+            kind: "Retirement",
+            description,
+            details: serde_json::json!({ "team": teams::format_team(car, self.game_year, self.options.identify_by), "lap": lap, "reason": reason }),
+        };
+        self.record_timeline(entry);
+    }
+
+    /// Logs a fault when it first appears, so an impending failure is visible before the retirement
+    fn record_fault(&mut self, car_idx: usize, component: Component, session_time: u32) {
+        let Some(car) = self.cars.get(car_idx) else {
+            return;
+        };
+
+        let lap = self.lap_data.get(car_idx).map(|lap| lap.current_lap_num);
+        let driver = teams::format_driver(car, self.options.identify_by);
+        let description = match lap {
+            Some(lap) => format!("{} fault reported for {}, lap {}", component.name(), driver, lap),
+            None => format!("{} fault reported for {}", component.name(), driver),
+        };
+        println!("{}", description);
+        let entry = TimelineEntry {
+            session_time,
+            kind: "Fault",
+            description,
+            details: serde_json::json!({ "team": teams::format_team(car, self.game_year, self.options.identify_by), "lap": lap, "component": component.name() }),
+        };
+        self.record_timeline(entry);
+    }
+
+    /// Updates the holder from a fastest lap event, also for drivers outside of [`SessionOptions::drivers`],
+    /// and reports the new holder if selected
+    fn record_fastest_lap(&mut self, fastest_lap: &FastestLap, session_time: u32, selected: bool) {
+        let holder = FastestLapHolder {
+            car_idx: fastest_lap.vehicle_idx,
+            lap_time_ms: (fastest_lap.lap_time * 1000.0).round() as u32,
+            confirmed: true,
+        };
+        // A fastest lap only ever improves, an event slower than the confirmed holder is a stale one, and the
+        // holder must be a car on track
+        let stale =
+            self.fastest_lap.is_some_and(|current| current.confirmed && current.lap_time_ms < holder.lap_time_ms);
+        let on_track = self.lap_data.get(holder.car_idx as usize).is_some_and(|lap| lap.car_position > 0);
+        if stale || !on_track {
+            return;
+        }
+        let previous = self.fastest_lap.replace(holder);
+        let in_points = self.fastest_lap_in_points();
+        self.fastest_lap_in_points = in_points;
+
+        let Some(car) = self.cars.get(fastest_lap.vehicle_idx as usize).filter(|_| selected) else {
+            return;
+        };
+        let driver = teams::format_driver(car, self.options.identify_by);
+        let team = teams::format_team(car, self.game_year, self.options.identify_by);
+
+        let changes_hands = previous.is_some_and(|previous| previous.car_idx != holder.car_idx);
+        if let Some(laps_to_go) = self.final_laps_to_go().filter(|_| changes_hands) {
+            println!(
+                "Fastest lap changes hands with {} to go: {} {}{}",
+                format_laps(laps_to_go),
+                driver,
+                display::format_time(holder.lap_time_ms),
+                if in_points == Some(false) { ", outside the points" } else { "" }
+            );
+        }
+
+        let entry = TimelineEntry {
+            session_time,
+            kind: "Fastest Lap",
+            description: format!("{} sets the fastest lap", driver),
+            details: serde_json::json!({ "team": team, "lap_time": fastest_lap.lap_time, "in_points": in_points }),
+        };
+        self.record_timeline(entry);
+    }
+
+    /// Takes the fastest lap from the completed laps while no event named a holder, e.g. when the logger was
+    /// started after the fastest lap was set
+    fn check_fastest_lap(&mut self) {
+        let race = self.session_info.as_ref().is_some_and(sessions::is_race);
+        if race && self.fastest_lap.is_none_or(|holder| !holder.confirmed) {
+            self.fastest_lap = self.lap_history.fastest_lap();
+        }
+    }
+
+    /// Whether the fastest lap holder currently runs in a points paying position, the condition of the fastest
+    /// lap bonus. `None` without a holder or while its position is unknown.
+    fn fastest_lap_in_points(&self) -> Option<bool> {
+        let holder = self.fastest_lap?;
+        let position = self.lap_data.get(holder.car_idx as usize).map(|lap| lap.car_position).filter(|&p| p > 0)?;
+        let paying_positions =
+            self.options.points_scheme.as_ref().map_or(DEFAULT_PAYING_POSITIONS, PointsScheme::paying_positions);
+        Some(position as usize <= paying_positions)
+    }
+
+    /// Laps left for the leader including the current one, if the race is within [`FINAL_LAPS`] of its end
+    fn final_laps_to_go(&self) -> Option<u8> {
+        let session = self.session_info.as_ref().filter(|session| sessions::is_race(session))?;
+        let leader_lap = self.lap_data.iter().find(|lap| lap.car_position == 1)?.current_lap_num;
+        let laps_to_go = session.total_laps.checked_sub(leader_lap)? + 1;
+        (laps_to_go <= FINAL_LAPS).then_some(laps_to_go)
+    }
+
+    /// Announces the fastest lap holder moving into or out of the points in the final laps, as the bonus
+    /// follows the finishing position rather than the position the lap was set in
+    fn update_fastest_lap_eligibility(&mut self) {
+        let in_points = self.fastest_lap_in_points();
+        let previous = std::mem::replace(&mut self.fastest_lap_in_points, in_points);
+        let (Some(previous), Some(in_points)) = (previous, in_points) else {
+            return;
+        };
+        if previous == in_points {
+            return;
+        }
+
+        let (Some(laps_to_go), Some(car)) =
+            (self.final_laps_to_go(), self.fastest_lap.and_then(|holder| self.cars.get(holder.car_idx as usize)))
+        else {
+            return;
+        };
+        println!(
+            "Fastest lap holder {} {} the points with {} to go",
+            teams::format_driver(car, self.options.identify_by),
+            if in_points { "moves into" } else { "drops out of" },
+            format_laps(laps_to_go)
+        );
+    }
+
+    fn record_timeline(&mut self, entry: TimelineEntry) {
+        if let Some(sink) = self.kafka_sink.as_mut() {
+            sink.publish(self.session_uid, &entry);
+        }
+        if let Some(db) = self.replay_db.as_mut() {
+            db.record_event(self.session_uid, &entry);
+        }
+        if let Some(sink) = self.socket_sink.as_mut() {
+            sink.publish(self.session_uid, &entry);
+        }
+        if let Some(sink) = self.statsd_sink.as_mut() {
+            sink.record_event(&entry);
+        }
+        if vod::MARKED_KINDS.contains(&entry.kind) {
+            self.push_vod_marker(entry.session_time, entry.kind, &entry.description);
+        }
+        if self.options.timeline {
+            self.timeline.push(entry);
+        }
+    }
+
+    fn write_timeline(&mut self) -> io::Result<()> {
+        if self.timeline.is_empty() {
+            return Ok(());
+        }
+        if self.session_info.is_none() {
+            return Ok(());
+        }
+
+        let mut writer = self.create_new_csv_writer("Timeline", &TIMELINE_CSV_HEADERS)?;
+        self.timeline.write(&mut writer)?;
+        self.timeline.clear();
+        Ok(())
+    }
+
+    /// Maps an event onto the race recording, dropping events outside of it
+    fn push_vod_marker(&mut self, session_time: u32, kind: &'static str, description: &str) {
+        let Some(vod) = self.options.vod.as_ref() else {
+            return;
+        };
+
+        let wall_clock = self.session_started_at
+            + chrono::Duration::milliseconds(session_time as i64 - self.session_start_time as i64);
+        let since_lights_out_ms = self.race_start_time.map(|start| session_time as i64 - start as i64);
+        if let Some(video_ms) = vod.video_time_ms(wall_clock, since_lights_out_ms) {
+            self.vod_markers.push(VodMarker { video_ms, kind, description: description.to_string() });
+        }
+    }
+
+    /// Writes the markers of the race recording as a "VodMarkers" CSV or as YouTube chapters
+    fn write_vod_markers(&mut self) -> io::Result<()> {
+        let Some(vod) = self.options.vod.as_ref() else {
+            return Ok(());
+        };
+        if self.session_info.is_none() || self.vod_markers.is_empty() {
+            return Ok(());
+        }
+
+        // Lead changes are only seen with the next lap data, after events of the same moment
+        self.vod_markers.sort_by_key(|marker| marker.video_ms);
+        match vod.format {
+            VodFormat::Csv => {
+                let mut writer = self.create_new_csv_writer("VodMarkers", &VOD_MARKER_CSV_HEADERS)?;
+                for marker in &self.vod_markers {
+                    writer.write_record([
+                        format_timecode(marker.video_ms).as_str(),
+                        format_session_secs(marker.video_ms).as_str(),
+                        marker.kind,
+                        marker.description.as_str(),
+                    ])?;
+                }
+                writer.flush()?;
+            },
+            VodFormat::YouTube => {
+                let filename = self.output_path("Chapters", "txt");
+                println!("Writing YouTube chapters to {:?}", &filename);
+                vod::write_chapters(&filename, &self.vod_markers)?;
+                self.created_files.push(filename);
+            },
+        }
+        self.vod_markers.clear();
+        Ok(())
+    }
+
+    /// Writes the overtake counts over the whole lap. The bins are also given in percent of the track length,
+    /// so heatmaps of different tracks line up.
+    fn write_heatmap(&mut self) -> io::Result<()> {
+        let Some(bin_m) = self.options.heatmap_bin_m else {
+            return Ok(());
+        };
+        let Some(session_info) = self.session_info.as_ref() else {
+            return Ok(());
+        };
+        if self.session_uid == NO_SESSION_UID || self.overtake_heatmap.is_empty() {
+            return Ok(());
+        }
+
+        let track_length = session_info.track_length;
+        let percent = |distance_m: u16| match track_length {
+            0 => String::new(),
+            length => format!("{:.1}", distance_m as f64 * 100.0 / length as f64),
+        };
+
+        let mut writer = self.create_new_csv_writer("Heatmap", &HEATMAP_CSV_HEADERS)?;
+        for (bin, count) in self.overtake_heatmap.bins(bin_m, track_length) {
+            writer.write_record(&[
+                bin.start_m.to_string(),
+                bin.end_m.to_string(),
+                percent(bin.start_m),
+                percent(bin.end_m),
+                count.to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        self.overtake_heatmap.clear();
+        Ok(())
+    }
+
+    /// Writes the runs of practice and qualifying sessions, closing runs still in progress with the laps
+    /// completed so far
+    fn write_runs(&mut self) -> io::Result<()> {
+        self.runs.close_all(&self.car_status);
+        let Some(session_info) = self.session_info.as_ref() else {
+            return Ok(());
+        };
+        if self.session_uid == NO_SESSION_UID || sessions::is_race(session_info) || self.runs.completed().is_empty() {
+            return Ok(());
+        }
+
+        let mut writer = self.create_new_csv_writer("Runs", &RUN_CSV_HEADERS)?;
+        for run in self.runs.completed() {
+            let Some(car) = self.cars.get(run.car_idx) else {
+                continue;
+            };
+
+            writer.write_record(&[
+                teams::format_driver(car, self.options.identify_by),
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                run.number.to_string(),
+                run.compound.clone(),
+                run.lap_times.len().to_string(),
+                run.best_lap_ms().map(|time| time.to_string()).unwrap_or_default(),
+                run.average_lap_ms().map(|time| time.to_string()).unwrap_or_default(),
+                run.fuel_at_start.map(|fuel| format!("{:.1}", fuel)).unwrap_or_default(),
+                run.end_tyre_age.map(|age| age.to_string()).unwrap_or_default(),
+            ])?;
+        }
+
+        writer.flush()?;
+        self.runs.clear();
+        Ok(())
+    }
+
+    fn handle_penalty(
+        &mut self,
+        penalty: &Penalty,
+        session_time: u32,
+        selected: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.writers.is_enabled(LogCategory::Penalties) || self.cars.is_empty() {
+            return Ok(());
+        }
+
+        let penalty_event = self.create_penalty_event(penalty, session_time)?;
+        if matches!(penalty.penalty_type, PenaltyType::GridPenalty) {
+            self.grid_penalised.push(penalty.vehicle_idx as usize);
+        }
+        self.penalty_ledger.record_issued(penalty.vehicle_idx as usize, &penalty.penalty_type, penalty_event.time_secs);
+        if !selected {
+            return Ok(());
+        }
+
+        if self.options.report_md {
+            self.race_report.push_penalty(ReportPenalty {
+                lap: penalty_event.lap,
+                driver: penalty_event.driver_name.clone(),
+                penalty: penalty_event.penalty_type.clone(),
+                infringement: penalty_event.infringement_type.clone(),
+                time_secs: penalty_event.time_secs,
+            });
+        }
+        let description = format!(
+            "{}: {} for {}",
+            penalty_event.driver_name, penalty_event.penalty_type, penalty_event.infringement_type
+        );
+        self.highlight_followed(&[penalty.vehicle_idx, penalty.other_vehicle_idx], &description);
+        self.record_timeline(TimelineEntry {
+            session_time,
+            kind: "Penalty",
+            description,
+            details: serde_json::json!({
+                "other_driver": penalty_event.other_driver_name,
+                "time": penalty_event.time_secs,
+                "lap": penalty_event.lap,
+                "places_gained": penalty_event.places_gained,
+            }),
+        });
+
+        if let Some(writer) = self.writers.get_mut(LogCategory::Penalties) {
+            writer.write_record([
+                &penalty_event.driver_name,
+                &penalty_event.driver_team,
+                &penalty_event.penalty_type,
+                &penalty_event.infringement_type,
+                &penalty_event.other_driver_name.unwrap_or_default(),
+                &penalty_event.time_secs.map(|time| time.to_string()).unwrap_or_default(),
+                &penalty_event.lap.to_string(),
+                &penalty_event.places_gained.map(|places| places.to_string()).unwrap_or_default(),
+                &penalty_event.session_time.to_string(),
+                &format_optional_bool(penalty_event.involves_player),
+                &penalty_event.grid_places.map(|places| places.to_string()).unwrap_or_default(),
+                &penalty_event.source.to_string(),
+            ]);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn write_final_classification(
+        &mut self,
+        fc: PacketFinalClassificationData,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.session_uid == NO_SESSION_UID {
+            return Ok(());
+        }
+
+        if self.session_info.is_none() {
+            return Err("No session info available".into());
+        }
+
+        let all_columns: Vec<&str> = CLASSIFICATION_COLUMNS.iter().map(|column| column.key).collect();
+        let columns = columns::select_columns(
+            &CLASSIFICATION_COLUMNS,
+            CLASSIFICATION_REQUIRED_COLUMNS,
+            &all_columns,
+            self.options.classification_columns.as_ref(),
+        );
+        let mut headers: Vec<&str> = columns
+            .iter()
+            .map(|&i| match CLASSIFICATION_COLUMNS[i].key {
+                "penalty_time" if self.options.penalty_time_ms => "Penalty Time [ms]",
+                _ => CLASSIFICATION_COLUMNS[i].header,
+            })
+            .collect();
+        if self.options.points_scheme.is_some() {
+            headers.push("Points");
+        }
+        if self.options.driver_ids.is_some() {
+            headers.push("Driver ID");
+        }
+        if self.options.final_tyres {
+            headers.extend(["Final Compound", "Final Tyre Age"]);
+        }
+
+        let results = &fc.final_classifications[..(fc.num_cars as usize).min(fc.final_classifications.len())];
+        self.classification_written_for = Some(self.session_uid);
+        self.last_classification = format!("{:?}", results);
+        let classification: Vec<(u8, u8)> =
+            results.iter().map(|result| (result.position, result.penalties_time)).collect();
+        if self.provisional_classification.is_empty() {
+            self.provisional_classification = classification;
+        } else {
+            let amendments = amendments::amendments(&self.provisional_classification, &classification);
+            self.write_amendments(&amendments)?;
+        }
+        self.classified_penalties =
+            results.iter().map(|result| (result.num_penalties, result.penalties_time)).collect();
+        if let Some(session_info) = self.session_info.as_ref() {
+            if sessions::is_race(session_info) {
+                self.grid_positions = results.iter().map(|result| result.grid_position).collect();
+                self.write_inferred_grid_penalties();
+            } else if SessionKind::of(&session_info.session_type) == SessionKind::Qualifying {
+                self.qualifying =
+                    results.iter().zip(&self.cars).map(|(result, car)| (car.race_number, result.position)).collect();
+            }
+        }
+        let fastest_lap_idx = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| result.best_lap_time > 0)
+            .min_by_key(|(_, result)| result.best_lap_time)
+            .map(|(i, _)| i);
+
+        let format_ms = |value: Option<u32>| value.map(|ms| ms.to_string()).unwrap_or_default();
+        let mut lap_history_incomplete = false;
+        let mut records = Vec::with_capacity(results.len());
+        for (i, result) in results.iter().enumerate() {
+            let car = self.cars.get(i).ok_or_else(|| Box::<dyn std::error::Error>::from("Car data not found"))?;
+
+            // The game reports the race time in seconds and the penalty time in whole seconds
+            let finished = result.result_status == ResultStatus::Finished;
+            let finish_time_ms = (result.total_race_time * 1000.0).round() as u64;
+            let penalty_time_ms = result.penalties_time as u64 * 1000;
+            let penalty_time =
+                if self.options.penalty_time_ms { penalty_time_ms } else { result.penalties_time as u64 };
+
+            // In the order of `CLASSIFICATION_COLUMNS`
+            let mut values = vec![
+                result.position.to_string(),
+                teams::format_driver(car, self.options.identify_by),
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                result.grid_position.to_string(),
+                result.best_lap_time.to_string(),
+                finish_time_ms.to_string(),
+                result.num_laps.to_string(),
+                result.num_pit_stops.to_string(),
+                result.num_penalties.to_string(),
+                penalty_time.to_string(),
+                finished.then(|| (finish_time_ms + penalty_time_ms).to_string()).unwrap_or_default(),
+                format!("{:?}", result.result_status),
+            ];
+
+            // Computed from the laps tracked so far, the last laps may still be missing at this point
+            lap_history_incomplete |= self.lap_history.laps(i).len() < result.num_laps as usize;
+            let best_sectors = self.lap_history.best_sectors(i);
+            values.extend([
+                self.lap_history.best_lap(i).map(|lap| lap.lap.to_string()).unwrap_or_default(),
+                format_ms(best_sectors.map(|sectors| sectors[0])),
+                format_ms(best_sectors.map(|sectors| sectors[1])),
+                format_ms(best_sectors.map(|sectors| sectors[2])),
+                format_ms(best_sectors.map(|sectors| sectors.iter().sum())),
+            ]);
+
+            let mut record: Vec<String> = columns.iter().map(|&column| values[column].clone()).collect();
+            if let Some(scheme) = &self.options.points_scheme {
+                record.push(scheme.points(result.position, finished, fastest_lap_idx == Some(i)).to_string());
+            }
+            if let Some(ids) = &self.options.driver_ids {
+                record.push(ids.stable_id(car));
+            }
+            if self.options.final_tyres {
+                let tyres = self.final_tyres.get(i).and_then(Option::as_ref);
+                record.push(tyres.map(|tyres| tyres.compound.clone()).unwrap_or_default());
+                record.push(format_tyre_age(tyres));
+            }
+            if let Some(db) = self.replay_db.as_mut() {
+                let row = ClassificationRow {
+                    car_idx: i as u8,
+                    position: result.position,
+                    driver: values[1].clone(),
+                    team: values[2].clone(),
+                    grid_position: result.grid_position,
+                    laps: result.num_laps,
+                    best_lap_ms: result.best_lap_time,
+                    race_time_ms: finish_time_ms,
+                    penalties_secs: result.penalties_time,
+                    result_status: values[11].clone(),
+                };
+                db.record_classification(self.session_uid, row);
+            }
+            records.push(record);
+        }
+
+        let complete = match self.writer(LogCategory::Results, &headers) {
+            Ok(Some(writer)) => {
+                for record in &records {
+                    writer.write_record(record);
+                }
+                // Closed right away, so a repeated classification packet rewrites the file instead of appending
+                let fsync = self.options.fsync;
+                self.writers.close(LogCategory::Results).is_none_or(|writer| writer.close(fsync))
+            },
+            Ok(None) => true,
+            Err(err) => {
+                println!("ERROR: Failed to create the results file: {}", err);
+                false
+            },
+        };
+        // The results are the most valuable output, so they are saved elsewhere rather than lost
+        if !complete {
+            self.write_recovery_file(LogCategory::Results.file_type(), &headers, &records)?;
+        }
+
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata.lap_history_incomplete = Some(lap_history_incomplete);
+        }
+        if self.session_info.is_some() {
+            self.write_metadata()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the classification was already written for this session with the same results. Some games send
+    /// the packet more than once, a changed one, e.g. after post-race penalties, is written again.
+    pub(crate) fn is_repeated_classification(&self, fc: &PacketFinalClassificationData) -> bool {
+        let results = &fc.final_classifications[..(fc.num_cars as usize).min(fc.final_classifications.len())];
+        self.classification_written_for == Some(self.session_uid)
+            && self.last_classification == format!("{:?}", results)
+    }
+
+    /// Writes the position changes of a repeated classification, e.g. after post-race penalties. Every
+    /// repetition rewrites the file, comparing against the provisional classification.
+    fn write_amendments(&mut self, amendments: &[Amendment]) -> io::Result<()> {
+        if self.session_info.is_none() || amendments.is_empty() {
+            return Ok(());
+        }
+
+        println!("Classification amended, {} drivers changed position", amendments.len());
+        let mut writer = self.create_new_csv_writer("Amendments", &AMENDMENT_CSV_HEADERS)?;
+        for amendment in amendments {
+            let (driver, team) = match self.cars.get(amendment.car_idx) {
+                Some(car) => (
+                    teams::format_driver(car, self.options.identify_by),
+                    teams::format_team(car, self.game_year, self.options.identify_by),
+                ),
+                None => (format!("Car {}", amendment.car_idx), String::new()),
+            };
+            writer.write_record(&[
+                driver,
+                team,
+                amendment.old_position.to_string(),
+                amendment.new_position.to_string(),
+                amendment.position_change().to_string(),
+                amendment.penalty_secs_added.to_string(),
+            ])?;
+        }
+        writer.flush()
+    }
+
+    pub(crate) fn write_session_summary(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.session_uid == NO_SESSION_UID {
+            return Ok(());
+        }
+        if self.session_info.is_none() {
+            return Err("No session info available".into());
+        }
+
+        let mut positions = SummarySection::new("Positions", &POSITIONS_SUMMARY_HEADERS);
+        // The followed car leads the table, the others keep their order
+        let mut car_order: Vec<usize> = (0..self.cars.len()).collect();
+        car_order.sort_by_key(|&i| Some(i as u8) != self.followed_car);
+        for i in car_order {
+            let car = &self.cars[i];
+            let laps = self.lap_history.laps(i);
+            let gained: i16 = laps.iter().map(|lap| lap.positions_gained().max(0)).sum();
+            let lost: i16 = laps.iter().map(|lap| (-lap.positions_gained()).max(0)).sum();
+
+            positions.push_row(vec![
+                teams::format_driver(car, self.options.identify_by),
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                laps.len().to_string(),
+                gained.to_string(),
+                lost.to_string(),
+                self.lap_history.total_positions_gained(i).to_string(),
+            ]);
+        }
+
+        let (driver_pit_stops, team_pit_stops) = self.pit_stop_summary(session_info.track_length);
+
+        let mut overtake_locations = SummarySection::new("Overtakes by Location", &LOCATION_SUMMARY_HEADERS);
+        for (bin, count) in self.overtake_locations.ranked() {
+            overtake_locations.push_row(vec![bin.to_string(), count.to_string()]);
+        }
+
+        let mut penalties = SummarySection::new("PenaltyReconciliation", &PENALTY_RECONCILIATION_HEADERS);
+        for (i, &(classified, time_added)) in self.classified_penalties.iter().enumerate() {
+            let Some(car) = self.cars.get(i) else {
+                continue;
+            };
+            let reconciliation = self.penalty_ledger.reconcile(i, classified, time_added);
+            if reconciliation.is_empty() {
+                continue;
+            }
+            if let Some(mismatch) = reconciliation.mismatch() {
+                println!("Penalty mismatch for {}: {}", teams::format_driver(car, self.options.identify_by), mismatch);
+            }
+
+            penalties.push_row(vec![
+                teams::format_driver(car, self.options.identify_by),
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                reconciliation.issued.to_string(),
+                reconciliation.classified.to_string(),
+                reconciliation.time_issued_secs.to_string(),
+                reconciliation.served.to_string(),
+                reconciliation.unserved.to_string(),
+                reconciliation.time_added_secs.to_string(),
+                reconciliation.mismatch().unwrap_or_default().to_string(),
+            ]);
+        }
+
+        let grid_drops = self.grid_drop_summary();
+
+        let filename = self.output_path("Summary", "csv");
+        println!("Writing summary to {:?}", &filename);
+        if let Some((bin, count)) = self.overtake_locations.ranked().first() {
+            println!("Most overtakes happened at {} m into the lap: {}", bin, count);
+        }
+        summary::write_summary(
+            &filename,
+            &[positions, driver_pit_stops, team_pit_stops, overtake_locations, penalties, grid_drops],
+        )?;
+        self.created_files.push(filename);
+
+        Ok(())
+    }
+
+    /// Cars starting behind their qualifying position as car index, qualifying and grid position. Only known
+    /// with the weekend's qualifying linked by `--link-weekend` and the race's classification. A drop can also
+    /// come from a car ahead of it being promoted, e.g. after another car's disqualification from qualifying,
+    /// and grid penalties leaving a car on its qualifying position, e.g. at the back of the grid, go unnoticed.
+    fn grid_drops(&self) -> Vec<(usize, u8, u8)> {
+        let Some(weekend) = self.weekend.as_ref() else {
+            return Vec::new();
+        };
+
+        self.cars
+            .iter()
+            .zip(&self.grid_positions)
+            .enumerate()
+            .filter_map(|(i, (car, &grid))| {
+                let qualified = weekend.qualifying_position(car.race_number).filter(|&qualified| grid > qualified)?;
+                Some((i, qualified, grid))
+            })
+            .collect()
+    }
+
+    /// Logs the grid drops of cars without a grid penalty event to the penalties file. The game doesn't send
+    /// the penalty events of earlier sessions or of engine and gearbox changes made before the race.
+    fn write_inferred_grid_penalties(&mut self) {
+        for (i, qualified, grid) in self.grid_drops() {
+            if self.grid_penalised.contains(&i) {
+                continue;
+            }
+            self.grid_penalised.push(i);
+
+            let Some(car) = self.cars.get(i) else {
+                continue;
+            };
+            if !self.is_selected([i as u8].iter()) {
+                continue;
+            }
+            let involves_player = self.involves_player(&[i as u8]);
+            let record = [
+                teams::format_driver(car, self.options.identify_by),
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                "GridPenalty".to_string(),
+                "Unknown".to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                format_optional_bool(involves_player),
+                (grid - qualified).to_string(),
+                "Inferred".to_string(),
+            ];
+            if let Some(writer) = self.writers.get_mut(LogCategory::Penalties) {
+                writer.write_record(record);
+            }
+        }
+    }
+
+    fn grid_drop_summary(&self) -> SummarySection {
+        let mut section = SummarySection::new("Grid Drops", &GRID_DROP_SUMMARY_HEADERS);
+        for (i, qualified, grid) in self.grid_drops() {
+            let Some(car) = self.cars.get(i) else {
+                continue;
+            };
+            let driver = teams::format_driver(car, self.options.identify_by);
+            println!("{} starts P{} after qualifying P{}, likely a grid penalty", driver, grid, qualified);
+
+            section.push_row(vec![
+                driver,
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                qualified.to_string(),
+                grid.to_string(),
+                (grid - qualified).to_string(),
+            ]);
+        }
+        section
+    }
+
+    /// The current session's context for the next session of the weekend, `None` unless linking weekends
+    fn weekend_link(&self) -> Option<WeekendLink> {
+        if !self.options.link_weekend || self.session_uid == NO_SESSION_UID {
+            return None;
+        }
+        let session_info = self.session_info.as_ref()?;
+
+        // The latest qualifying of the weekend stays relevant through the sessions after it
+        let qualifying = match self.weekend.as_ref() {
+            Some(weekend) if self.qualifying.is_empty() => weekend.qualifying.clone(),
+            _ => self.qualifying.clone(),
+        };
+        Some(WeekendLink {
+            session_uid: self.session_uid,
+            track: session_info.track.name().to_string(),
+            ended_at: self.session_started_at + chrono::Duration::milliseconds(self.session_duration_ms as i64),
+            qualifying,
+            pseudonyms: self.anonymizer.names().to_vec(),
+        })
+    }
+
+    /// Links the new session to the previous one if both are part of the same weekend, anything carried is
+    /// dropped once the track changes. The menus between two sessions don't break the link.
+    fn link_weekend(&mut self, previous_session: Option<WeekendLink>, session_data: &PacketSessionData) {
+        self.weekend = match previous_session {
+            Some(link) if self.session_uid == NO_SESSION_UID => Some(link),
+            Some(link) if link.continues_with(session_data.track.name(), self.session_started_at) => {
+                println!("Linked to session {} of the same weekend", link.session_uid);
+                Some(link)
+            },
+            _ => None,
+        };
+
+        // Drivers keep their pseudonyms through the weekend. The carried pseudonyms are written to the new
+        // session's mapping too, even when no driver joins, so each session's files come with their mapping.
+        (self.anonymizer, self.mapping_pending) = match self.weekend.as_ref() {
+            Some(link) if self.options.anonymize => (Anonymizer::continuing(link.pseudonyms.clone()), true),
+            _ => (Anonymizer::default(), false),
+        };
+    }
+
+    /// Writes the Markdown race report from the classification and the events collected during the race
+    pub(crate) fn write_race_report(&mut self, fc: &PacketFinalClassificationData) -> io::Result<()> {
+        if !self.options.report_md || self.session_uid == NO_SESSION_UID {
+            return Ok(());
+        }
+        let Some(session_info) = self.session_info.as_ref() else {
+            return Ok(());
+        };
+        if !sessions::is_race(session_info) {
+            return Ok(());
+        }
+
+        let results = &fc.final_classifications[..(fc.num_cars as usize).min(fc.final_classifications.len())];
+        let mut report_results: Vec<ReportResult> = results
+            .iter()
+            .enumerate()
+            .filter_map(|(i, result)| {
+                let car = self.cars.get(i)?;
+                let finished = result.result_status == ResultStatus::Finished;
+                let time_ms = (result.total_race_time * 1000.0).round() as u64 + result.penalties_time as u64 * 1000;
+                Some(ReportResult {
+                    position: result.position,
+                    driver: teams::format_driver(car, self.options.identify_by),
+                    team: teams::format_team(car, self.game_year, self.options.identify_by),
+                    time_ms: finished.then_some(time_ms),
+                    best_lap_ms: result.best_lap_time,
+                })
+            })
+            .collect();
+        report_results.sort_by_key(|result| result.position);
+
+        let title =
+            format!("{} {} Race Report", session_info.track.name(), sessions::session_name(&session_info.session_type));
+        let filename = self.output_path("Report", "md");
+        println!("Writing race report to {:?}", &filename);
+        self.race_report.write(&filename, &title, &report_results)?;
+        self.created_files.push(filename);
+
+        Ok(())
+    }
+
+    pub(crate) fn write_pit_stops(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.session_uid == NO_SESSION_UID || self.pit_stops.completed().is_empty() {
+            return Ok(());
+        }
+
+        let track_length = self
+            .session_info
+            .as_ref()
+            .ok_or_else(|| Box::<dyn std::error::Error>::from("No session info available"))?
+            .track_length;
+
+        // Written at the end of the session, as penalty served events may arrive after the car left the pits
+        let mut writer = self.create_new_csv_writer("PitStops", &PIT_STOP_CSV_HEADERS)?;
+        for stop in self.pit_stops.completed() {
+            let Some(car) = self.cars.get(stop.car_idx) else {
+                continue;
+            };
+
+            let rival = self.pit_stop_rival(stop);
+            let pace =
+                stop.pace_window(self.lap_history.laps(stop.car_idx), rival.map(|rival| self.lap_history.laps(rival)));
+            let quantified = match pace {
+                PaceWindow::Quantified(pace) => Some(pace),
+                PaceWindow::Neutralised | PaceWindow::Incomplete => None,
+            };
+
+            writer.write_record(&[
+                teams::format_driver(car, self.options.identify_by),
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                stop.lap.to_string(),
+                stop.entry_time.to_string(),
+                stop.exit_time.to_string(),
+                stop.pit_lane_ms().to_string(),
+                stop.stationary_ms.to_string(),
+                stop.time_loss_ms(track_length).map(|loss| loss.to_string()).unwrap_or_default(),
+                format_optional_bool(Some(stop.penalty)),
+                stop.tyres_before.as_ref().map(|tyres| tyres.compound.clone()).unwrap_or_default(),
+                format_tyre_age(stop.tyres_before.as_ref()),
+                stop.tyres_after.as_ref().map(|tyres| tyres.compound.clone()).unwrap_or_default(),
+                format_tyre_age(stop.tyres_after.as_ref()),
+                format_optional_bool(stop.tyres_changed()),
+                rival
+                    .and_then(|rival| self.cars.get(rival))
+                    .map(|rival| teams::format_driver(rival, self.options.identify_by))
+                    .unwrap_or_default(),
+                quantified.map(|pace| format!("{:.0}", pace.before_ms)).unwrap_or_default(),
+                quantified.map(|pace| format!("{:.0}", pace.after_ms)).unwrap_or_default(),
+                quantified.map(|pace| pace.pit_loss_ms.to_string()).unwrap_or_default(),
+                quantified
+                    .and_then(|pace| pace.net_swing_ms)
+                    .map(|swing| format!("{:.3}", swing / 1000.0))
+                    .unwrap_or_default(),
+                match pace {
+                    PaceWindow::Quantified(_) => format_optional_bool(Some(true)),
+                    PaceWindow::Neutralised => format_optional_bool(Some(false)),
+                    PaceWindow::Incomplete => format_optional_bool(None),
+                },
+            ])?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// The car a pit stop is compared against: the one a position ahead when the in lap started, i.e. the
+    /// car an undercut attacks, or the one behind for the leader. Only cars on the same lap are found.
+    fn pit_stop_rival(&self, stop: &PitStop) -> Option<usize> {
+        let position = self.lap_history.laps(stop.car_idx).iter().find(|lap| lap.lap == stop.lap)?.start_position;
+        let target = if position == 1 { 2 } else { position - 1 };
+        (0..self.cars.len())
+            .filter(|&i| i != stop.car_idx)
+            .find(|&i| self.lap_history.laps(i).iter().any(|lap| lap.lap + 1 == stop.lap && lap.end_position == target))
+    }
+
+    /// Writes each car's position at the end of every lap, one row per lap and one column per driver, the
+    /// input of race progression charts. Row 0 holds the grid if the race was logged from the start. Lapped
+    /// cars are listed at the end of their own lap with that number, retired cars are blank after their last lap.
+    pub(crate) fn write_lap_positions(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.session_uid == NO_SESSION_UID || self.lap_history.max_laps() == 0 {
+            return Ok(());
+        }
+
+        let session_info = self
+            .session_info
+            .as_ref()
+            .ok_or_else(|| Box::<dyn std::error::Error>::from("No session info available"))?;
+        if !sessions::is_race(session_info) {
+            return Ok(());
+        }
+
+        let drivers: Vec<String> =
+            self.cars.iter().map(|car| teams::format_driver(car, self.options.identify_by)).collect();
+        let mut headers = vec!["Lap"];
+        headers.extend(drivers.iter().map(String::as_str));
+        let mut writer = self.create_new_csv_writer("Positions", &headers)?;
+
+        // Keyed by the session lap, so the rows line up for cars that were lapped or joined late. The history
+        // starts later when the logger was started during the race.
+        let laps = (0..self.cars.len()).flat_map(|i| self.lap_history.laps(i)).map(|snapshot| snapshot.session_lap);
+        let (first_lap, last_lap) = (laps.clone().min().unwrap_or(1), laps.max().unwrap_or_default());
+
+        if first_lap == 1 {
+            let grid = (0..self.cars.len()).map(|i| {
+                let first = self.lap_history.laps(i).first().filter(|snapshot| snapshot.lap == 1);
+                first.map(|snapshot| snapshot.start_position.to_string()).unwrap_or_default()
+            });
+            writer.write_record(std::iter::once("0".to_string()).chain(grid))?;
+        }
+
+        for lap in first_lap..=last_lap {
+            let positions = (0..self.cars.len()).map(|i| {
+                self.lap_history
+                    .laps(i)
+                    .iter()
+                    .find(|snapshot| snapshot.session_lap == lap)
+                    .map(|snapshot| snapshot.end_position.to_string())
+                    .unwrap_or_default()
+            });
+            writer.write_record(std::iter::once(lap.to_string()).chain(positions))?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes each driver's lap time spread over the clean laps, i.e. without the standing start, in and
+    /// out laps and laps not fully run under green flag. Drivers with too few clean laps get "N/A".
+    pub(crate) fn write_consistency(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.session_uid == NO_SESSION_UID || self.lap_history.max_laps() == 0 {
+            return Ok(());
+        }
+        if self.session_info.is_none() {
+            return Err("No session info available".into());
+        }
+
+        let mut rows: Vec<(Option<f64>, Vec<String>)> = Vec::new();
+        for (i, car) in self.cars.iter().enumerate() {
+            let stats = self.lap_history.lap_time_stats(i);
+            let std_dev = stats.and_then(|stats| stats.std_dev_ms);
+            let fastest = self.lap_history.laps(i).iter().map(|lap| lap.lap_time_ms).filter(|&time| time > 0).min();
+            let stints = self.pit_stops.stint(i);
+
+            rows.push((
+                std_dev,
+                vec![
+                    teams::format_driver(car, self.options.identify_by),
+                    teams::format_team(car, self.game_year, self.options.identify_by),
+                    stats.map(|stats| stats.clean_laps).unwrap_or_default().to_string(),
+                    stats.map(|stats| format!("{:.0}", stats.mean_ms)).unwrap_or_else(|| "N/A".to_string()),
+                    std_dev.map(|std_dev| format!("{:.0}", std_dev)).unwrap_or_else(|| "N/A".to_string()),
+                    fastest.map(|time| time.to_string()).unwrap_or_default(),
+                    stints.to_string(),
+                ],
+            ));
+        }
+
+        // Most consistent drivers first, drivers without enough clean laps last
+        rows.sort_by(|(a, _), (b, _)| a.unwrap_or(f64::MAX).total_cmp(&b.unwrap_or(f64::MAX)));
+
+        let mut writer = self.create_new_csv_writer("Consistency", &CONSISTENCY_CSV_HEADERS)?;
+        for (_, row) in rows {
+            writer.write_record(&row)?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the "Performance" CSV with the best sectors, top speed and clean lap pace averaged per team
+    pub(crate) fn write_performance(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.session_uid == NO_SESSION_UID || self.lap_history.max_laps() == 0 {
+            return Ok(());
+        }
+        if self.session_info.is_none() {
+            return Err("No session info available".into());
+        }
+
+        let mut writer = self.create_new_csv_writer("Performance", &PERFORMANCE_CSV_HEADERS)?;
+        // Cars without a lap, e.g. unused participant slots, would dilute the averages
+        let cars =
+            self.cars.iter().enumerate().filter(|&(i, _)| !self.lap_history.laps(i).is_empty()).map(|(i, car)| {
+                let performance = CarPerformance {
+                    best_sectors: self.lap_history.best_sectors(i),
+                    top_speed: self.top_speeds.get(i).copied().filter(|&speed| speed > 0),
+                    race_pace_ms: self.lap_history.lap_time_stats(i).map(|stats| stats.mean_ms),
+                };
+                (teams::team_key(car, self.game_year), performance)
+            });
+
+        let format_ms = |value: Option<f64>| value.map(|ms| format!("{:.0}", ms)).unwrap_or_default();
+        for team in performance::by_team(cars) {
+            writer.write_record(&[
+                team.team,
+                team.drivers.to_string(),
+                format_ms(team.best_sectors_ms[0]),
+                format_ms(team.best_sectors_ms[1]),
+                format_ms(team.best_sectors_ms[2]),
+                team.top_speed.map(|speed| format!("{:.1}", speed)).unwrap_or_default(),
+                format_ms(team.race_pace_ms),
+            ])?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn observers_mut(&mut self) -> [&mut dyn PacketObserver; 4] {
+        [&mut self.position_holds, &mut self.gaps, &mut self.pit_stops, &mut self.driving_style]
+    }
+
+    /// Hands the packet to the trackers, before the session state is updated from it
+    pub(crate) fn observe(&mut self, packet: &Packet) {
+        let context = SessionContext {
+            lap_data: &self.lap_data,
+            car_status: &self.car_status,
+            cars: &self.cars,
+            game_year: self.game_year,
+            metadata: self.metadata.as_ref(),
+        };
+        // Borrowed field by field rather than through observers_mut(), as the context borrows the state too
+        let observers: [&mut dyn PacketObserver; 4] =
+            [&mut self.position_holds, &mut self.gaps, &mut self.pit_stops, &mut self.driving_style];
+        for observer in observers {
+            observer.observe(packet, &context);
+        }
+    }
+
+    pub(crate) fn update_lap_data(&mut self, lap_packet: PacketLapData) -> Result<(), Box<dyn std::error::Error>> {
+        let session_time = lap_packet.header.session_time;
+        let phase = self
+            .session_info
+            .as_ref()
+            .map_or(Phase::Green, |session| Phase::from_safety_car_status(&session.safety_car_status));
+        let completed = self.lap_history.update(&self.lap_data, &lap_packet.lap_data, session_time, phase);
+        self.write_completed_laps(&completed);
+        self.write_lap_styles(&completed)?;
+        self.record_lap_start_tyres(&completed);
+        for &i in &completed {
+            if let Some(lap) = self.lap_history.last_lap(i) {
+                self.runs.record_lap(i, lap);
+                if let (Some(sink), Some(car)) = (self.statsd_sink.as_mut(), self.cars.get(i)) {
+                    if lap.lap_time_ms > 0 {
+                        sink.record_lap_time(&teams::format_driver(car, self.options.identify_by), lap.lap_time_ms);
+                    }
+                }
+            }
+        }
+        self.runs.update(&lap_packet.lap_data, &self.car_status);
+        self.record_frames(&lap_packet.lap_data, session_time);
+        self.flush_if_due(session_time)?;
+
+        self.detect_finishes(&lap_packet.lap_data, session_time)?;
+
+        let time_trial =
+            self.session_info.as_ref().is_some_and(|session| matches!(session.session_type, SessionType::TimeTrial));
+        if self.options.live_display && time_trial {
+            // Without a player, e.g. while spectating a time trial, the display shows the spectated car
+            let (car_idx, spectating) = match self.player_car_index {
+                Some(idx) => (Some(idx), false),
+                None => (self.spectated_car, self.spectated_car.is_some()),
+            };
+            self.time_trial_display.update(&self.lap_data, &lap_packet.lap_data, car_idx, spectating);
+        }
+
+        self.detect_lead_change(&lap_packet.lap_data, session_time);
+        self.lap_data = lap_packet.lap_data;
+        if !completed.is_empty() {
+            self.check_fastest_lap();
+        }
+        self.update_fastest_lap_eligibility();
+        self.check_car_counts();
+        self.print_progress();
+        Ok(())
+    }
+
+    /// Records a change of the race leader once the race is underway, the grid order isn't a lead change
+    fn detect_lead_change(&mut self, lap_data: &[LapData], session_time: u32) {
+        if self.race_start_time.is_none() {
+            return;
+        }
+
+        let leader = |laps: &[LapData]| laps.iter().position(|lap| lap.car_position == 1);
+        let (Some(previous), Some(current)) = (leader(&self.lap_data), leader(lap_data)) else {
+            return;
+        };
+        if previous == current {
+            return;
+        }
+        let (Some(old_leader), Some(new_leader)) = (self.cars.get(previous), self.cars.get(current)) else {
+            return;
+        };
+
+        let description = format!(
+            "{} takes the lead from {}",
+            teams::format_driver(new_leader, self.options.identify_by),
+            teams::format_driver(old_leader, self.options.identify_by)
+        );
+        self.record_timeline(TimelineEntry {
+            session_time,
+            kind: "Lead Change",
+            description,
+            details: serde_json::json!({ "lap": lap_data[current].current_lap_num }),
+        });
+    }
+
+    fn record_frames(&mut self, lap_data: &[LapData], session_time: u32) {
+        let Some(db) = self.replay_db.as_mut() else {
+            return;
+        };
+        if self.session_uid == NO_SESSION_UID {
+            return;
+        }
+
+        // Unused slots of the grid have no position
+        for (i, lap) in lap_data.iter().enumerate().filter(|(_, lap)| lap.car_position > 0) {
+            let snapshot = FrameSnapshot {
+                session_time,
+                car_idx: i as u8,
+                lap: lap.current_lap_num,
+                position: lap.car_position,
+                lap_distance: lap.lap_distance,
+                speed: self.car_speeds.get(i).copied().unwrap_or_default(),
+            };
+            db.record_frame(self.session_uid, snapshot);
+        }
+    }
+
+    pub(crate) fn update_player_trace(&mut self, motion: &PacketMotionData) -> io::Result<()> {
+        let Some(interval) = self.options.player_trace_interval_ms else {
+            return Ok(());
+        };
+        if self.trace_writer.is_none() && self.compact_trace_writer.is_none() {
+            return Ok(());
+        }
+
+        let session_time = motion.header.session_time;
+        if self.last_trace_time.is_some_and(|last| session_time < last.saturating_add(interval)) {
+            return Ok(());
+        }
+
+        let Some(player_idx) = self.player_car_index else {
+            return Ok(());
+        };
+
+        let Some(car_motion) = motion.car_motion_data.get(player_idx as usize) else {
+            return Ok(());
+        };
+        let lap = self.lap_data.get(player_idx as usize).map(|lap| lap.current_lap_num).unwrap_or_default();
+
+        if let Some(writer) = self.trace_writer.as_mut() {
+            writer.write_record([
+                session_time.to_string(),
+                lap.to_string(),
+                car_motion.world_position.x.to_string(),
+                car_motion.world_position.z.to_string(),
+            ]);
+        }
+        if let Some(writer) = self.compact_trace_writer.as_mut() {
+            let point =
+                PositionPoint { session_time, lap, x: car_motion.world_position.x, z: car_motion.world_position.z };
+            writer.push(player_idx, point)?;
+        }
+        self.last_trace_time = Some(session_time);
+
+        Ok(())
+    }
+
+    pub(crate) fn update_participants(&mut self, participants_packet: PacketParticipantsData) {
+        self.num_active_cars = Some(participants_packet.num_active_cars);
+        self.cars = participants_packet.participants;
+        let filled = self.options.empty_names.fill(&mut self.cars, participants_packet.num_active_cars as usize);
+        if filled > 0 && !self.empty_names_reported {
+            println!("{} drivers were sent without a name, see --empty-names", filled);
+            self.empty_names_reported = true;
+        }
+        // Resolved before anonymizing, as the drivers are given by their real names
+        self.update_selected_cars();
+        if self.options.anonymize {
+            self.mapping_pending |= self.anonymizer.anonymize(&mut self.cars);
+            self.write_mapping();
+        }
+        self.check_car_counts();
+
+        // Without a given car the followed car is the spectated one, if any, see `update_spectated_car`
+        let Some(follow_car) = self.options.follow_car else {
+            return;
+        };
+        let followed_car = follow_car.resolve(&self.cars);
+        if followed_car != self.followed_car {
+            match followed_car.and_then(|idx| self.cars.get(idx as usize)) {
+                Some(car) => println!("Following {}", teams::format_driver(car, self.options.identify_by)),
+                None => println!("Followed car not found among the participants"),
+            }
+            self.followed_car = followed_car;
+        }
+    }
+
+    fn update_selected_cars(&mut self) {
+        let Some(drivers) = &self.options.drivers else {
+            return;
+        };
+        let selected_cars = drivers.resolve(&self.cars);
+        if self.selected_cars.as_ref() != Some(&selected_cars) {
+            let names: Vec<&str> = selected_cars
+                .iter()
+                .filter_map(|&idx| self.cars.get(idx as usize))
+                .map(|car| car.name.as_str())
+                .collect();
+            if names.is_empty() {
+                println!("None of the selected drivers is among the participants");
+            } else {
+                println!("Logging the events of {}", names.join(", "));
+            }
+            self.selected_cars = Some(selected_cars);
+        }
+    }
+
+    /// Rewrites the "Mapping" CSV of the real names once new pseudonyms were assigned. Retried with the next
+    /// participants packet if the session isn't known yet or writing fails.
+    fn write_mapping(&mut self) {
+        if !self.mapping_pending || self.session_uid == NO_SESSION_UID {
+            return;
+        }
+        if self.session_info.is_none() {
+            return;
+        }
+
+        let written = self.create_new_csv_writer("Mapping", &MAPPING_CSV_HEADERS).and_then(|mut writer| {
+            for (pseudonym, name) in self.anonymizer.mapping() {
+                writer.write_record([pseudonym.as_str(), name])?;
+            }
+            writer.flush()
+        });
+        match written {
+            Ok(()) => self.mapping_pending = false,
+            Err(err) => println!("Failed to write the pseudonym mapping: {}", err),
+        }
+    }
+
+    /// Tracks the car the camera follows while spectating, which is also the followed car with
+    /// [`SessionOptions::follow_spectated`] unless a car is given explicitly
+    fn update_spectated_car(&mut self, session_data: &PacketSessionData) {
+        let spectated_car =
+            Some(session_data.spectator_car_index).filter(|&idx| session_data.is_spectating && idx < MAX_CARS);
+        if spectated_car == self.spectated_car {
+            return;
+        }
+
+        match spectated_car.and_then(|idx| self.cars.get(idx as usize)) {
+            Some(car) => println!("Spectating {}", teams::format_driver(car, self.options.identify_by)),
+            None if spectated_car.is_some() => println!("Spectating car {}", session_data.spectator_car_index),
+            None => {},
+        }
+        self.spectated_car = spectated_car;
+
+        if self.options.follow_spectated && self.options.follow_car.is_none() {
+            self.followed_car = spectated_car;
+        }
+    }
+
+    /// Prints the description of an event involving the followed car, which is logged like any other
+    fn highlight_followed(&self, car_indexes: &[u8], description: &str) {
+        if self.followed_car.is_some_and(|followed| car_indexes.contains(&followed)) {
+            println!("[Followed] {}", description);
+        }
+    }
+
+    /// Warns when the participants, car status and lap data arrays disagree on the number of cars, which
+    /// leaves some cars without a name or data. Lookups by car index all go through `get` to skip those cars.
+    fn check_car_counts(&mut self) {
+        let (cars, status, laps) = (self.cars.len(), self.car_status.len(), self.lap_data.len());
+        let Some(active) = self.num_active_cars else {
+            return;
+        };
+
+        // Arrays not received yet are empty and not a mismatch
+        let consistent = [cars, status, laps].iter().all(|&len| len == 0 || len >= active as usize)
+            && [status, laps].iter().all(|&len| len == 0 || len == cars);
+        if consistent {
+            self.car_count_mismatch = None;
+            return;
+        }
+
+        let counts = (cars, status, laps, active);
+        if self.car_count_mismatch != Some(counts) {
+            println!(
+                "Car counts disagree - {} active cars, {} participants, {} car status, {} lap data entries",
+                active, cars, status, laps
+            );
+            self.car_count_mismatch = Some(counts);
+        }
+    }
+
+    /// Keeps the tyres of the cars still racing, the status of cars that finished or retired isn't updated
+    /// reliably, e.g. a pit stop on the cool-down lap would show up as their final tyres
+    fn update_final_tyres(&mut self) {
+        if self.final_tyres.len() < self.car_status.len() {
+            self.final_tyres.resize(self.car_status.len(), None);
+        }
+
+        for (i, status) in self.car_status.iter().enumerate() {
+            if self.lap_data.get(i).is_some_and(|lap| lap.result_status == ResultStatus::Active) {
+                self.final_tyres[i] = Some(TyreSet::from_status(status));
+            }
+        }
+    }
+
+    /// Records the tyres and stint the cars start their current lap on. The laps CSV reports these rather than
+    /// the car status when the lap is completed, which may already show the tyres fitted in a pit stop.
+    fn record_lap_start_tyres(&mut self, cars: &[usize]) {
+        for &i in cars {
+            let Some(status) = self.car_status.get(i) else {
+                continue;
+            };
+            if self.lap_start_tyres.len() <= i {
+                self.lap_start_tyres.resize(i + 1, None);
+            }
+            self.lap_start_tyres[i] = Some((TyreSet::from_status(status), self.pit_stops.stint(i)));
+        }
+    }
+
+    pub(crate) fn update_car_status(&mut self, status_packet: PacketCarStatusData) -> io::Result<()> {
+        self.car_status = status_packet.car_status_data;
+        self.check_car_counts();
+        if self.session_uid == NO_SESSION_UID {
+            return Ok(());
+        }
+
+        if self.options.final_tyres {
+            self.update_final_tyres();
+        }
+        let unrecorded: Vec<usize> =
+            (0..self.car_status.len()).filter(|&i| self.lap_start_tyres.get(i).is_none_or(Option::is_none)).collect();
+        self.record_lap_start_tyres(&unrecorded);
+        for (car_idx, component) in self.faults.update(&self.car_status) {
+            self.record_fault(car_idx, component, status_packet.header.session_time);
+        }
+
+        let car_indexes: Vec<usize> = if self.options.controls_all_cars {
+            (0..self.car_status.len()).collect()
+        } else {
+            self.player_car_index.map(|idx| idx as usize).into_iter().collect()
+        };
+
+        for i in car_indexes {
+            let (Some(status), Some(lap)) = (self.car_status.get(i), self.lap_data.get(i)) else {
+                continue;
+            };
+
+            let change = ControlChange {
+                car_idx: i,
+                modes: ControlModes::from_status(status),
+                lap: lap.current_lap_num,
+                lap_distance: lap.lap_distance,
+                session_time: status_packet.header.session_time,
+            };
+            if let Some(change) = self.controls.update(change) {
+                self.write_control_change(&change)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_control_change(&mut self, change: &ControlChange) -> io::Result<()> {
+        if self.cars.get(change.car_idx).is_none() {
+            return Ok(());
+        }
+
+        if self.controls_writer.is_none() {
+            if self.session_info.is_none() {
+                return Ok(());
+            }
+            self.controls_writer = Some(self.create_sink("Controls", &CONTROLS_CSV_HEADERS)?);
+        }
+
+        if let (Some(car), Some(writer)) = (self.cars.get(change.car_idx), self.controls_writer.as_mut()) {
+            writer.write_record(&[
+                teams::format_driver(car, self.options.identify_by),
+                teams::format_team(car, self.game_year, self.options.identify_by),
+                format!("{:?}", change.modes.fuel_mix),
+                format!("{:?}", change.modes.ers_deploy_mode),
+                change.lap.to_string(),
+                (change.lap_distance as u16).to_string(),
+                change.session_time.to_string(),
+            ]);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn update_car_speeds(&mut self, telemetry: &[CarTelemetryData]) {
+        self.car_speeds.clear();
+        self.car_speeds.extend(telemetry.iter().map(|car| car.speed));
+
+        self.top_speeds.resize(self.car_speeds.len().max(self.top_speeds.len()), 0);
+        for (top_speed, &speed) in self.top_speeds.iter_mut().zip(&self.car_speeds) {
+            *top_speed = (*top_speed).max(speed);
+        }
+    }
+
+    /// Follows the player through the corners with the latest speeds, writing the minimum speed of every
+    /// completed corner
+    pub(crate) fn update_corner_speeds(&mut self) -> io::Result<()> {
+        let (Some(corners), Some(player_idx)) = (self.options.corners.as_ref(), self.player_car_index) else {
+            return Ok(());
+        };
+        let Some(session_info) = self.session_info.as_ref().filter(|_| self.session_uid != NO_SESSION_UID) else {
+            return Ok(());
+        };
+        let (Some(lap), Some(&speed)) =
+            (self.lap_data.get(player_idx as usize), self.car_speeds.get(player_idx as usize))
+        else {
+            return Ok(());
+        };
+
+        let track = session_info.track.name();
+        let Some(pass) = self.corner_speeds.record(corners, track, lap.current_lap_num, lap.lap_distance, speed) else {
+            return Ok(());
+        };
+        self.write_corner_speed(pass)
+    }
+
+    /// Writes the corner the player was in when the session ended, which no later sample completes
+    fn finish_corner_speeds(&mut self) -> io::Result<()> {
+        let Some(pass) = self.options.corners.as_ref().and_then(|corners| self.corner_speeds.take_pass(corners)) else {
+            return Ok(());
+        };
+        if self.session_uid == NO_SESSION_UID {
+            return Ok(());
+        }
+        self.write_corner_speed(pass)
+    }
+
+    fn write_corner_speed(&mut self, pass: CornerSpeed) -> io::Result<()> {
+        if self.corner_speeds_writer.is_none() {
+            self.corner_speeds_writer = Some(self.create_sink("CornerSpeeds", &CORNER_SPEED_CSV_HEADERS)?);
+        }
+        if let Some(writer) = self.corner_speeds_writer.as_mut() {
+            writer.write_record([
+                pass.lap.to_string(),
+                pass.corner,
+                pass.min_speed.map(|speed| speed.to_string()).unwrap_or_default(),
+                pass.lap_distance.map(|distance| format!("{:.0}", distance)).unwrap_or_default(),
+                pass.samples.to_string(),
+                format_optional_bool(Some(pass.clear)),
+            ]);
+        }
+        Ok(())
+    }
+
+    /// Measures the telemetry send rate, recording it in the metadata and warning about the features it makes
+    /// coarse
+    pub(crate) fn update_telemetry_rate(&mut self, session_time: u32) -> io::Result<()> {
+        if self.session_uid == NO_SESSION_UID {
+            return Ok(());
+        }
+        let Some(hz) = self.telemetry_rate.record(session_time) else {
+            return Ok(());
+        };
+
+        println!("Telemetry is arriving at {} Hz", hz);
+        let logs_overtakes = self.writers.is_enabled(LogCategory::Overtakes);
+        for warning in rates::rate_warnings(hz, self.options.player_trace_interval_ms, logs_overtakes) {
+            println!("WARNING: {}", warning);
+        }
+
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata.telemetry_rate_hz = Some(hz);
+        }
+        if self.session_info.is_some() {
+            self.write_metadata()?;
+        }
+        Ok(())
+    }
+
+    fn handle_chequered_flag(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.chequered_flag_shown {
+            return Ok(());
+        }
+
+        if self.session_info.is_none() {
+            return Err("No session info available".into());
+        }
+
+        // Cars that already crossed the line stay finished, the flag may arrive after the winner's crossing
+        self.chequered_flag_shown = true;
+        if self.finished_cars.len() < self.lap_data.len() {
+            self.finished_cars.resize(self.lap_data.len(), false);
+        }
+
+        Ok(())
+    }
+
+    fn detect_finishes(
+        &mut self,
+        new_lap_data: &[LapData],
+        session_time: u32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // Other sessions have no finish before the flag, e.g. a car's run ending in qualifying
+        let race = self.session_info.as_ref().is_some_and(sessions::is_race);
+        if self.session_uid == NO_SESSION_UID || !(race || self.chequered_flag_shown) {
+            return Ok(());
+        }
+        if self.finished_cars.len() < new_lap_data.len() {
+            self.finished_cars.resize(new_lap_data.len(), false);
+        }
+
+        let total_laps = self.session_info.as_ref().map_or(0, |session| session.total_laps);
+        // Position and completed laps of the cars crossing the line with this packet
+        let mut finishes: Vec<(usize, u8, u8)> = Vec::new();
+        for (i, (previous, current)) in self.lap_data.iter().zip(new_lap_data).enumerate() {
+            if self.finished_cars[i] {
+                continue;
+            }
+
+            match current.result_status {
+                // Retired cars never cross the line, so stop tracking them
+                ResultStatus::Retired | ResultStatus::DidNotFinish | ResultStatus::Disqualified => {
+                    self.finished_cars[i] = true;
+                    continue;
+                },
+                _ => {},
+            }
+
+            // The game may keep the lap number of the last lap at the line and only change the result status
+            let lap_completed = current.current_lap_num > previous.current_lap_num;
+            let status_finished =
+                current.result_status == ResultStatus::Finished && previous.result_status != ResultStatus::Finished;
+            let final_lap = total_laps > 0 && previous.current_lap_num >= total_laps;
+            if !(status_finished || (lap_completed && (self.chequered_flag_shown || final_lap))) {
+                continue;
+            }
+
+            self.finished_cars[i] = true;
+            let laps = if lap_completed { previous.current_lap_num } else { current.current_lap_num };
+            finishes.push((i, current.car_position, laps));
+        }
+        if finishes.is_empty() {
+            return Ok(());
+        }
+
+        if self.finishes_writer.is_none() {
+            if self.session_info.is_none() {
+                return Ok(());
+            }
+            self.finishes_writer = Some(self.create_sink("Finishes", &FINISH_CSV_HEADERS)?);
+        }
+
+        for (i, position, laps) in finishes {
+            let winner_time = *self.winner_finish_time.get_or_insert(session_time);
+            let Some(car) = self.cars.get(i) else {
+                continue;
+            };
+
+            if let Some(writer) = self.finishes_writer.as_mut() {
+                writer.write_record(&[
+                    position.to_string(),
+                    teams::format_driver(car, self.options.identify_by),
+                    teams::format_team(car, self.game_year, self.options.identify_by),
+                    laps.to_string(),
+                    session_time.to_string(),
+                    // Reordered packets may report a later car with an earlier session time
+                    session_time.saturating_sub(winner_time).to_string(),
+                ]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ranks drivers and teams by their stationary times, ignoring penalty stops which would skew the
+    /// ranking. The time loss per driver covers all stops, as penalty stops cost time all the same.
+    fn pit_stop_summary(&self, track_length: u16) -> (SummarySection, SummarySection) {
+        fn stationary_stats(stops: &[&PitStop]) -> (usize, Option<u32>, Option<u32>) {
+            let times: Vec<u32> = stops.iter().filter(|stop| !stop.penalty).map(|stop| stop.stationary_ms).collect();
+            let best = times.iter().min().copied();
+            let average = (!times.is_empty()).then(|| times.iter().sum::<u32>() / times.len() as u32);
+            (times.len(), best, average)
+        }
+
+        let format_ms = |value: Option<u32>| value.map(|ms| ms.to_string()).unwrap_or_default();
+
+        let mut drivers: Vec<(Option<u32>, Vec<String>)> = Vec::new();
+        let mut team_stops: Vec<(String, Vec<&PitStop>)> = Vec::new();
+        for (i, car) in self.cars.iter().enumerate() {
+            let stops: Vec<&PitStop> = self.pit_stops.completed().iter().filter(|stop| stop.car_idx == i).collect();
+            if stops.is_empty() {
+                continue;
+            }
+
+            let (count, best, average) = stationary_stats(&stops);
+            let time_loss: u32 = stops.iter().filter_map(|stop| stop.time_loss_ms(track_length)).sum();
+            drivers.push((
+                best,
+                vec![
+                    teams::format_driver(car, self.options.identify_by),
+                    teams::format_team(car, self.game_year, self.options.identify_by),
+                    count.to_string(),
+                    format_ms(best),
+                    format_ms(average),
+                    time_loss.to_string(),
+                ],
+            ));
+
+            let team = teams::team_name(&car.team, self.game_year).to_string();
+            match team_stops.iter_mut().find(|(name, _)| *name == team) {
+                Some((_, existing)) => existing.extend(stops),
+                None => team_stops.push((team, stops)),
+            }
+        }
+
+        // Drivers and teams without a regular stop are listed last
+        drivers.sort_by_key(|(best, _)| best.unwrap_or(u32::MAX));
+        let mut driver_section = SummarySection::new("Pit Stops by Driver", &PIT_STOP_DRIVER_SUMMARY_HEADERS);
+        for (_, row) in drivers {
+            driver_section.push_row(row);
+        }
+
+        let mut team_rows: Vec<(Option<u32>, Vec<String>)> = team_stops
+            .into_iter()
+            .map(|(team, stops)| {
+                let (count, best, average) = stationary_stats(&stops);
+                (best, vec![team, count.to_string(), format_ms(best), format_ms(average)])
+            })
+            .collect();
+        team_rows.sort_by_key(|(best, _)| best.unwrap_or(u32::MAX));
+        let mut team_section = SummarySection::new("Pit Stops by Team", &PIT_STOP_TEAM_SUMMARY_HEADERS);
+        for (_, row) in team_rows {
+            team_section.push_row(row);
+        }
+
+        (driver_section, team_section)
+    }
+
+    /// Writes the completed laps into the laps CSV and the rolling file, each with its own columns
+    fn write_completed_laps(&mut self, completed: &[usize]) {
+        if completed.is_empty() {
+            return;
+        }
+
+        let context = SessionContext {
+            lap_data: &self.lap_data,
+            car_status: &self.car_status,
+            cars: &self.cars,
+            game_year: self.game_year,
+            metadata: self.metadata.as_ref(),
+        };
+        let rolling_lap_columns = columns::columns_by_header(&LAP_COLUMNS, &ROLLING_LAP_CSV_HEADERS);
+        let mut outputs: Vec<(&mut dyn EventSink, &[usize])> = Vec::with_capacity(2);
+        if let Some(writer) = self.writers.get_mut(LogCategory::Laps) {
+            outputs.push((writer, &self.lap_columns));
+        }
+        // The rows of the rolling file are only told apart by their session's context
+        if let (Some(writer), Some(_)) = (self.rolling_laps_writer.as_mut(), context.metadata) {
+            outputs.push((writer, &rolling_lap_columns));
+        }
+
+        for &i in completed {
+            let (Some(car), Some(lap)) = (self.cars.get(i), self.lap_history.last_lap(i)) else {
+                continue;
+            };
+
+            let lap_row = LapRow {
+                context: &context,
+                car,
+                lap,
+                run: self.runs.current_run(i),
+                start_tyres: self.lap_start_tyres.get(i).and_then(Option::as_ref),
+            };
+            for (writer, columns) in &mut outputs {
+                self.row.clear();
+                for &column in columns.iter() {
+                    lap_row.write_column(LAP_COLUMNS[column].key, self.options.identify_by, &mut self.row);
+                }
+                writer.write_row(self.row.record());
+            }
+        }
+    }
+
+    /// Writes the driving style of the completed laps, resetting the counters of the cars for their next lap
+    fn write_lap_styles(&mut self, completed: &[usize]) -> io::Result<()> {
+        for &i in completed {
+            let style = self.driving_style.finish_lap(i);
+            if !self.options.driving_style || self.session_uid == NO_SESSION_UID {
+                continue;
+            }
+            if self.cars.get(i).is_none() || self.lap_history.last_lap(i).is_none() || self.session_info.is_none() {
+                continue;
+            }
+
+            if self.style_writer.is_none() {
+                self.style_writer = Some(self.create_sink("Style", &STYLE_CSV_HEADERS)?);
+            }
+            if let (Some(car), Some(lap), Some(writer)) =
+                (self.cars.get(i), self.lap_history.last_lap(i), self.style_writer.as_mut())
+            {
+                self.row
+                    .clear()
+                    .display(teams::driver_label(car, self.options.identify_by))
+                    .display(teams::team_label(car, self.game_year, self.options.identify_by))
+                    .display(lap.lap)
+                    .display_opt(style.map(|style| style.gear_changes))
+                    .display_opt(style.map(|style| style.hard_brakings))
+                    .display_opt(style.map(|style| Percent(style.full_throttle_percent)))
+                    .text(bool_label(style.map(|style| style.reliable)));
+                writer.write_record(self.row.record());
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes all open writers at most once per [`FLUSH_INTERVAL_MS`] of session time, so bursts of events
+    /// don't each cost a write to disk while rows still reach the files shortly after they are logged
+    fn flush_if_due(&mut self, session_time: u32) -> io::Result<()> {
+        if session_time.saturating_sub(self.last_flush_time) < FLUSH_INTERVAL_MS {
+            return Ok(());
+        }
+
+        self.last_flush_time = session_time;
+        self.flush_writers()?;
+
+        // A single failing output is isolated by its sink, only losing every output stops the logger
+        let mut quarantined: Vec<bool> =
+            self.sinks_mut().into_iter().flatten().map(|sink| sink.is_quarantined()).collect();
+        quarantined.extend(self.writers.sinks_mut().map(|sink| sink.is_quarantined()));
+        if !quarantined.is_empty() && quarantined.iter().all(|&quarantined| quarantined) {
+            return Err(io::Error::new(io::ErrorKind::Other, "All outputs are failing"));
+        }
+        Ok(())
+    }
+
+    fn flush_writers(&mut self) -> io::Result<()> {
+        for sink in self.sinks_mut().into_iter().flatten() {
+            sink.flush();
+        }
+        for sink in self.writers.sinks_mut() {
+            sink.flush();
+        }
+        if let Some(writer) = self.compact_trace_writer.as_mut() {
+            writer.flush()?;
+        }
+        if let Some(writer) = self.rolling_laps_writer.as_mut() {
+            writer.flush();
+        }
+        if let Some(db) = self.replay_db.as_mut() {
+            db.flush();
+        }
+        Ok(())
+    }
+
+    fn sinks_mut(&mut self) -> [&mut Option<CsvSink>; 10] {
+        [
+            &mut self.finishes_writer,
+            &mut self.trace_writer,
+            &mut self.unknown_events_writer,
+            &mut self.raw_events_writer,
+            &mut self.controls_writer,
+            &mut self.markers_writer,
+            &mut self.corner_speeds_writer,
+            &mut self.style_writer,
+            &mut self.forecast_writer,
+            &mut self.conditions_writer,
+        ]
+    }
+
+    fn reset_finish_tracking(&mut self) -> io::Result<()> {
+        self.chequered_flag_shown = false;
+        self.finished_cars.clear();
+        self.winner_finish_time = None;
+        let fsync = self.options.fsync;
+        incomplete_outputs(self.finishes_writer.take().is_some_and(|sink| !sink.close(fsync)).into())
+    }
+
+    fn create_overtake_event(
+        &self,
+        ot: &Overtake,
+        session_time: u32,
+    ) -> Result<OvertakeEventLog, Box<dyn std::error::Error>> {
+        let get_car = |idx: u8| -> Result<&ParticipantData, Box<dyn std::error::Error>> {
+            self.cars.get(idx as usize).ok_or_else(|| Box::from("Car data not found"))
+        };
+        let get_status = |idx: u8| -> Result<&CarStatusData, Box<dyn std::error::Error>> {
+            self.car_status.get(idx as usize).ok_or_else(|| Box::from("Car status not found"))
+        };
+        let get_speed = |idx: u8| -> u16 { self.car_speeds.get(idx as usize).copied().unwrap_or(0) };
+        let get_last_lap = |idx: u8| -> Option<u32> {
+            self.lap_data.get(idx as usize).map(|lap| lap.last_lap_time).filter(|&time| time > 0)
+        };
+
+        let previous_swap = self.pair_swaps.get(&swap_pair(ot.overtaking_vehicle_idx, ot.being_overtaken_vehicle_idx));
+
+        let overtaker = get_car(ot.overtaking_vehicle_idx)?;
         let overtaker_status = get_status(ot.overtaking_vehicle_idx)?;
         let overtakee = get_car(ot.being_overtaken_vehicle_idx)?;
         let overtakee_status = get_status(ot.being_overtaken_vehicle_idx)?;
@@ -178,66 +3700,565 @@ impl SessionState {
             .lap_data
             .get(ot.being_overtaken_vehicle_idx as usize)
             .ok_or_else(|| Box::<dyn std::error::Error>::from("Lap data not found"))?;
+        let track_length = self.session_info.as_ref().map_or(0, |session| session.track_length);
+        let lap_distance = locations::normalize_lap_distance(lap.lap_distance, track_length);
 
         Ok(OvertakeEventLog {
-            overtaker_name: overtaker.name.clone(),
-            overtaker_team: format!("{} ({})", overtaker.team.name(), overtaker.race_number),
+            overtaker_name: teams::format_driver(overtaker, self.options.identify_by),
+            overtaker_team: teams::format_team(overtaker, self.game_year, self.options.identify_by),
             overtaker_speed: get_speed(ot.overtaking_vehicle_idx),
             overtaker_tyre_compound: overtaker_status.visual_tyre_compound.name().to_string(),
             overtaker_tyre_age: overtaker_status.tyre_age_laps.unwrap_or(u8::MAX),
-            overtakee_name: overtakee.name.clone(),
-            overtakee_team: format!("{} ({})", overtakee.team.name(), overtakee.race_number),
+            overtakee_name: teams::format_driver(overtakee, self.options.identify_by),
+            overtakee_team: teams::format_team(overtakee, self.game_year, self.options.identify_by),
             overtakee_speed: get_speed(ot.being_overtaken_vehicle_idx),
             overtakee_tyre_compound: overtakee_status.visual_tyre_compound.name().to_string(),
             overtakee_tyre_age: overtakee_status.tyre_age_laps.unwrap_or(u8::MAX),
             for_pos: lap.car_position,
             lap: lap.current_lap_num,
+            track_position: lap_distance as u16,
+            near_line: locations::near_line(lap_distance, track_length),
+            location_bin: LocationBin::new(lap_distance as u16, self.options.location_bin_m),
+            session_time_ms: session_time,
+            involves_player: self.involves_player(&[ot.overtaking_vehicle_idx, ot.being_overtaken_vehicle_idx]),
+            collision_time: self.recent_collisions.find(
+                ot.overtaking_vehicle_idx,
+                ot.being_overtaken_vehicle_idx,
+                session_time,
+            ),
+            defense_duration_ms: self.position_holds.held_for_ms(ot.being_overtaken_vehicle_idx as usize, session_time),
+            pressure_from_behind_ms: self
+                .car_behind(ot.being_overtaken_vehicle_idx, ot.overtaking_vehicle_idx)
+                .and_then(|behind| {
+                    self.gaps.gap_to_car_in_front_ms(&self.lap_data, ot.being_overtaken_vehicle_idx as usize, behind)
+                }),
+            overtaker_last_lap_ms: get_last_lap(ot.overtaking_vehicle_idx),
+            overtakee_last_lap_ms: get_last_lap(ot.being_overtaken_vehicle_idx),
+            reversed: self
+                .recent_passes
+                .find(ot.being_overtaken_vehicle_idx, ot.overtaking_vehicle_idx, session_time)
+                .is_some(),
+            // The latest lap data usually still has the overtaker behind, otherwise the gap is reconstructed
+            gap_ahead_ms: self.gaps.gap_to_car_in_front_ms(
+                &self.lap_data,
+                ot.being_overtaken_vehicle_idx as usize,
+                ot.overtaking_vehicle_idx as usize,
+            ),
+            gap_lap_before_ms: self
+                .gaps
+                .gap_lap_before_ms(ot.being_overtaken_vehicle_idx as usize, ot.overtaking_vehicle_idx as usize),
+            in_drs_zone: self
+                .session_info
+                .as_ref()
+                .and_then(|session| self.options.drs_zones.contains(session.track.name(), lap_distance)),
+            pit_related: [ot.overtaking_vehicle_idx, ot.being_overtaken_vehicle_idx]
+                .iter()
+                .any(|&idx| self.lap_data.get(idx as usize).is_some_and(|lap| lap.pit_status != PitStatus::None)),
+            rematch_after_ms: previous_swap.map(|&(time, _)| session_time.saturating_sub(time)),
+            pair_swaps: previous_swap.map_or(0, |&(_, swaps)| swaps) + 1,
+            grip_advantage: self.session_info.as_ref().filter(|_| self.options.grip_advantage).and_then(|session| {
+                grip::advantage(
+                    (overtaker_status.visual_tyre_compound.name(), overtaker_status.tyre_age_laps),
+                    (overtakee_status.visual_tyre_compound.name(), overtakee_status.tyre_age_laps),
+                    session.track_temperature,
+                )
+            }),
+            overtaker_id: self.options.driver_ids.as_ref().map(|ids| ids.stable_id(overtaker)),
+            overtakee_id: self.options.driver_ids.as_ref().map(|ids| ids.stable_id(overtakee)),
+        })
+    }
+
+    /// The car running directly behind `car_idx`, ignoring `excluding`. The lap data may not reflect a
+    /// just completed overtake yet, so the overtaker is excluded rather than relying on its position.
+    fn car_behind(&self, car_idx: u8, excluding: u8) -> Option<usize> {
+        let position = self.lap_data.get(car_idx as usize)?.car_position;
+
+        self.lap_data
+            .iter()
+            .enumerate()
+            .filter(|&(i, lap)| i != excluding as usize && lap.car_position > position)
+            .min_by_key(|(_, lap)| lap.car_position)
+            .map(|(i, _)| i)
+    }
+
+    fn create_penalty_event(
+        &self,
+        penalty: &Penalty,
+        session_time: u32,
+    ) -> Result<PenaltyEventLog, Box<dyn std::error::Error>> {
+        let driver = self
+            .cars
+            .get(penalty.vehicle_idx as usize)
+            .ok_or_else(|| Box::<dyn std::error::Error>::from("Car data not found"))?;
+        let other_driver = self.cars.get(penalty.other_vehicle_idx as usize);
+        let applicable = |value: u8| Some(value).filter(|&value| value != NOT_APPLICABLE);
+
+        let mut involved = vec![penalty.vehicle_idx];
+        if other_driver.is_some() {
+            involved.push(penalty.other_vehicle_idx);
+        }
+
+        // Grid penalties carry the places dropped in the time field
+        let (time_secs, grid_places) = if matches!(penalty.penalty_type, PenaltyType::GridPenalty) {
+            (None, applicable(penalty.time))
+        } else {
+            (applicable(penalty.time), None)
+        };
+
+        Ok(PenaltyEventLog {
+            driver_name: teams::format_driver(driver, self.options.identify_by),
+            driver_team: teams::format_team(driver, self.game_year, self.options.identify_by),
+            penalty_type: format!("{:?}", penalty.penalty_type),
+            infringement_type: format!("{:?}", penalty.infringement_type),
+            other_driver_name: other_driver.map(|car| teams::format_driver(car, self.options.identify_by)),
+            time_secs,
+            lap: penalty.lap_num,
+            places_gained: applicable(penalty.places_gained),
+            grid_places,
+            session_time,
+            involves_player: self.involves_player(&involved),
+            // Before the lights out, also when the logger was started during the race and missed them
+            source: if self.race_start_time.is_none() && penalty.lap_num <= 1 { "Pre-Race" } else { "Race" },
+        })
+    }
+
+    fn create_collision_event(
+        &self,
+        collision: &Collision,
+        session_time: u32,
+    ) -> Result<CollisionEventLog, Box<dyn std::error::Error>> {
+        let get_car = |idx: u8| -> Result<&ParticipantData, Box<dyn std::error::Error>> {
+            self.cars.get(idx as usize).ok_or_else(|| Box::from("Car data not found"))
+        };
+
+        let driver_1 = get_car(collision.vehicle_1_idx)?;
+        let driver_2 = get_car(collision.vehicle_2_idx)?;
+        let lap = self
+            .lap_data
+            .get(collision.vehicle_1_idx as usize)
+            .ok_or_else(|| Box::<dyn std::error::Error>::from("Lap data not found"))?;
+
+        Ok(CollisionEventLog {
+            driver_1_name: teams::format_driver(driver_1, self.options.identify_by),
+            driver_1_team: teams::format_team(driver_1, self.game_year, self.options.identify_by),
+            driver_2_name: teams::format_driver(driver_2, self.options.identify_by),
+            driver_2_team: teams::format_team(driver_2, self.game_year, self.options.identify_by),
+            lap: lap.current_lap_num,
             track_position: lap.lap_distance as u16,
             time_secs: session_time,
+            involves_player: self.involves_player(&[collision.vehicle_1_idx, collision.vehicle_2_idx]),
+            overtake_time: self.recent_overtakes.find(collision.vehicle_1_idx, collision.vehicle_2_idx, session_time),
         })
     }
 
-    fn create_new_csv_writer(
-        &self,
-        session_data: &PacketSessionData,
+    /// A new file for an output written whole, e.g. at the end of the session, and flushed by the caller.
+    /// Unlike the category writers, which are reused while open and closed with the session, writing such an
+    /// output again in the same session replaces its file.
+    fn create_new_csv_writer(&mut self, event_type: &str, headers: &[&str]) -> io::Result<csv::Writer<fs::File>> {
+        Ok(self.create_csv_file(event_type, headers)?.1)
+    }
+
+    /// The new CSV file of the current session with the headers written, along with its path
+    fn create_csv_file(
+        &mut self,
         event_type: &str,
         headers: &[&str],
-    ) -> io::Result<csv::Writer<fs::File>> {
-        let filename = path::PathBuf::from(format!(
-            "{} {} {}_{}.csv",
-            session_data.track.name(),
-            session_data.session_type.name(),
-            event_type,
-            session_data.header.session_uid,
-        ));
-        println!("Writing {} to {:?}", event_type.to_lowercase(), &filename);
+    ) -> io::Result<(path::PathBuf, csv::Writer<fs::File>)> {
+        let (filename, writer) = self.output_files().create_csv(event_type, headers)?;
+        if !self.created_files.contains(&filename) {
+            self.created_files.push(filename.clone());
+        }
+        Ok((filename, writer))
+    }
 
-        let mut writer = csv::Writer::from_path(&filename)?;
-        writer.write_record(headers)?;
+    /// Saves rows that didn't reach their output file next to it, or in the temp directory if that fails too,
+    /// e.g. on a full disk
+    fn write_recovery_file(&self, event_type: &str, headers: &[&str], records: &[Vec<String>]) -> io::Result<()> {
+        if self.session_info.is_none() {
+            return Ok(());
+        }
+
+        let path = self.output_path(event_type, "csv.recovery");
+        let fallback = env::temp_dir().join(path.file_name().unwrap_or_default());
+        for candidate in [path, fallback] {
+            match write_csv_file(&candidate, headers, records) {
+                Ok(()) => {
+                    println!("ERROR: {} could not be written completely, saved them to {:?}", event_type, candidate);
+                    return Ok(());
+                },
+                Err(err) => println!("ERROR: Failed to write recovery file {:?}: {}", candidate, err),
+            }
+        }
+
+        Err(io::Error::new(io::ErrorKind::Other, format!("{} could not be saved anywhere", event_type)))
+    }
+
+    /// The category's writer, created with the given headers on first use. `None` while the category is
+    /// disabled.
+    fn writer(&mut self, category: LogCategory, headers: &[&str]) -> io::Result<Option<&mut dyn EventSink>> {
+        if !self.writers.is_enabled(category) {
+            return Ok(None);
+        }
+
+        if self.session_info.is_none() && self.writers.get(category).is_none() {
+            return Ok(None);
+        }
+
+        let files = OutputFiles {
+            options: &self.options,
+            names: &self.output_names,
+            session_uid: self.session_uid,
+            started_at: self.session_started_at,
+        };
+        let created_files = &mut self.created_files;
+        self.writers.get_or_create(category, || {
+            let (filename, sink) = files.create_sink(category.file_type(), headers)?;
+            created_files.push(filename);
+            Ok(Box::new(sink) as Box<dyn EventSink>)
+        })
+    }
+
+    fn create_sink(&mut self, event_type: &str, headers: &[&str]) -> io::Result<CsvSink> {
+        let (filename, sink) = self.output_files().create_sink(event_type, headers)?;
+        self.created_files.push(filename);
+        Ok(sink)
+    }
+
+    fn output_files(&self) -> OutputFiles<'_> {
+        OutputFiles {
+            options: &self.options,
+            names: &self.output_names,
+            session_uid: self.session_uid,
+            started_at: self.session_started_at,
+        }
+    }
 
-        Ok(writer)
+    /// Path of an output file of the current session
+    fn output_path(&self, event_type: &str, extension: &str) -> path::PathBuf {
+        self.output_files().path(event_type, extension)
     }
 
     fn write_overtake_event(&mut self, event: &OvertakeEventLog) -> io::Result<()> {
-        if let Some(writer) = self.csv_writer.as_mut() {
-            writer.write_record([
-                &event.overtaker_name,
-                &event.overtaker_team,
-                &event.overtaker_speed.to_string(),
-                &event.overtaker_tyre_compound,
-                &event.overtaker_tyre_age.to_string(),
-                &event.overtakee_name,
-                &event.overtakee_team,
-                &event.overtakee_speed.to_string(),
-                &event.overtakee_tyre_compound,
-                &event.overtakee_tyre_age.to_string(),
-                &event.for_pos.to_string(),
-                &event.lap.to_string(),
-                &event.track_position.to_string(),
-                &event.time_secs.to_string(),
-            ])?;
-            writer.flush()?;
+        // The headers are only needed to create the file, not for every row
+        let mut headers = Vec::new();
+        if self.writers.get_mut(LogCategory::Overtakes).is_none() {
+            headers.extend(OVERTAKE_CSV_HEADERS);
+            if self.options.grip_advantage {
+                headers.push("Grip Advantage [%]");
+            }
+            if self.options.driver_ids.is_some() {
+                headers.extend(["Overtaker ID", "Overtakee ID"]);
+            }
+        }
+
+        let grip_advantage = self.options.grip_advantage;
+        let mut row = std::mem::take(&mut self.row);
+        if let Some(writer) = self.writer(LogCategory::Overtakes, &headers)? {
+            row.clear()
+                .text(&event.overtaker_name)
+                .text(&event.overtaker_team)
+                .display(event.overtaker_speed)
+                .text(&event.overtaker_tyre_compound)
+                .display(event.overtaker_tyre_age)
+                .text(&event.overtakee_name)
+                .text(&event.overtakee_team)
+                .display(event.overtakee_speed)
+                .text(&event.overtakee_tyre_compound)
+                .display(event.overtakee_tyre_age)
+                .display(event.for_pos)
+                .display(event.lap)
+                .display(event.track_position)
+                .display(SessionSecs(event.session_time_ms))
+                .text(bool_label(event.involves_player))
+                .display_opt(event.collision_time.map(SessionSecs))
+                .display_opt(event.defense_duration_ms.map(Secs))
+                .display_opt(event.pressure_from_behind_ms.map(Secs))
+                .display_opt(event.overtaker_last_lap_ms)
+                .display_opt(event.overtakee_last_lap_ms)
+                .text(bool_label(Some(event.reversed)))
+                .display_opt(event.gap_ahead_ms.map(Secs))
+                .text(bool_label(event.in_drs_zone))
+                .display(&event.location_bin)
+                .display_opt(event.rematch_after_ms.map(Secs))
+                .display(event.pair_swaps)
+                .text(bool_label(Some(event.pit_related)))
+                // Whether the overtaker already ran within DRS range of the car a lap earlier, e.g. in a DRS
+                // train. The gap just before the pass is within range for nearly every overtake.
+                .text(bool_label(event.gap_lap_before_ms.map(|gap| gap <= DRS_RANGE_MS)))
+                .display(SessionClock(event.session_time_ms))
+                .text(bool_label(event.near_line));
+            // Blank rather than missing when unknown, so the IDs stay in their columns
+            if grip_advantage {
+                row.display_opt(event.grip_advantage.map(|advantage| format!("{:+.1}", advantage)));
+            }
+            for id in event.overtaker_id.iter().chain(&event.overtakee_id) {
+                row.text(id);
+            }
+            writer.write_row(row.record());
         }
+        self.row = row;
         Ok(())
     }
 }
+
+fn format_tyre_age(tyres: Option<&TyreSet>) -> String {
+    tyres.and_then(|tyres| tyres.age_laps).map(|age| age.to_string()).unwrap_or_default()
+}
+
+/// Fails when outputs lost rows on closing, which their sinks reported already
+fn incomplete_outputs(count: usize) -> io::Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+
+    Err(io::Error::new(io::ErrorKind::Other, format!("{} output files could not be written completely", count)))
+}
+
+fn write_csv_file(path: &path::Path, headers: &[&str], records: &[Vec<String>]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(headers)?;
+    for record in records {
+        writer.write_record(record)?;
+    }
+    writer.into_inner().map_err(|err| err.into_error())?.sync_all()
+}
+
+/// Syncs the contents of the files and, on Unix, their directory entries to disk
+fn sync_files(files: &[path::PathBuf]) -> io::Result<()> {
+    for file in files {
+        match fs::OpenOptions::new().append(true).open(file) {
+            Ok(handle) => handle.sync_all()?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => return Err(err),
+        }
+    }
+
+    // Windows can't open directories for syncing, but makes file creation durable along with the contents
+    #[cfg(unix)]
+    {
+        let mut directories: Vec<&path::Path> = Vec::new();
+        for file in files {
+            let directory = file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(path::Path::new("."));
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+        for directory in directories {
+            fs::File::open(directory)?.sync_all()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn format_optional_bool(value: Option<bool>) -> String {
+    bool_label(value).to_string()
+}
+
+fn bool_label(value: Option<bool>) -> &'static str {
+    match value {
+        Some(true) => "Yes",
+        Some(false) => "No",
+        None => "",
+    }
+}
+
+/// Formats a duration as `HH:MM:SS.mmm`, the format video editors accept for marker imports
+fn format_timecode(ms: u32) -> String {
+    let secs = ms / 1000;
+    format!("{:02}:{:02}:{:02}.{:03}", secs / 3600, secs / 60 % 60, secs % 60, ms % 1000)
+}
+
+fn format_session_secs(ms: u32) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+/// A session time displayed as `mm:ss.mmm`, the minutes continue past an hour
+struct SessionClock(u32);
+
+impl fmt::Display for SessionClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.0;
+        write!(f, "{:02}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+    }
+}
+
+fn format_laps(laps: u8) -> String {
+    match laps {
+        1 => "1 lap".to_string(),
+        laps => format!("{} laps", laps),
+    }
+}
+
+/// Key of two cars regardless of which overtook the other
+fn swap_pair(a: u8, b: u8) -> (u8, u8) {
+    (a.min(b), a.max(b))
+}
+
+/// A percentage displayed with one decimal
+struct Percent(f64);
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}", self.0)
+    }
+}
+
+/// A session time in milliseconds displayed as seconds with millisecond precision
+struct SessionSecs(u32);
+
+impl fmt::Display for SessionSecs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3}", self.0 as f64 / 1000.0)
+    }
+}
+
+/// Milliseconds displayed as seconds with one decimal
+struct Secs(u32);
+
+impl fmt::Display for Secs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}", self.0 as f32 / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Sink adding its category to a list shared with the test when it is closed
+    struct ClosingSink {
+        category: LogCategory,
+        closed: Rc<RefCell<Vec<LogCategory>>>,
+    }
+
+    impl EventSink for ClosingSink {
+        fn write_row(&mut self, _row: &csv::ByteRecord) {}
+
+        fn flush(&mut self) {}
+
+        fn close(self: Box<Self>, _fsync: bool) -> bool {
+            self.closed.borrow_mut().push(self.category);
+            true
+        }
+
+        fn is_quarantined(&self) -> bool {
+            false
+        }
+
+        fn written(&self) -> u64 {
+            0
+        }
+    }
+
+    fn create(
+        category: LogCategory,
+        closed: &Rc<RefCell<Vec<LogCategory>>>,
+    ) -> impl FnOnce() -> io::Result<Box<dyn EventSink>> {
+        let closed = Rc::clone(closed);
+        move || Ok(Box::new(ClosingSink { category, closed }) as Box<dyn EventSink>)
+    }
+
+    #[test]
+    fn creates_writers_of_enabled_categories_once() {
+        let closed = Rc::default();
+        let mut writers = WriterRegistry::default();
+        let disabled = writers.get_or_create(LogCategory::Overtakes, || panic!("disabled categories get no writer"));
+        assert!(disabled.unwrap().is_none());
+
+        writers.enable(LogCategory::Overtakes);
+        assert!(writers
+            .get_or_create(LogCategory::Overtakes, create(LogCategory::Overtakes, &closed))
+            .unwrap()
+            .is_some());
+        let existing = writers.get_or_create(LogCategory::Overtakes, || panic!("the writer is created once"));
+        assert!(existing.unwrap().is_some());
+    }
+
+    #[test]
+    fn rotation_closes_every_writer() {
+        let closed = Rc::default();
+        let mut writers = WriterRegistry::default();
+        let categories = [LogCategory::Overtakes, LogCategory::Collisions, LogCategory::Laps, LogCategory::Results];
+        for category in categories {
+            writers.enable(category);
+            writers.get_or_create(category, create(category, &closed)).unwrap();
+        }
+
+        for sink in writers.close_all() {
+            assert!(sink.close(false));
+        }
+        assert_eq!(*closed.borrow(), categories);
+
+        // Nothing is written until the next session enables the categories again, which then get new files
+        let rotated = writers.get_or_create(LogCategory::Overtakes, || panic!("disabled until the next session"));
+        assert!(rotated.unwrap().is_none());
+        writers.enable(LogCategory::Overtakes);
+        writers.get_or_create(LogCategory::Overtakes, create(LogCategory::Overtakes, &closed)).unwrap();
+        assert!(writers.get(LogCategory::Overtakes).is_some());
+    }
+
+    #[test]
+    fn disabling_closes_only_its_category() {
+        let closed = Rc::default();
+        let mut writers = WriterRegistry::default();
+        for category in [LogCategory::Overtakes, LogCategory::Laps] {
+            writers.enable(category);
+            writers.get_or_create(category, create(category, &closed)).unwrap();
+        }
+
+        let sink = writers.disable(LogCategory::Overtakes).expect("the writer was created");
+        assert!(sink.close(false));
+        assert_eq!(*closed.borrow(), [LogCategory::Overtakes]);
+        assert!(!writers.is_enabled(LogCategory::Overtakes));
+        assert!(writers.get(LogCategory::Laps).is_some());
+    }
+
+    #[test]
+    fn rolling_file_columns_are_lap_columns() {
+        let columns = columns::columns_by_header(&LAP_COLUMNS, &ROLLING_LAP_CSV_HEADERS);
+        let headers: Vec<&str> = columns.iter().map(|&i| LAP_COLUMNS[i].header).collect();
+        assert_eq!(headers, ROLLING_LAP_CSV_HEADERS);
+    }
+
+    #[test]
+    fn lap_rows_take_the_session_columns_from_the_context() {
+        let metadata = SessionMetadata {
+            session_uid: 42,
+            track: "Monza".to_string(),
+            ai_difficulty: Some(90),
+            network_game: Some(false),
+            ..Default::default()
+        };
+        let lap = LapSnapshot {
+            lap: 3,
+            session_lap: 3,
+            start_position: 5,
+            end_position: 4,
+            session_time: 300_000,
+            lap_time_ms: 91_234,
+            sectors_ms: None,
+            phases: crate::phases::LapPhases::default(),
+            clean: true,
+        };
+        let start_tyres = (TyreSet { compound: "Soft".to_string(), age_laps: Some(2) }, 2);
+        let car = ParticipantData::default();
+        let keys = ["lap", "lap_time", "session_uid", "track", "ai_difficulty", "network_game", "compound", "stint"];
+
+        let fields = |metadata: Option<&SessionMetadata>| {
+            let context = SessionContext { lap_data: &[], car_status: &[], cars: &[], game_year: 2023, metadata };
+            let lap_row =
+                LapRow { context: &context, car: &car, lap: &lap, run: None, start_tyres: Some(&start_tyres) };
+            let mut row = RowBuffer::default();
+            for key in keys {
+                lap_row.write_column(key, IdentifyBy::Name, &mut row);
+            }
+            row.record().iter().map(|field| String::from_utf8_lossy(field).into_owned()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(fields(Some(&metadata)), ["3", "91234", "42", "Monza", "90", "No", "Soft", "2"]);
+        assert_eq!(fields(None), ["3", "91234", "", "", "", "", "Soft", "2"]);
+    }
+}
@@ -0,0 +1,109 @@
+use f1_telemetry::packet::session::{PacketSessionData, RuleSet, SessionType};
+
+/// Kind of a session type, covering the sprint variants newer games added next to the numbered sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SessionKind {
+    Practice,
+    Qualifying,
+    /// Sprint shootout or sprint qualifying, run with the qualifying rules
+    SprintShootout,
+    /// Races, including the sprint race
+    Race,
+    TimeTrial,
+    Unknown,
+}
+
+impl SessionKind {
+    pub(crate) fn of(session_type: &SessionType) -> Self {
+        match session_type {
+            SessionType::Practice1 | SessionType::Practice2 | SessionType::Practice3 | SessionType::PracticeShort => {
+                Self::Practice
+            },
+            SessionType::Qualifying1
+            | SessionType::Qualifying2
+            | SessionType::Qualifying3
+            | SessionType::QualifyingShort
+            | SessionType::OneShotQualifying => Self::Qualifying,
+            SessionType::SprintShootout1
+            | SessionType::SprintShootout2
+            | SessionType::SprintShootout3
+            | SessionType::SprintShootoutShort
+            | SessionType::OneShotSprintShootout => Self::SprintShootout,
+            SessionType::Race | SessionType::Race2 | SessionType::Race3 => Self::Race,
+            SessionType::TimeTrial => Self::TimeTrial,
+            SessionType::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// Readable name of the session type for filenames and reports, e.g. "Sprint Shootout 1" or "Race 2"
+pub(crate) fn session_name(session_type: &SessionType) -> &'static str {
+    match session_type {
+        SessionType::Practice1 => "Practice 1",
+        SessionType::Practice2 => "Practice 2",
+        SessionType::Practice3 => "Practice 3",
+        SessionType::PracticeShort => "Short Practice",
+        SessionType::Qualifying1 => "Qualifying 1",
+        SessionType::Qualifying2 => "Qualifying 2",
+        SessionType::Qualifying3 => "Qualifying 3",
+        SessionType::QualifyingShort => "Short Qualifying",
+        SessionType::OneShotQualifying => "One-Shot Qualifying",
+        SessionType::SprintShootout1 => "Sprint Shootout 1",
+        SessionType::SprintShootout2 => "Sprint Shootout 2",
+        SessionType::SprintShootout3 => "Sprint Shootout 3",
+        SessionType::SprintShootoutShort => "Short Sprint Shootout",
+        SessionType::OneShotSprintShootout => "One-Shot Sprint Shootout",
+        SessionType::Race => "Race",
+        SessionType::Race2 => "Race 2",
+        SessionType::Race3 => "Race 3",
+        SessionType::TimeTrial => "Time Trial",
+        SessionType::Unknown => "Unknown",
+    }
+}
+
+/// Whether events are logged for the session: races by their rule set, or by their session type for games
+/// sending a rule set the telemetry crate doesn't know. Sprint shootouts use the qualifying rules.
+pub(crate) fn is_race(session_data: &PacketSessionData) -> bool {
+    match session_data.rule_set {
+        Some(rule_set) => rule_set == RuleSet::Race,
+        None => SessionKind::of(&session_data.session_type) == SessionKind::Race,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sprint_shootouts_are_their_own_kind() {
+        for session_type in [
+            SessionType::SprintShootout1,
+            SessionType::SprintShootout2,
+            SessionType::SprintShootout3,
+            SessionType::SprintShootoutShort,
+            SessionType::OneShotSprintShootout,
+        ] {
+            assert_eq!(SessionKind::of(&session_type), SessionKind::SprintShootout, "{:?}", session_type);
+        }
+    }
+
+    #[test]
+    fn kinds_of_the_numbered_sessions() {
+        assert_eq!(SessionKind::of(&SessionType::Practice2), SessionKind::Practice);
+        assert_eq!(SessionKind::of(&SessionType::PracticeShort), SessionKind::Practice);
+        assert_eq!(SessionKind::of(&SessionType::Qualifying3), SessionKind::Qualifying);
+        assert_eq!(SessionKind::of(&SessionType::OneShotQualifying), SessionKind::Qualifying);
+        assert_eq!(SessionKind::of(&SessionType::Race), SessionKind::Race);
+        assert_eq!(SessionKind::of(&SessionType::Race2), SessionKind::Race);
+        assert_eq!(SessionKind::of(&SessionType::TimeTrial), SessionKind::TimeTrial);
+        assert_eq!(SessionKind::of(&SessionType::Unknown), SessionKind::Unknown);
+    }
+
+    #[test]
+    fn names_session_types() {
+        assert_eq!(session_name(&SessionType::SprintShootout1), "Sprint Shootout 1");
+        assert_eq!(session_name(&SessionType::OneShotSprintShootout), "One-Shot Sprint Shootout");
+        assert_eq!(session_name(&SessionType::QualifyingShort), "Short Qualifying");
+        assert_eq!(session_name(&SessionType::Race2), "Race 2");
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt::{self, Write};
+use std::io::{Read, Seek};
+use std::{fs, io, path, thread, time};
+
+/// Consecutive failures after which a sink is quarantined
+const QUARANTINE_AFTER_FAILURES: u32 = 5;
+
+/// Time after which a quarantined sink is tried again
+const QUARANTINE_RETRY_INTERVAL: time::Duration = time::Duration::from_secs(30);
+
+/// Attempts of the final flush of a sink before its data is given up
+const FINAL_FLUSH_ATTEMPTS: u32 = 3;
+
+/// Pause between two attempts of the final flush
+const FINAL_FLUSH_RETRY_DELAY: time::Duration = time::Duration::from_millis(200);
+
+/// The last record written to a sink, encoded like in the file, to read it back after flushing
+struct ReadBack {
+    path: path::PathBuf,
+    last_record: Option<Vec<u8>>,
+    mismatches: u64,
+}
+
+/// A CSV output whose errors are counted and logged instead of aborting the packet handling, so a failing
+/// output, e.g. on a full disk, doesn't stop the others. Persistently failing sinks are quarantined and
+/// retried periodically. Sinks are closed explicitly, see [`CsvSink::close`].
+pub(crate) struct CsvSink {
+    name: String,
+    writer: csv::Writer<fs::File>,
+    written: u64,
+    failed: u64,
+    consecutive_failures: u32,
+    quarantines: u32,
+    quarantined_since: Option<time::Instant>,
+    read_back: Option<ReadBack>,
+    closed: bool,
+}
+
+impl CsvSink {
+    pub(crate) fn new(name: &str, writer: csv::Writer<fs::File>) -> Self {
+        Self {
+            name: name.to_string(),
+            writer,
+            written: 0,
+            failed: 0,
+            consecutive_failures: 0,
+            quarantines: 0,
+            quarantined_since: None,
+            read_back: None,
+            closed: false,
+        }
+    }
+
+    /// Reads the last record back from the file at `path` after every flush and warns when it differs from
+    /// the record written, which points at failing storage
+    pub(crate) fn verify_after_write(mut self, path: path::PathBuf) -> Self {
+        self.read_back = Some(ReadBack { path, last_record: None, mismatches: 0 });
+        self
+    }
+
+    pub(crate) fn write_record<I, T>(&mut self, record: I)
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        // Rows dropped while quarantined are lost like failed ones, so closing reports the output incomplete
+        if self.is_skipping() {
+            self.failed += 1;
+            return;
+        }
+
+        let result = match self.read_back.as_mut() {
+            Some(read_back) => {
+                let record: Vec<T> = record.into_iter().collect();
+                let result = self.writer.write_record(&record);
+                if result.is_ok() {
+                    read_back.last_record = encode_record(&record);
+                }
+                result
+            },
+            None => self.writer.write_record(record),
+        };
+        if self.track(result.map_err(Into::into)) {
+            self.written += 1;
+        }
+    }
+
+    pub(crate) fn flush(&mut self) {
+        if !self.is_skipping() {
+            let result = self.writer.flush();
+            if self.track(result) {
+                self.verify_last_record();
+            }
+        }
+    }
+
+    /// Flushes the sink a last time, syncing the file to disk with `fsync`, and prints its report. Returns
+    /// whether all rows reached the file, so callers holding the data can save it elsewhere.
+    #[must_use = "rows lost when closing are only reported on the console otherwise"]
+    pub(crate) fn close(mut self, fsync: bool) -> bool {
+        let mut complete = self.finish();
+        if fsync && complete {
+            if let Err(err) = self.writer.get_ref().sync_all() {
+                println!("ERROR: Output {} could not be synced to disk: {}", self.name, err);
+                complete = false;
+            }
+        }
+
+        self.closed = true;
+        println!("{}", self.report());
+        complete
+    }
+
+    /// Flushes the sink a last time before it is closed, retrying failures and ignoring the quarantine. Returns
+    /// false when any row was lost, in this flush or before.
+    fn finish(&mut self) -> bool {
+        for attempt in 1..=FINAL_FLUSH_ATTEMPTS {
+            let result = self.writer.flush();
+            if self.track(result) {
+                self.verify_last_record();
+                break;
+            }
+            if attempt == FINAL_FLUSH_ATTEMPTS {
+                println!(
+                    "ERROR: Output {} could not be flushed after {} attempts, its last rows are lost",
+                    self.name, FINAL_FLUSH_ATTEMPTS
+                );
+                return false;
+            }
+            thread::sleep(FINAL_FLUSH_RETRY_DELAY);
+        }
+
+        self.failed == 0
+    }
+
+    pub(crate) fn is_quarantined(&self) -> bool {
+        self.quarantined_since.is_some()
+    }
+
+    pub(crate) fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Console summary of the rows written and failures of this sink
+    pub(crate) fn report(&self) -> String {
+        let report = format!(
+            "{}: {} rows written, {} failed, quarantined {} times",
+            self.name, self.written, self.failed, self.quarantines
+        );
+        match self.read_back.as_ref() {
+            Some(read_back) => format!("{}, {} read back differently", report, read_back.mismatches),
+            None => report,
+        }
+    }
+
+    /// Compares the end of the file with the last record written since the previous check
+    fn verify_last_record(&mut self) {
+        let Some(read_back) = self.read_back.as_mut() else {
+            return;
+        };
+        let Some(expected) = read_back.last_record.take() else {
+            return;
+        };
+
+        match read_tail(&read_back.path, expected.len()) {
+            Ok(actual) if actual == expected => {},
+            Ok(_) => {
+                read_back.mismatches += 1;
+                println!(
+                    "WARNING: The last row of {} read back from {:?} differs from the row written, check the storage",
+                    self.name, read_back.path
+                );
+            },
+            Err(err) => {
+                println!(
+                    "WARNING: Failed to read back the last row of {} from {:?}: {}",
+                    self.name, read_back.path, err
+                )
+            },
+        }
+    }
+
+    /// Whether a quarantined sink should still be skipped, i.e. is not due for a retry
+    fn is_skipping(&self) -> bool {
+        self.quarantined_since.is_some_and(|since| since.elapsed() < QUARANTINE_RETRY_INTERVAL)
+    }
+
+    /// Counts the outcome of an operation and returns whether it succeeded
+    fn track(&mut self, result: io::Result<()>) -> bool {
+        match result {
+            Ok(()) => {
+                if self.quarantined_since.take().is_some() {
+                    println!("Output {} recovered", self.name);
+                }
+                self.consecutive_failures = 0;
+                true
+            },
+            Err(err) => {
+                self.failed += 1;
+                self.consecutive_failures += 1;
+                println!("Failed to write {}: {}", self.name, err);
+
+                if self.quarantined_since.is_some() {
+                    // A failed retry restarts the quarantine
+                    self.quarantined_since = Some(time::Instant::now());
+                } else if self.consecutive_failures >= QUARANTINE_AFTER_FAILURES {
+                    println!(
+                        "Output {} failed {} times in a row, pausing it for {} s",
+                        self.name,
+                        self.consecutive_failures,
+                        QUARANTINE_RETRY_INTERVAL.as_secs()
+                    );
+                    self.quarantines += 1;
+                    self.quarantined_since = Some(time::Instant::now());
+                }
+                false
+            },
+        }
+    }
+}
+
+/// An output the session writes rows to, as kept by the session's writer registry. Rows are passed encoded so
+/// that sinks can be kept as `dyn EventSink`, whose `write_record` takes the fields like
+/// [`CsvSink::write_record`].
+pub(crate) trait EventSink {
+    fn write_row(&mut self, row: &csv::ByteRecord);
+    fn flush(&mut self);
+    /// Flushes and closes the output, returning whether all rows reached it, see [`CsvSink::close`]
+    #[must_use = "rows lost when closing are only reported on the console otherwise"]
+    fn close(self: Box<Self>, fsync: bool) -> bool;
+    fn is_quarantined(&self) -> bool;
+    fn written(&self) -> u64;
+}
+
+impl dyn EventSink + '_ {
+    pub(crate) fn write_record<I, T>(&mut self, record: I)
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let mut row = csv::ByteRecord::new();
+        for field in record {
+            row.push_field(field.as_ref());
+        }
+        self.write_row(&row);
+    }
+}
+
+impl EventSink for CsvSink {
+    fn write_row(&mut self, row: &csv::ByteRecord) {
+        CsvSink::write_record(self, row);
+    }
+
+    fn flush(&mut self) {
+        CsvSink::flush(self);
+    }
+
+    fn close(self: Box<Self>, fsync: bool) -> bool {
+        CsvSink::close(*self, fsync)
+    }
+
+    fn is_quarantined(&self) -> bool {
+        CsvSink::is_quarantined(self)
+    }
+
+    fn written(&self) -> u64 {
+        CsvSink::written(self)
+    }
+}
+
+impl Drop for CsvSink {
+    /// The CSV writer still flushes when dropped, but ignores any error, so the last rows may be lost unnoticed
+    fn drop(&mut self) {
+        if !self.closed {
+            println!("WARNING: Output {} was not closed, errors writing its last rows go unreported", self.name);
+        }
+    }
+}
+
+/// A row built field by field into reused buffers, so writing a row doesn't allocate a string per field
+#[derive(Debug, Default)]
+pub(crate) struct RowBuffer {
+    record: csv::ByteRecord,
+    scratch: String,
+}
+
+impl RowBuffer {
+    pub(crate) fn clear(&mut self) -> &mut Self {
+        self.record.clear();
+        self
+    }
+
+    pub(crate) fn text(&mut self, value: &str) -> &mut Self {
+        self.record.push_field(value.as_bytes());
+        self
+    }
+
+    pub(crate) fn display(&mut self, value: impl fmt::Display) -> &mut Self {
+        self.scratch.clear();
+        // Writing into a string can't fail
+        let _ = write!(self.scratch, "{}", value);
+        self.record.push_field(self.scratch.as_bytes());
+        self
+    }
+
+    /// An empty field for `None`
+    pub(crate) fn display_opt(&mut self, value: Option<impl fmt::Display>) -> &mut Self {
+        match value {
+            Some(value) => self.display(value),
+            None => self.text(""),
+        }
+    }
+
+    pub(crate) fn record(&self) -> &csv::ByteRecord {
+        &self.record
+    }
+}
+
+/// The record as the CSV writer writes it to the file, including the line terminator
+fn encode_record<I, T>(record: I) -> Option<Vec<u8>>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record).ok()?;
+    writer.into_inner().ok()
+}
+
+/// The last `len` bytes of the file, read through a new handle so the writer's position is left alone
+fn read_tail(path: &path::Path, len: usize) -> io::Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    file.seek(io::SeekFrom::End(-(len as i64)))?;
+    let mut tail = vec![0; len];
+    file.read_exact(&mut tail)?;
+    Ok(tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sink over /dev/full, which accepts opening but fails every write with "no space left on device"
+    #[cfg(target_os = "linux")]
+    fn full_device_sink() -> CsvSink {
+        let file = fs::OpenOptions::new().write(true).open("/dev/full").expect("/dev/full should open");
+        CsvSink::new("Full", csv::Writer::from_writer(file))
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn close_reports_failed_flush() {
+        let mut sink = full_device_sink();
+        // Buffered by the CSV writer, the device is only written to when the sink is closed
+        sink.write_record(["Driver", "Lap"]);
+        assert_eq!(sink.written(), 1);
+
+        assert!(!sink.close(false));
+    }
+
+    #[test]
+    fn close_reports_rows_skipped_in_quarantine() {
+        let path = std::env::temp_dir().join(format!("f1-eventlogger-quarantine-{}.csv", std::process::id()));
+        let mut sink = CsvSink::new("Events", csv::Writer::from_path(&path).expect("temp file should be created"));
+        sink.write_record(["Driver", "Lap"]);
+        sink.quarantined_since = Some(time::Instant::now());
+        sink.write_record(["Hamilton", "1"]);
+        assert_eq!(sink.written(), 1);
+
+        // The file itself flushes fine, but the skipped row is lost
+        assert!(!sink.close(false));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Driver,Lap\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn close_reports_complete_output() {
+        let path = std::env::temp_dir().join(format!("f1-eventlogger-sink-{}.csv", std::process::id()));
+        let mut sink = CsvSink::new("Laps", csv::Writer::from_path(&path).expect("temp file should be created"));
+        sink.write_record(["Driver", "Lap"]);
+
+        assert!(sink.close(true));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Driver,Lap\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::timeline::TimelineEntry;
+
+/// Prefix of all metric names
+const METRIC_PREFIX: &str = "f1";
+
+/// Resolves the StatsD endpoint given as "host:port", e.g. "localhost:8125"
+pub(crate) fn resolve(addr: &str) -> Result<SocketAddr, String> {
+    addr.to_socket_addrs()
+        .map_err(|err| format!("Invalid StatsD address '{}': {}", addr, err))?
+        .next()
+        .ok_or_else(|| format!("StatsD address '{}' didn't resolve", addr))
+}
+
+/// Lowercase metric name segment with anything but letters and digits replaced, e.g. "safety_car"
+fn segment(name: &str) -> String {
+    name.trim().chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' }).collect()
+}
+
+/// Pushes counters and timers to a StatsD endpoint over UDP: `f1.events.<type>` counts every event,
+/// `f1.overtakes.<driver>` the overtakes by driver and `f1.lap_time.<driver>` times the completed laps.
+/// Datagrams are sent without waiting, and lost while the endpoint is unreachable.
+pub(crate) struct StatsdSink {
+    socket: UdpSocket,
+    /// Whether sending failed since the last datagram went out, to report an unreachable endpoint once
+    unreachable: bool,
+}
+
+impl StatsdSink {
+    pub(crate) fn connect(addr: SocketAddr) -> Result<Self, String> {
+        let local: SocketAddr = if addr.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(addr).map(|()| socket))
+            .and_then(|socket| socket.set_nonblocking(true).map(|()| socket))
+            .map_err(|err| format!("Failed to set up StatsD metrics to {}: {}", addr, err))?;
+        println!("Pushing metrics to StatsD at {}", addr);
+
+        Ok(Self { socket, unreachable: false })
+    }
+
+    pub(crate) fn record_event(&mut self, entry: &TimelineEntry) {
+        self.send(&format!("{}.events.{}:1|c", METRIC_PREFIX, segment(entry.kind)));
+    }
+
+    pub(crate) fn record_overtake(&mut self, driver: &str) {
+        self.send(&format!("{}.overtakes.{}:1|c", METRIC_PREFIX, segment(driver)));
+    }
+
+    pub(crate) fn record_lap_time(&mut self, driver: &str, lap_time_ms: u32) {
+        self.send(&format!("{}.lap_time.{}:{}|ms", METRIC_PREFIX, segment(driver), lap_time_ms));
+    }
+
+    fn send(&mut self, metric: &str) {
+        match self.socket.send(metric.as_bytes()) {
+            Ok(_) => self.unreachable = false,
+            // The metric is dropped when the socket buffer is full
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {},
+            // An unreachable endpoint is reported by a later send after an earlier datagram was refused
+            Err(err) => {
+                if !self.unreachable {
+                    println!("Failed to push metrics to StatsD: {}", err);
+                }
+                self.unreachable = true;
+            },
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use f1_telemetry::packet::header::PacketHeader;
+use std::time;
+
+/// Packet ids and names of the packet types counted for the status line
+const STATUS_PACKET_TYPES: [(u8, &str); 7] =
+    [(0, "motion"), (1, "session"), (2, "lap"), (3, "event"), (4, "participants"), (6, "telemetry"), (7, "status")];
+
+/// Packet counts since the last status line, for a heartbeat showing the logger still receives data
+#[derive(Debug)]
+pub(crate) struct PacketCounters {
+    counts: [u64; STATUS_PACKET_TYPES.len()],
+    since: time::Instant,
+    last_packet: Option<time::Instant>,
+}
+
+impl Default for PacketCounters {
+    fn default() -> Self {
+        Self { counts: [0; STATUS_PACKET_TYPES.len()], since: time::Instant::now(), last_packet: None }
+    }
+}
+
+impl PacketCounters {
+    pub(crate) fn record(&mut self, header: &PacketHeader) {
+        self.last_packet = Some(time::Instant::now());
+        if let Some(i) = STATUS_PACKET_TYPES.iter().position(|&(id, _)| id == header.packet_id) {
+            self.counts[i] += 1;
+        }
+    }
+
+    /// Packets per second of each type since the previous call, then the time since the last packet.
+    /// Restarts the counts.
+    pub(crate) fn take_status(&mut self) -> String {
+        let elapsed = self.since.elapsed().as_secs_f64().max(f64::EPSILON);
+        let rates: Vec<String> = STATUS_PACKET_TYPES
+            .iter()
+            .zip(self.counts)
+            .map(|(&(_, name), count)| format!("{} {:.1}/s", name, count as f64 / elapsed))
+            .collect();
+        let last_packet = match self.last_packet {
+            Some(last) => format!("last packet {:.1} s ago", last.elapsed().as_secs_f64()),
+            None => "no packets yet".to_string(),
+        };
+
+        self.counts = [0; STATUS_PACKET_TYPES.len()];
+        self.since = time::Instant::now();
+        format!("{} | {}", rates.join(", "), last_packet)
+    }
+}
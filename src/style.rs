@@ -0,0 +1,191 @@
+use f1_telemetry::packet::car_telemetry::CarTelemetryData;
+use f1_telemetry::packet::participants::{ParticipantData, Telemetry};
+
+/// Brake application counted as hard braking
+const HARD_BRAKING: f32 = 0.9;
+
+/// Throttle counted as full throttle, short of 1.0 as some pedals never quite reach it
+const FULL_THROTTLE: f32 = 0.99;
+
+/// Gap between two telemetry samples beyond which a lap's counts are flagged as unreliable
+const MAX_SAMPLE_GAP_MS: u32 = 1000;
+
+/// Driving style of a car over one lap, from the telemetry samples received during it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct LapStyle {
+    pub(crate) gear_changes: u32,
+    /// Brake applications going beyond [`HARD_BRAKING`]
+    pub(crate) hard_brakings: u32,
+    pub(crate) full_throttle_percent: f64,
+    /// Whether no gap between the samples exceeded [`MAX_SAMPLE_GAP_MS`], e.g. because of packet loss or a pause
+    pub(crate) reliable: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct StyleCounter {
+    samples: u32,
+    full_throttle_samples: u32,
+    gear_changes: u32,
+    hard_brakings: u32,
+    max_gap_ms: u32,
+    last_gear: Option<i8>,
+    braking_hard: bool,
+    /// Kept across laps, so a gap at the line counts against the lap it ends in
+    last_sample_time: Option<u32>,
+}
+
+/// Running counters of every car's gear changes, hard braking and full throttle, reset at the lap boundary
+#[derive(Debug, Default)]
+pub(crate) struct StyleTracker {
+    cars: Vec<StyleCounter>,
+}
+
+impl StyleTracker {
+    pub(crate) fn clear(&mut self) {
+        self.cars.clear();
+    }
+
+    /// Counts a telemetry sample of every car. Cars with restricted telemetry are sent without any inputs, so
+    /// they are told by their participant's setting, and unused slots by having no participant.
+    pub(crate) fn record(
+        &mut self,
+        telemetry: &[CarTelemetryData],
+        participants: &[ParticipantData],
+        session_time: u32,
+    ) {
+        if self.cars.len() < telemetry.len() {
+            self.cars.resize_with(telemetry.len(), StyleCounter::default);
+        }
+
+        for (i, (counter, car)) in self.cars.iter_mut().zip(telemetry).enumerate() {
+            let public = participants
+                .get(i)
+                .is_some_and(|participant| !matches!(participant.telemetry_access, Telemetry::Restricted));
+            if !public {
+                continue;
+            }
+
+            if let Some(last) = counter.last_sample_time {
+                // A flashback moves the session time back, which isn't a gap in the data
+                counter.max_gap_ms = counter.max_gap_ms.max(session_time.saturating_sub(last));
+            }
+            counter.last_sample_time = Some(session_time);
+
+            counter.samples += 1;
+            if car.throttle >= FULL_THROTTLE {
+                counter.full_throttle_samples += 1;
+            }
+            if counter.last_gear.is_some_and(|gear| gear != car.gear) {
+                counter.gear_changes += 1;
+            }
+            counter.last_gear = Some(car.gear);
+
+            let braking_hard = car.brake > HARD_BRAKING;
+            if braking_hard && !counter.braking_hard {
+                counter.hard_brakings += 1;
+            }
+            counter.braking_hard = braking_hard;
+        }
+    }
+
+    /// Takes the counts of the lap the car just completed, `None` without any telemetry during it
+    pub(crate) fn finish_lap(&mut self, car_idx: usize) -> Option<LapStyle> {
+        let counter = self.cars.get_mut(car_idx)?;
+        let style = (counter.samples > 0).then(|| LapStyle {
+            gear_changes: counter.gear_changes,
+            hard_brakings: counter.hard_brakings,
+            full_throttle_percent: counter.full_throttle_samples as f64 * 100.0 / counter.samples as f64,
+            reliable: counter.max_gap_ms <= MAX_SAMPLE_GAP_MS,
+        });
+
+        // The gear and brake state carry over, a change right at the line counts for the next lap
+        *counter = StyleCounter {
+            last_gear: counter.last_gear,
+            braking_hard: counter.braking_hard,
+            last_sample_time: counter.last_sample_time,
+            ..StyleCounter::default()
+        };
+        style
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(gear: i8, throttle: f32, brake: f32) -> CarTelemetryData {
+        CarTelemetryData { speed: 200, gear, throttle, brake, ..Default::default() }
+    }
+
+    fn participant(telemetry_access: Telemetry) -> ParticipantData {
+        ParticipantData { telemetry_access, ..Default::default() }
+    }
+
+    /// Records the samples of a single car with public telemetry, 100 ms apart from `start_time`
+    fn record(tracker: &mut StyleTracker, samples: &[CarTelemetryData], start_time: u32) {
+        let participants = [participant(Telemetry::Public)];
+        for (i, sample) in samples.iter().enumerate() {
+            tracker.record(std::slice::from_ref(sample), &participants, start_time + i as u32 * 100);
+        }
+    }
+
+    #[test]
+    fn counts_gear_changes_hard_braking_and_full_throttle() {
+        let mut tracker = StyleTracker::default();
+        record(
+            &mut tracker,
+            &[
+                sample(6, 1.0, 0.0),
+                sample(7, 1.0, 0.0),
+                sample(7, 0.0, 1.0),
+                sample(5, 0.0, 0.95),
+                sample(4, 0.5, 0.0),
+                sample(4, 0.0, 1.0),
+            ],
+            1000,
+        );
+
+        let style = tracker.finish_lap(0).unwrap();
+        assert_eq!(style.gear_changes, 3);
+        assert_eq!(style.hard_brakings, 2);
+        assert!((style.full_throttle_percent - 100.0 / 3.0).abs() < 1e-9);
+        assert!(style.reliable);
+    }
+
+    #[test]
+    fn carries_the_gear_and_brake_over_the_line() {
+        let mut tracker = StyleTracker::default();
+        record(&mut tracker, &[sample(7, 0.0, 0.0), sample(7, 0.0, 1.0)], 1000);
+        assert_eq!(tracker.finish_lap(0).map(|style| style.hard_brakings), Some(1));
+
+        // Still braking from the last lap, then a downshift right after the line
+        record(&mut tracker, &[sample(7, 0.0, 1.0), sample(6, 0.0, 0.0)], 1200);
+        let style = tracker.finish_lap(0).unwrap();
+        assert_eq!((style.gear_changes, style.hard_brakings), (1, 0));
+        assert_eq!(style.full_throttle_percent, 0.0);
+    }
+
+    #[test]
+    fn flags_gaps_in_the_telemetry() {
+        let mut tracker = StyleTracker::default();
+        record(&mut tracker, &[sample(7, 1.0, 0.0)], 1000);
+        record(&mut tracker, &[sample(7, 1.0, 0.0)], 2500);
+        assert_eq!(tracker.finish_lap(0).map(|style| style.reliable), Some(false));
+
+        // The gap is measured across the line, but not held against the following laps
+        record(&mut tracker, &[sample(7, 1.0, 0.0)], 2600);
+        assert_eq!(tracker.finish_lap(0).map(|style| style.reliable), Some(true));
+    }
+
+    #[test]
+    fn skips_restricted_telemetry_and_unused_slots() {
+        let mut tracker = StyleTracker::default();
+        let participants = [participant(Telemetry::Restricted), participant(Telemetry::Public)];
+        let samples = [sample(7, 1.0, 0.0), sample(7, 1.0, 0.0), sample(7, 1.0, 0.0)];
+        tracker.record(&samples, &participants, 1000);
+
+        assert_eq!(tracker.finish_lap(0), None);
+        assert!(tracker.finish_lap(1).is_some());
+        assert_eq!(tracker.finish_lap(2), None);
+    }
+}
@@ -0,0 +1,34 @@
+use std::path;
+
+/// A titled table in the post-race summary file
+pub(crate) struct SummarySection {
+    title: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl SummarySection {
+    pub(crate) fn new(title: &'static str, headers: &'static [&'static str]) -> Self {
+        Self { title, headers, rows: Vec::new() }
+    }
+
+    pub(crate) fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+/// Writes all sections into a single CSV file, each introduced by its title and header row
+pub(crate) fn write_summary(path: &path::Path, sections: &[SummarySection]) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_path(path)?;
+
+    for section in sections {
+        writer.write_record([section.title])?;
+        writer.write_record(section.headers)?;
+        for row in &section.rows {
+            writer.write_record(row)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
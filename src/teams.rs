@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::{fmt, path};
+
+use f1_telemetry::packet::participants::{ParticipantData, Team};
+
+/// How drivers are identified in the outputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum IdentifyBy {
+    Name,
+    Number,
+    /// The driver's name, with the race number next to the team
+    #[default]
+    Both,
+}
+
+impl FromStr for IdentifyBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "name" => Ok(Self::Name),
+            "number" => Ok(Self::Number),
+            "both" => Ok(Self::Both),
+            other => Err(format!("Invalid driver identification '{}', expected name, number or both", other)),
+        }
+    }
+}
+
+/// Name given to active participants the game sends without one, as happens in some online lobbies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum EmptyNames {
+    /// Kept empty, leaving the driver blank in the outputs
+    Blank,
+    /// The car index, e.g. "Car 5"
+    #[default]
+    CarIndex,
+    /// The race number, e.g. "#44"
+    RaceNumber,
+}
+
+impl EmptyNames {
+    /// Fills in the names of the active participants sent without one, returning how many were filled in.
+    /// Unused slots beyond the active cars keep their empty names, which tell them apart.
+    pub(crate) fn fill(&self, cars: &mut [ParticipantData], num_active_cars: usize) -> usize {
+        let mut filled = 0;
+        for (i, car) in cars.iter_mut().enumerate().take(num_active_cars) {
+            if !car.name.trim().is_empty() {
+                continue;
+            }
+            car.name = match self {
+                EmptyNames::Blank => continue,
+                EmptyNames::CarIndex => format!("Car {}", i),
+                EmptyNames::RaceNumber => format!("#{}", car.race_number),
+            };
+            filled += 1;
+        }
+        filled
+    }
+}
+
+impl FromStr for EmptyNames {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "blank" => Ok(Self::Blank),
+            "car" => Ok(Self::CarIndex),
+            "number" => Ok(Self::RaceNumber),
+            other => Err(format!("Invalid empty name handling '{}', expected blank, car or number", other)),
+        }
+    }
+}
+
+/// Driver ids that stay the same across sessions, for joining the outputs of several sessions, e.g. of a
+/// league season. Online names can change and collide, so a mapping from names to ids can be given by the
+/// user as a CSV with the columns "Name" and "ID".
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct DriverIds {
+    by_name: HashMap<String, String>,
+}
+
+impl DriverIds {
+    pub(crate) fn from_file(path: &path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut by_name = HashMap::new();
+        for record in reader.records() {
+            let record = record?;
+            let (Some(name), Some(id)) = (record.get(0), record.get(1)) else {
+                continue;
+            };
+            by_name.insert(name.trim().to_string(), id.trim().to_string());
+        }
+
+        Ok(Self { by_name })
+    }
+
+    /// The mapped id of the driver's name, the game's driver for AI cars or else the name itself. The
+    /// telemetry doesn't carry platform account ids, and network ids only hold within one session.
+    pub(crate) fn stable_id(&self, car: &ParticipantData) -> String {
+        if let Some(id) = self.by_name.get(&car.name) {
+            return id.clone();
+        }
+        if car.ai_controlled {
+            return format!("AI {:?}", car.driver);
+        }
+        car.name.clone()
+    }
+}
+
+/// Car to follow, given as car index (e.g. "3") or race number (e.g. "#44")
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FollowCar {
+    Index(u8),
+    RaceNumber(u8),
+}
+
+impl FollowCar {
+    /// Index of the followed car among the participants, `None` if no participant matches
+    pub(crate) fn resolve(&self, cars: &[ParticipantData]) -> Option<u8> {
+        match *self {
+            FollowCar::Index(idx) => ((idx as usize) < cars.len()).then_some(idx),
+            FollowCar::RaceNumber(number) => cars.iter().position(|car| car.race_number == number).map(|idx| idx as u8),
+        }
+    }
+}
+
+impl FromStr for FollowCar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = |_| format!("Invalid car '{}', expected a car index like 3 or a race number like #44", s);
+        match s.strip_prefix('#') {
+            Some(number) => number.parse().map(FollowCar::RaceNumber).map_err(invalid),
+            None => s.parse().map(FollowCar::Index).map_err(invalid),
+        }
+    }
+}
+
+/// Drivers to log the events of, given as comma separated race numbers (e.g. "#44" or "44") and names
+/// (e.g. "Hamilton"). Resolved to car indexes whenever the participants change, as drivers may swap cars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DriverSelection {
+    race_numbers: Vec<u8>,
+    names: Vec<String>,
+}
+
+impl DriverSelection {
+    /// Indexes of the selected participants, names compared ignoring case
+    pub(crate) fn resolve(&self, cars: &[ParticipantData]) -> Vec<u8> {
+        (0..cars.len())
+            .filter(|&i| {
+                let car = &cars[i];
+                !car.name.is_empty()
+                    && (self.race_numbers.contains(&car.race_number)
+                        || self.names.iter().any(|name| name.eq_ignore_ascii_case(&car.name)))
+            })
+            .map(|i| i as u8)
+            .collect()
+    }
+}
+
+impl FromStr for DriverSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut selection = Self { race_numbers: Vec::new(), names: Vec::new() };
+        for driver in s.split(',').map(str::trim) {
+            if driver.is_empty() {
+                return Err(format!("Invalid driver list '{}', expected e.g. #44,Leclerc", s));
+            }
+            match driver.strip_prefix('#').unwrap_or(driver).parse() {
+                Ok(number) => selection.race_numbers.push(number),
+                Err(_) => selection.names.push(driver.to_string()),
+            }
+        }
+        Ok(selection)
+    }
+}
+
+/// Resolves the name a team carried in the given game year.
+///
+/// Team ids are reused by the game when a team is renamed, so the telemetry crate only knows the
+/// latest name for each of them. Older captures would otherwise be labeled with the wrong team.
+pub(crate) fn team_name(team: &Team, game_year: u16) -> &str {
+    match (team, game_year) {
+        (Team::AstonMartin, ..=2020) => "Racing Point",
+        (Team::Alpine, ..=2020) => "Renault",
+        (Team::AlphaTauri, ..=2019) => "Toro Rosso",
+        (Team::AlphaTauri, 2024..) => "RB",
+        (Team::AlfaRomeo, 2024..) => "Sauber",
+        _ => team.name(),
+    }
+}
+
+/// Key grouping the cars of a team. MyTeam and custom teams share a generic team id, so their cars are told
+/// apart by the race number as well.
+pub(crate) fn team_key(car: &ParticipantData, game_year: u16) -> String {
+    let name = team_name(&car.team, game_year);
+    match car.team {
+        Team::MyTeam | Team::Unknown => format!("{} #{}", name, car.race_number),
+        _ => name.to_string(),
+    }
+}
+
+/// A car's driver as written to the outputs, displayed straight into a row without allocating the name
+pub(crate) struct DriverLabel<'a> {
+    car: &'a ParticipantData,
+    identify_by: IdentifyBy,
+}
+
+impl fmt::Display for DriverLabel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.identify_by {
+            IdentifyBy::Name | IdentifyBy::Both => f.write_str(&self.car.name),
+            IdentifyBy::Number => write!(f, "#{}", self.car.race_number),
+        }
+    }
+}
+
+/// A car's team as written to the outputs, see [`DriverLabel`]
+pub(crate) struct TeamLabel<'a> {
+    car: &'a ParticipantData,
+    game_year: u16,
+    identify_by: IdentifyBy,
+}
+
+impl fmt::Display for TeamLabel<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = team_name(&self.car.team, self.game_year);
+        match self.identify_by {
+            IdentifyBy::Both => write!(f, "{} ({})", name, self.car.race_number),
+            IdentifyBy::Name | IdentifyBy::Number => f.write_str(name),
+        }
+    }
+}
+
+pub(crate) fn driver_label(car: &ParticipantData, identify_by: IdentifyBy) -> DriverLabel<'_> {
+    DriverLabel { car, identify_by }
+}
+
+pub(crate) fn team_label(car: &ParticipantData, game_year: u16, identify_by: IdentifyBy) -> TeamLabel<'_> {
+    TeamLabel { car, game_year, identify_by }
+}
+
+/// Formats a car's driver, e.g. "Leclerc" or "#16"
+pub(crate) fn format_driver(car: &ParticipantData, identify_by: IdentifyBy) -> String {
+    driver_label(car, identify_by).to_string()
+}
+
+/// Formats a car's team, together with its race number when identifying drivers by both, e.g. "Ferrari (16)"
+pub(crate) fn format_team(car: &ParticipantData, game_year: u16, identify_by: IdentifyBy) -> String {
+    team_label(car, game_year, identify_by).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cars(names: &[&str]) -> Vec<ParticipantData> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| ParticipantData {
+                name: name.to_string(),
+                race_number: 10 + i as u8,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn names(cars: &[ParticipantData]) -> Vec<&str> {
+        cars.iter().map(|car| car.name.as_str()).collect()
+    }
+
+    #[test]
+    fn fills_empty_names_of_active_cars() {
+        let mut by_index = cars(&["Hamilton", "", " ", ""]);
+        assert_eq!(EmptyNames::CarIndex.fill(&mut by_index, 3), 2);
+        assert_eq!(names(&by_index), ["Hamilton", "Car 1", "Car 2", ""]);
+
+        let mut by_number = cars(&["Hamilton", "", " ", ""]);
+        assert_eq!(EmptyNames::RaceNumber.fill(&mut by_number, 3), 2);
+        assert_eq!(names(&by_number), ["Hamilton", "#11", "#12", ""]);
+    }
+
+    #[test]
+    fn keeps_names_blank() {
+        let mut blank = cars(&["Hamilton", ""]);
+        assert_eq!(EmptyNames::Blank.fill(&mut blank, 2), 0);
+        assert_eq!(names(&blank), ["Hamilton", ""]);
+    }
+
+    #[test]
+    fn tells_generic_team_cars_apart_by_number() {
+        let car = |team, race_number| ParticipantData { team, race_number, ..Default::default() };
+
+        assert_eq!(team_key(&car(Team::Ferrari, 16), 2023), team_key(&car(Team::Ferrari, 55), 2023));
+        assert_ne!(team_key(&car(Team::MyTeam, 2), 2023), team_key(&car(Team::MyTeam, 3), 2023));
+        assert_ne!(team_key(&car(Team::Unknown, 2), 2023), team_key(&car(Team::Unknown, 3), 2023));
+    }
+
+    #[test]
+    fn parses_driver_selection() {
+        let selection: DriverSelection = "#44, Leclerc,16".parse().unwrap();
+        assert_eq!(selection.race_numbers, [44, 16]);
+        assert_eq!(selection.names, ["Leclerc"]);
+
+        assert!("".parse::<DriverSelection>().is_err());
+        assert!("#44,,Leclerc".parse::<DriverSelection>().is_err());
+    }
+
+    #[test]
+    fn resolves_driver_selection() {
+        let grid = cars(&["Hamilton", "Russell", "Leclerc", ""]);
+
+        let selection: DriverSelection = "#11,leclerc".parse().unwrap();
+        assert_eq!(selection.resolve(&grid), [1, 2]);
+
+        // Unused slots carry no name and are never selected, even by their race number
+        let selection: DriverSelection = "#13,Verstappen".parse().unwrap();
+        assert!(selection.resolve(&grid).is_empty());
+    }
+}
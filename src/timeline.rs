@@ -0,0 +1,63 @@
+use std::fs;
+
+pub(crate) const TIMELINE_CSV_HEADERS: [&str; 4] = ["Sessiontime [ms]", "Type", "Description", "Details"];
+
+/// A single entry of the combined race timeline, with type specific data as JSON
+#[derive(Debug, Clone)]
+pub(crate) struct TimelineEntry {
+    pub(crate) session_time: u32,
+    pub(crate) kind: &'static str,
+    pub(crate) description: String,
+    pub(crate) details: serde_json::Value,
+}
+
+impl TimelineEntry {
+    /// The entry as JSON for the event streams, e.g. Kafka
+    pub(crate) fn to_json(&self, session_uid: u64) -> serde_json::Value {
+        serde_json::json!({
+            "session_uid": session_uid,
+            "session_time": self.session_time,
+            "type": self.kind,
+            "description": self.description,
+            "details": self.details,
+        })
+    }
+}
+
+/// Collects the output of all event handlers to write them as one chronological file
+#[derive(Debug, Default)]
+pub(crate) struct Timeline {
+    entries: Vec<TimelineEntry>,
+}
+
+impl Timeline {
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, entry: TimelineEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Writes all entries ordered by session time. Packets of different types may arrive slightly out of
+    /// order, so entries are sorted (stably, keeping receipt order for equal times) before writing.
+    pub(crate) fn write(&mut self, writer: &mut csv::Writer<fs::File>) -> csv::Result<()> {
+        self.entries.sort_by_key(|entry| entry.session_time);
+
+        for entry in &self.entries {
+            writer.write_record([
+                entry.session_time.to_string().as_str(),
+                entry.kind,
+                entry.description.as_str(),
+                entry.details.to_string().as_str(),
+            ])?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
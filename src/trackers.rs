@@ -0,0 +1,175 @@
+//! Trackers that follow the session from the packets alone, driven by [`SessionState::observe`] before the
+//! state is updated from the packet. Only trackers whose updates neither write rows nor depend on what the
+//! session state derives from the same packet are observers; the lap history, the runs, the faults and the
+//! other trackers feeding rows are updated by the session state's handlers, which decide when rows are due.
+//!
+//! [`SessionState::observe`]: crate::session::SessionState::observe
+use f1_telemetry::packet::car_status::CarStatusData;
+use f1_telemetry::packet::lap::LapData;
+use f1_telemetry::packet::participants::ParticipantData;
+use f1_telemetry::packet::Packet;
+
+use crate::gaps::GapTracker;
+use crate::laps::PositionHolds;
+use crate::metadata::SessionMetadata;
+use crate::pits::PitStopTracker;
+use crate::style::StyleTracker;
+
+/// State of the session the trackers see a packet in, from before the packet is applied. Rows of the session
+/// take their session columns from it too, see `LapRow`.
+pub(crate) struct SessionContext<'a> {
+    /// Lap data of the previous frame, a lap data packet holds the current one
+    pub(crate) lap_data: &'a [LapData],
+    pub(crate) car_status: &'a [CarStatusData],
+    pub(crate) cars: &'a [ParticipantData],
+    pub(crate) game_year: u16,
+    /// `None` until the session packet arrived
+    pub(crate) metadata: Option<&'a SessionMetadata>,
+}
+
+/// A tracker following the session through the packets it's interested in, independently of the session
+/// state around it, so it can be driven by synthetic packets
+pub(crate) trait PacketObserver {
+    fn observe(&mut self, packet: &Packet, context: &SessionContext);
+
+    /// Forgets the session, e.g. when another one starts
+    fn clear(&mut self);
+}
+
+impl PacketObserver for PositionHolds {
+    fn observe(&mut self, packet: &Packet, context: &SessionContext) {
+        if let Packet::LapData(lap_packet) = packet {
+            self.update(context.lap_data, &lap_packet.lap_data, lap_packet.header.session_time);
+        }
+    }
+
+    fn clear(&mut self) {
+        PositionHolds::clear(self);
+    }
+}
+
+impl PacketObserver for GapTracker {
+    fn observe(&mut self, packet: &Packet, _context: &SessionContext) {
+        if let Packet::LapData(lap_packet) = packet {
+            self.update(&lap_packet.lap_data, lap_packet.header.session_time);
+        }
+    }
+
+    fn clear(&mut self) {
+        GapTracker::clear(self);
+    }
+}
+
+impl PacketObserver for StyleTracker {
+    fn observe(&mut self, packet: &Packet, context: &SessionContext) {
+        if let Packet::CarTelemetry(telemetry_packet) = packet {
+            self.record(&telemetry_packet.car_telemetry_data, context.cars, telemetry_packet.header.session_time);
+        }
+    }
+
+    fn clear(&mut self) {
+        StyleTracker::clear(self);
+    }
+}
+
+impl PacketObserver for PitStopTracker {
+    fn observe(&mut self, packet: &Packet, context: &SessionContext) {
+        if let Packet::LapData(lap_packet) = packet {
+            self.update(context.lap_data, &lap_packet.lap_data, context.car_status, lap_packet.header.session_time);
+        }
+    }
+
+    fn clear(&mut self) {
+        PitStopTracker::clear(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use f1_telemetry::packet::header::PacketHeader;
+    use f1_telemetry::packet::lap::{PacketLapData, PitStatus};
+
+    fn lap_packet(session_time: u32, cars: &[(u8, PitStatus)]) -> Packet {
+        Packet::LapData(PacketLapData {
+            header: PacketHeader { session_time, ..Default::default() },
+            lap_data: cars
+                .iter()
+                .map(|&(car_position, pit_status)| LapData {
+                    car_position,
+                    pit_status,
+                    current_lap_num: 3,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Feeds the packets to the observer with the lap data of the previous packet as context, like the
+    /// session state does
+    fn feed(observer: &mut dyn PacketObserver, packets: &[Packet]) {
+        let mut lap_data: &[LapData] = &[];
+        for packet in packets {
+            let context = SessionContext { lap_data, car_status: &[], cars: &[], game_year: 2023, metadata: None };
+            observer.observe(packet, &context);
+            if let Packet::LapData(lap_packet) = packet {
+                lap_data = &lap_packet.lap_data;
+            }
+        }
+    }
+
+    #[test]
+    fn pit_stop_from_lap_data() {
+        let mut pit_stops = PitStopTracker::default();
+        feed(
+            &mut pit_stops,
+            &[
+                lap_packet(1000, &[(1, PitStatus::None), (2, PitStatus::None)]),
+                lap_packet(2000, &[(1, PitStatus::None), (2, PitStatus::Pitting)]),
+                lap_packet(3000, &[(1, PitStatus::None), (2, PitStatus::InPitArea)]),
+                lap_packet(5500, &[(1, PitStatus::None), (2, PitStatus::Pitting)]),
+                lap_packet(7000, &[(1, PitStatus::None), (2, PitStatus::None)]),
+            ],
+        );
+
+        let [stop] = pit_stops.completed() else {
+            panic!("Expected one pit stop, got {:?}", pit_stops.completed());
+        };
+        assert_eq!((stop.car_idx, stop.lap), (1, 3));
+        assert_eq!((stop.entry_time, stop.exit_time, stop.stationary_ms), (2000, 7000, 2500));
+        assert_eq!(pit_stops.stint(1), 2);
+    }
+
+    #[test]
+    fn position_held_since_the_last_change() {
+        let mut holds = PositionHolds::default();
+        feed(
+            &mut holds,
+            &[
+                lap_packet(1000, &[(1, PitStatus::None), (2, PitStatus::None)]),
+                lap_packet(4000, &[(2, PitStatus::None), (1, PitStatus::None)]),
+                lap_packet(9000, &[(2, PitStatus::None), (1, PitStatus::None)]),
+            ],
+        );
+
+        assert_eq!(holds.held_for_ms(0, 10_000), Some(6000));
+        assert_eq!(holds.held_for_ms(1, 10_000), Some(6000));
+    }
+
+    #[test]
+    fn other_packets_are_ignored_and_clearing_forgets_the_session() {
+        let mut pit_stops = PitStopTracker::default();
+        let pit_lane = [
+            lap_packet(1000, &[(1, PitStatus::None)]),
+            lap_packet(2000, &[(1, PitStatus::Pitting)]),
+            lap_packet(3000, &[(1, PitStatus::None)]),
+        ];
+        feed(&mut pit_stops, &pit_lane);
+        feed(&mut pit_stops, &[Packet::Session(Default::default())]);
+        assert_eq!(pit_stops.completed().len(), 1);
+
+        PacketObserver::clear(&mut pit_stops);
+        assert!(pit_stops.completed().is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use std::path;
+
+use crate::timeline::TimelineEntry;
+
+/// Checks that the platform has Unix domain sockets. Windows would need a named pipe instead, which isn't
+/// implemented, so the option is rejected there.
+pub(crate) fn ensure_supported() -> Result<(), String> {
+    if cfg!(unix) {
+        Ok(())
+    } else {
+        Err("--unix-socket is only available on Unix, use --kafka-brokers on Windows".to_string())
+    }
+}
+
+/// Streams every event as one line of JSON to all processes connected to a Unix domain socket, e.g.
+/// `socat - UNIX-CONNECT:events.sock`. The payload is the one published to Kafka. Connections are accepted
+/// as events arrive and nothing is buffered for them, so the packet handling never waits for a consumer:
+/// a consumer that doesn't keep up is disconnected.
+pub(crate) struct UnixSocketSink {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    path: path::PathBuf,
+    #[cfg(unix)]
+    listener: std::os::unix::net::UnixListener,
+    #[cfg(unix)]
+    clients: Vec<std::os::unix::net::UnixStream>,
+}
+
+impl UnixSocketSink {
+    #[cfg(unix)]
+    pub(crate) fn bind(path: &path::Path) -> Result<Self, String> {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::{UnixListener, UnixStream};
+        use std::{fs, io};
+
+        // A socket file left by a crash refuses connections and is replaced, one still in use is kept
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.file_type().is_socket() => {
+                return Err(format!("{:?} exists and is not a socket", path));
+            },
+            Ok(_) => match UnixStream::connect(path) {
+                Ok(_) => return Err(format!("{:?} is in use by another process", path)),
+                Err(_) => {
+                    fs::remove_file(path)
+                        .map_err(|err| format!("Failed to remove stale socket {:?}: {}", path, err))?;
+                    println!("Removed stale socket {:?}", path);
+                },
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => return Err(format!("Failed to check {:?}: {}", path, err)),
+        }
+
+        let listener = UnixListener::bind(path)
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .map_err(|err| format!("Failed to listen on {:?}: {}", path, err))?;
+        println!("Streaming events to {:?}", path);
+
+        Ok(Self { path: path.to_path_buf(), listener, clients: Vec::new() })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn bind(path: &path::Path) -> Result<Self, String> {
+        ensure_supported().map(|()| Self { path: path.to_path_buf() })
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn publish(&mut self, session_uid: u64, entry: &TimelineEntry) {
+        use std::io::Write;
+
+        self.accept_pending();
+        if self.clients.is_empty() {
+            return;
+        }
+
+        let mut line = entry.to_json(session_uid).to_string();
+        line.push('\n');
+        self.clients.retain_mut(|client| match client.write_all(line.as_bytes()) {
+            Ok(()) => true,
+            Err(err) => {
+                println!("Disconnected an event stream consumer: {}", err);
+                false
+            },
+        });
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn publish(&mut self, _session_uid: u64, _entry: &TimelineEntry) {}
+
+    #[cfg(unix)]
+    fn accept_pending(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((client, _)) => match client.set_nonblocking(true) {
+                    Ok(()) => {
+                        println!("Event stream consumer connected to {:?}", self.path);
+                        self.clients.push(client);
+                    },
+                    Err(err) => println!("Failed to set up an event stream consumer: {}", err),
+                },
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    println!("Failed to accept an event stream consumer: {}", err);
+                    break;
+                },
+            }
+        }
+    }
+}
+
+impl Drop for UnixSocketSink {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,223 @@
+use std::io::Write;
+use std::str::FromStr;
+use std::{fs, io, path};
+
+use chrono::{DateTime, Local, NaiveTime};
+
+/// Timeline entries marked in the recording, besides the overtakes passing the significance filters
+pub(crate) const MARKED_KINDS: [&str; 3] = ["Collision", "Penalty", "Lead Change"];
+
+/// Shortest chapter YouTube accepts, markers closer together share a chapter
+const MIN_CHAPTER_MS: u32 = 10_000;
+
+/// Where the race recording starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum VodStart {
+    /// Wall-clock time the recording started, e.g. "14:03:27" for today or an RFC 3339 timestamp
+    WallClock(DateTime<Local>),
+    /// Position of the lights out in the recording, e.g. "+4:12"
+    LightsOutAt(u32),
+}
+
+impl FromStr for VodStart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(offset) = s.strip_prefix('+') {
+            return parse_duration(offset).map(VodStart::LightsOutAt);
+        }
+        if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+            return Ok(VodStart::WallClock(time.with_timezone(&Local)));
+        }
+
+        NaiveTime::parse_from_str(s, "%H:%M:%S")
+            .ok()
+            .and_then(|time| Local::now().date_naive().and_time(time).and_local_timezone(Local).single())
+            .map(VodStart::WallClock)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid VOD start '{}', expected a wall-clock time like 14:03:27 or the lights out in the \
+                     recording like +4:12",
+                    s
+                )
+            })
+    }
+}
+
+/// Parses a position in or length of a recording in milliseconds, given as "[[h:]m:]s", e.g. "1:02:03"
+pub(crate) fn parse_duration(s: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid video time '{}', expected e.g. 1:02:03", s);
+    if s.split(':').count() > 3 {
+        return Err(invalid());
+    }
+
+    s.split(':')
+        .try_fold(0u32, |secs, part| {
+            let value: u32 = part.trim().parse().ok()?;
+            secs.checked_mul(60)?.checked_add(value)
+        })
+        .and_then(|secs| secs.checked_mul(1000))
+        .ok_or_else(invalid)
+}
+
+/// File format of the recording markers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VodFormat {
+    Csv,
+    /// Chapter lines to paste into a YouTube video description
+    YouTube,
+}
+
+impl FromStr for VodFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(VodFormat::Csv),
+            "youtube" => Ok(VodFormat::YouTube),
+            other => Err(format!("Invalid VOD format '{}', expected csv or youtube", other)),
+        }
+    }
+}
+
+/// The race recording the significant events are mapped onto
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VodConfig {
+    pub(crate) start: VodStart,
+    /// Length of the recording, events after its end are dropped
+    pub(crate) duration_ms: Option<u32>,
+    pub(crate) format: VodFormat,
+}
+
+impl VodConfig {
+    /// Position of an event in the recording, from its wall-clock time or its time since the lights out
+    /// depending on how the start was given. `None` when the event falls outside of the recording, or
+    /// without a lights out to count from.
+    pub(crate) fn video_time_ms(&self, wall_clock: DateTime<Local>, since_lights_out_ms: Option<i64>) -> Option<u32> {
+        let video_ms = match self.start {
+            VodStart::WallClock(start) => (wall_clock - start).num_milliseconds(),
+            VodStart::LightsOutAt(lights_out_ms) => lights_out_ms as i64 + since_lights_out_ms?,
+        };
+
+        u32::try_from(video_ms).ok().filter(|&ms| self.duration_ms.is_none_or(|duration| ms <= duration))
+    }
+}
+
+/// An event at its position in the recording
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VodMarker {
+    pub(crate) video_ms: u32,
+    pub(crate) kind: &'static str,
+    pub(crate) description: String,
+}
+
+/// Start and title of the chapters of the markers, ordered by their position. YouTube requires the first
+/// chapter at 0:00 and chapters of at least [`MIN_CHAPTER_MS`], so markers following closer than that join
+/// the previous chapter.
+fn chapters(markers: &[VodMarker]) -> Vec<(u32, String)> {
+    let mut chapters: Vec<(u32, String)> = vec![(0, String::from("Start"))];
+    for marker in markers {
+        match chapters.last_mut() {
+            Some((start_ms, title)) if marker.video_ms < *start_ms + MIN_CHAPTER_MS => {
+                title.push_str("; ");
+                title.push_str(&marker.description);
+            },
+            _ => chapters.push((marker.video_ms, marker.description.clone())),
+        }
+    }
+    chapters
+}
+
+/// Formats a position in the recording the way YouTube links chapters, e.g. "4:05" or "1:04:05"
+fn format_chapter_time(ms: u32) -> String {
+    let secs = ms / 1000;
+    match secs / 3600 {
+        0 => format!("{}:{:02}", secs / 60, secs % 60),
+        hours => format!("{}:{:02}:{:02}", hours, secs / 60 % 60, secs % 60),
+    }
+}
+
+/// Writes the markers as YouTube chapters, ordered by their position, see [`chapters`]
+pub(crate) fn write_chapters(filename: &path::Path, markers: &[VodMarker]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(filename)?);
+    for (start_ms, title) in chapters(markers) {
+        writeln!(file, "{} {}", format_chapter_time(start_ms), title)?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(video_ms: u32, description: &str) -> VodMarker {
+        VodMarker { video_ms, kind: "Overtake", description: description.to_string() }
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("42"), Ok(42_000));
+        assert_eq!(parse_duration("4:12"), Ok(252_000));
+        assert_eq!(parse_duration("1:02:03"), Ok(3_723_000));
+
+        for invalid in ["", "1:2:3:4", "4:x", "-3", "99999999"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn parses_vod_start() {
+        assert_eq!("+4:12".parse(), Ok(VodStart::LightsOutAt(252_000)));
+
+        let rfc3339 = DateTime::parse_from_rfc3339("2024-05-01T14:03:27+02:00").unwrap();
+        assert_eq!("2024-05-01T14:03:27+02:00".parse(), Ok(VodStart::WallClock(rfc3339.with_timezone(&Local))));
+
+        let Ok(VodStart::WallClock(today)) = "14:03:27".parse() else {
+            panic!("Expected a wall-clock time");
+        };
+        assert_eq!(today.time(), NaiveTime::from_hms_opt(14, 3, 27).unwrap());
+        assert_eq!(today.date_naive(), Local::now().date_naive());
+
+        assert!("+x".parse::<VodStart>().is_err());
+        assert!("14:03".parse::<VodStart>().is_err());
+    }
+
+    #[test]
+    fn maps_events_onto_the_recording() {
+        let start = DateTime::parse_from_rfc3339("2024-05-01T14:00:00+00:00").unwrap().with_timezone(&Local);
+        let at = |secs| start + chrono::Duration::seconds(secs);
+
+        let wall_clock =
+            VodConfig { start: VodStart::WallClock(start), duration_ms: Some(600_000), format: VodFormat::Csv };
+        assert_eq!(wall_clock.video_time_ms(at(90), None), Some(90_000));
+        assert_eq!(wall_clock.video_time_ms(at(-5), Some(0)), None);
+        assert_eq!(wall_clock.video_time_ms(at(601), Some(0)), None);
+
+        let lights_out =
+            VodConfig { start: VodStart::LightsOutAt(252_000), duration_ms: None, format: VodFormat::YouTube };
+        assert_eq!(lights_out.video_time_ms(at(0), Some(10_000)), Some(262_000));
+        assert_eq!(lights_out.video_time_ms(at(0), Some(-300_000)), None);
+        assert_eq!(lights_out.video_time_ms(at(0), None), None);
+    }
+
+    #[test]
+    fn merges_close_markers_into_one_chapter() {
+        let markers = [
+            marker(5_000, "Start collision"),
+            marker(30_000, "Leclerc overtakes Sainz"),
+            marker(35_000, "Sainz: DriveThrough"),
+            marker(3_700_000, "Lead change"),
+        ];
+
+        assert_eq!(
+            chapters(&markers),
+            [
+                (0, "Start; Start collision".to_string()),
+                (30_000, "Leclerc overtakes Sainz; Sainz: DriveThrough".to_string()),
+                (3_700_000, "Lead change".to_string()),
+            ]
+        );
+        assert_eq!(format_chapter_time(30_000), "0:30");
+        assert_eq!(format_chapter_time(3_700_000), "1:01:40");
+    }
+}
@@ -0,0 +1,29 @@
+use chrono::{DateTime, Duration, Local};
+
+/// Longest break between two sessions of the same track for them to belong to one weekend, e.g. a career
+/// weekend played practice, qualifying and race in one evening
+const WEEKEND_WINDOW_HOURS: i64 = 6;
+
+/// Context of a session carried to the next session of a race weekend with `--link-weekend`. Career and My
+/// Team weekends give every session its own UID, so the link is made by the track and the time between them.
+#[derive(Debug, Clone)]
+pub(crate) struct WeekendLink {
+    pub(crate) session_uid: u64,
+    pub(crate) track: String,
+    pub(crate) ended_at: DateTime<Local>,
+    /// Qualifying position by race number of the weekend's latest qualifying, for spotting grid penalties
+    pub(crate) qualifying: Vec<(u8, u8)>,
+    /// Real names in the order of their pseudonyms with `--anonymize`
+    pub(crate) pseudonyms: Vec<String>,
+}
+
+impl WeekendLink {
+    /// Whether a session on the track starting at the given time continues this weekend
+    pub(crate) fn continues_with(&self, track: &str, started_at: DateTime<Local>) -> bool {
+        self.track == track && started_at - self.ended_at <= Duration::hours(WEEKEND_WINDOW_HOURS)
+    }
+
+    pub(crate) fn qualifying_position(&self, race_number: u8) -> Option<u8> {
+        self.qualifying.iter().find(|&&(number, _)| number == race_number).map(|&(_, position)| position)
+    }
+}
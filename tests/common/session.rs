@@ -0,0 +1,165 @@
+// Modules of the logger and the session options shared by the replay tests and the session benchmark, which
+// include this file at their crate root. Paths are relative to this file.
+
+#[path = "../../src/amendments.rs"]
+mod amendments;
+#[path = "../../src/anonymize.rs"]
+mod anonymize;
+#[path = "../../src/columns.rs"]
+mod columns;
+#[path = "../../src/controls.rs"]
+mod controls;
+#[path = "../../src/corners.rs"]
+mod corners;
+#[path = "../../src/dedup.rs"]
+mod dedup;
+#[path = "../../src/display.rs"]
+mod display;
+#[path = "../../src/drops.rs"]
+mod drops;
+#[path = "../../src/drs.rs"]
+mod drs;
+#[path = "../../src/faults.rs"]
+mod faults;
+#[path = "../../src/filenames.rs"]
+mod filenames;
+#[path = "../../src/forecast.rs"]
+mod forecast;
+#[path = "../../src/gaps.rs"]
+mod gaps;
+#[path = "../../src/grip.rs"]
+mod grip;
+#[path = "../../src/kafka.rs"]
+mod kafka;
+#[path = "../../src/laps.rs"]
+mod laps;
+#[path = "../../src/locations.rs"]
+mod locations;
+#[path = "../../src/metadata.rs"]
+mod metadata;
+#[path = "../../src/metadatahttp.rs"]
+mod metadatahttp;
+#[path = "../../src/notify.rs"]
+mod notify;
+#[path = "../../src/ordering.rs"]
+mod ordering;
+#[path = "../../src/pairs.rs"]
+mod pairs;
+#[path = "../../src/penalties.rs"]
+mod penalties;
+#[path = "../../src/performance.rs"]
+mod performance;
+#[path = "../../src/phases.rs"]
+mod phases;
+#[path = "../../src/pipeline.rs"]
+mod pipeline;
+#[path = "../../src/pits.rs"]
+mod pits;
+#[path = "../../src/points.rs"]
+mod points;
+#[path = "../../src/positions.rs"]
+mod positions;
+#[path = "../../src/rates.rs"]
+mod rates;
+#[path = "../../src/rawevents.rs"]
+mod rawevents;
+#[path = "../../src/replaydb.rs"]
+mod replaydb;
+#[path = "../../src/report.rs"]
+mod report;
+#[path = "../../src/retention.rs"]
+mod retention;
+#[path = "../../src/rolling.rs"]
+mod rolling;
+#[path = "../../src/runs.rs"]
+mod runs;
+#[path = "../../src/schema.rs"]
+mod schema;
+#[path = "../../src/selftest.rs"]
+mod selftest;
+#[path = "../../src/session.rs"]
+mod session;
+#[path = "../../src/sessions.rs"]
+mod sessions;
+#[path = "../../src/sinks.rs"]
+mod sinks;
+#[path = "../../src/statsd.rs"]
+mod statsd;
+#[path = "../../src/status.rs"]
+mod status;
+#[path = "../../src/style.rs"]
+mod style;
+#[path = "../../src/summary.rs"]
+mod summary;
+#[path = "../../src/teams.rs"]
+mod teams;
+#[path = "../../src/timeline.rs"]
+mod timeline;
+#[path = "../../src/trackers.rs"]
+mod trackers;
+#[path = "../../src/unixsocket.rs"]
+mod unixsocket;
+#[path = "../../src/vod.rs"]
+mod vod;
+#[path = "../../src/weekend.rs"]
+mod weekend;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Options writing every output named by its type into the directory, with the side effects and the filters
+/// of the optional features disabled
+fn session_options(output_dir: &std::path::Path) -> crate::session::SessionOptions {
+    crate::session::SessionOptions {
+        player_trace_interval_ms: None,
+        positions_format: positions::PositionsFormat::Csv,
+        cross_reference_window_ms: 5000,
+        points_scheme: None,
+        filename_template: output_dir.join("{type}").to_string_lossy().into_owned(),
+        log_unknown_events: false,
+        log_raw_events: false,
+        lap_columns: None,
+        rolling_laps: None,
+        classification_columns: None,
+        event_order: ordering::EventOrder::default(),
+        schema_check: false,
+        timeline: false,
+        kafka: None,
+        replay_db: None,
+        unix_socket: None,
+        statsd: None,
+        metadata_http: None,
+        controls_all_cars: false,
+        min_session_laps: 0,
+        min_session_secs: 0,
+        penalty_time_ms: false,
+        fsync: false,
+        verify_after_write: false,
+        link_weekend: false,
+        overtake_min_position: None,
+        overtake_min_speed_delta: None,
+        exclude_pit_overtakes: false,
+        markers: false,
+        notify: false,
+        identify_by: teams::IdentifyBy::Both,
+        empty_names: teams::EmptyNames::CarIndex,
+        reversal_window_ms: 20_000,
+        conditions_interval_ms: None,
+        live_display: false,
+        progress: false,
+        report_md: false,
+        follow_car: None,
+        follow_spectated: false,
+        drivers: None,
+        retention: retention::RetentionPolicy::default(),
+        drs_zones: drs::DrsZones::default(),
+        corners: None,
+        driving_style: false,
+        location_bin_m: locations::DEFAULT_BIN_SIZE_M,
+        heatmap_bin_m: None,
+        vod: None,
+        grip_advantage: false,
+        final_tyres: false,
+        driver_ids: None,
+        anonymize: false,
+    }
+}
@@ -0,0 +1,304 @@
+//! Golden-file tests of the outputs. Synthetic sessions are run through the packet pipeline into a temporary
+//! directory, and every file written is compared with its copy checked in under `tests/golden`, with the
+//! wall-clock values masked. Run with F1_EVENTLOGGER_BLESS=1 to rewrite the golden files after an intended
+//! change of the outputs, the diff of the golden files then shows the change for review.
+#![allow(dead_code)]
+
+include!("common/session.rs");
+
+use std::collections::BTreeMap;
+use std::{env, fs, path, process};
+
+use f1_telemetry::packet::header::PacketHeader;
+use f1_telemetry::packet::session::SessionType;
+use f1_telemetry::packet::Packet;
+
+use crate::pipeline::PacketPipeline;
+
+/// CSV columns and metadata fields taken from the wall clock, which differ on every run
+const WALL_CLOCK_FIELDS: [&str; 1] = ["started_at"];
+const MASK: &str = "<masked>";
+
+/// Frames of the synthetic race a flashback rewinds from and to, between both overtakes
+const FLASHBACK_FRAMES: (u32, u32) = (260, 200);
+
+fn golden_dir(name: &str) -> path::PathBuf {
+    path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name)
+}
+
+/// Runs the packets through the pipeline and returns the files written by name, with the wall-clock values
+/// masked
+fn replay(name: &str, packets: Vec<Packet>) -> Result<BTreeMap<String, Vec<u8>>> {
+    let output_dir = env::temp_dir().join(format!("f1-eventlogger-replay-{}-{}", name, process::id()));
+    fs::create_dir_all(&output_dir)?;
+
+    let mut pipeline = PacketPipeline::new(session_options(&output_dir));
+    for packet in packets {
+        pipeline.handle_packet(packet)?;
+    }
+    pipeline.close()?;
+
+    let outputs = read_outputs(&output_dir);
+    fs::remove_dir_all(&output_dir)?;
+    outputs
+}
+
+fn read_outputs(directory: &path::Path) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut outputs = BTreeMap::new();
+    for entry in fs::read_dir(directory)? {
+        let file = entry?.path();
+        let name = file.file_name().and_then(|name| name.to_str()).ok_or("Invalid output filename")?.to_string();
+        outputs.insert(name, masked_contents(&file)?);
+    }
+    Ok(outputs)
+}
+
+fn masked_contents(file: &path::Path) -> Result<Vec<u8>> {
+    let contents = fs::read(file)?;
+    match file.extension().and_then(|extension| extension.to_str()) {
+        Some("csv") => mask_csv(&contents),
+        Some("json") => mask_json(&contents),
+        _ => Ok(contents),
+    }
+}
+
+fn mask_csv(contents: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(contents);
+    let headers = reader.headers()?.clone();
+    let masked: Vec<bool> = headers.iter().map(|header| WALL_CLOCK_FIELDS.contains(&header)).collect();
+
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(Vec::new());
+    writer.write_record(&headers)?;
+    for record in reader.records() {
+        let record = record?;
+        writer.write_record(record.iter().enumerate().map(|(i, field)| {
+            if masked.get(i).copied().unwrap_or(false) {
+                MASK
+            } else {
+                field
+            }
+        }))?;
+    }
+    Ok(writer.into_inner().map_err(|err| err.into_error())?)
+}
+
+fn mask_json(contents: &[u8]) -> Result<Vec<u8>> {
+    let mut value: serde_json::Value = serde_json::from_slice(contents)?;
+    if let Some(object) = value.as_object_mut() {
+        for field in WALL_CLOCK_FIELDS {
+            if let Some(value) = object.get_mut(field) {
+                *value = MASK.into();
+            }
+        }
+    }
+
+    let mut masked = serde_json::to_vec_pretty(&value)?;
+    masked.push(b'\n');
+    Ok(masked)
+}
+
+/// Compares the outputs of the session with its golden files, or rewrites the golden files when blessing
+fn check_golden(name: &str, outputs: BTreeMap<String, Vec<u8>>) {
+    let golden_dir = golden_dir(name);
+    if env::var_os("F1_EVENTLOGGER_BLESS").is_some() {
+        if golden_dir.exists() {
+            fs::remove_dir_all(&golden_dir).unwrap();
+        }
+        fs::create_dir_all(&golden_dir).unwrap();
+        for (file, contents) in &outputs {
+            fs::write(golden_dir.join(file), contents).unwrap();
+        }
+        return;
+    }
+
+    let golden = read_golden(&golden_dir).unwrap_or_else(|err| {
+        panic!("No golden files in {} ({}), run with F1_EVENTLOGGER_BLESS=1", golden_dir.display(), err)
+    });
+    assert_eq!(
+        golden.keys().collect::<Vec<_>>(),
+        outputs.keys().collect::<Vec<_>>(),
+        "Files written for {}, run with F1_EVENTLOGGER_BLESS=1 if the change is intended",
+        name
+    );
+    for (file, contents) in &outputs {
+        assert_eq!(
+            String::from_utf8_lossy(&golden[file]),
+            String::from_utf8_lossy(contents),
+            "Contents of {} for {}, run with F1_EVENTLOGGER_BLESS=1 if the change is intended",
+            file,
+            name
+        );
+    }
+}
+
+fn header_mut(packet: &mut Packet) -> Option<&mut PacketHeader> {
+    match packet {
+        Packet::Motion(p) => Some(&mut p.header),
+        Packet::Session(p) => Some(&mut p.header),
+        Packet::LapData(p) => Some(&mut p.header),
+        Packet::Event(p) => Some(&mut p.header),
+        Packet::Participants(p) => Some(&mut p.header),
+        Packet::CarTelemetry(p) => Some(&mut p.header),
+        Packet::CarStatus(p) => Some(&mut p.header),
+        Packet::FinalClassification(p) => Some(&mut p.header),
+        _ => None,
+    }
+}
+
+fn read_golden(directory: &path::Path) -> Result<BTreeMap<String, Vec<u8>>> {
+    let mut golden = BTreeMap::new();
+    for entry in fs::read_dir(directory)? {
+        let file = entry?.path();
+        let name = file.file_name().and_then(|name| name.to_str()).ok_or("Invalid golden filename")?.to_string();
+        golden.insert(name, fs::read(&file)?);
+    }
+    Ok(golden)
+}
+
+#[test]
+fn race() {
+    let outputs = replay("race", selftest::race_packets()).unwrap();
+    check_golden("race", outputs);
+}
+
+/// The synthetic race run as a qualifying session, where no race events are logged
+#[test]
+fn qualifying() {
+    let mut packets = selftest::race_packets();
+    let Packet::Session(session) = &mut packets[0] else {
+        panic!("The race starts with a session packet");
+    };
+    session.rule_set = None;
+    session.session_type = SessionType::Qualifying1;
+
+    let outputs = replay("qualifying", packets).unwrap();
+    check_golden("qualifying", outputs);
+}
+
+/// The synthetic race rewound by a flashback: the session time and the frames jump back, and the first
+/// overtake happens again
+#[test]
+fn flashback() {
+    let (rewind_from, rewind_to) = FLASHBACK_FRAMES;
+    let frame = |packet: &Packet| pipeline::packet_header(packet).map_or(0, |header| header.frame_identifier);
+    let mut packets: Vec<Packet> =
+        selftest::race_packets().into_iter().filter(|packet| frame(packet) <= rewind_from).collect();
+    packets.extend(selftest::race_packets().into_iter().filter(|packet| frame(packet) >= rewind_to));
+
+    let outputs = replay("flashback", packets).unwrap();
+    check_golden("flashback", outputs);
+}
+
+/// The synthetic race watched as a spectator: no player car, the camera on the second car
+#[test]
+fn spectator() {
+    let mut packets = selftest::race_packets();
+    for packet in &mut packets {
+        if let Some(header) = header_mut(packet) {
+            header.player_car_index = 255;
+        }
+        if let Packet::Session(session) = packet {
+            session.is_spectating = true;
+            session.spectator_car_index = 1;
+        }
+    }
+
+    let outputs = replay("spectator", packets).unwrap();
+    check_golden("spectator", outputs);
+}
+
+/// Every event packet arriving twice, as on network paths duplicating datagrams, writes the same files
+#[test]
+fn duplicated_events() {
+    let mut packets = Vec::new();
+    for (packet, copy) in selftest::race_packets().into_iter().zip(selftest::race_packets()) {
+        packets.push(packet);
+        if let Packet::Event(_) = copy {
+            packets.push(copy);
+        }
+    }
+
+    let clean = replay("clean", selftest::race_packets()).unwrap();
+    let duplicated = replay("duplicated", packets).unwrap();
+    assert_eq!(clean.keys().collect::<Vec<_>>(), duplicated.keys().collect::<Vec<_>>());
+    for (file, contents) in &clean {
+        assert_eq!(String::from_utf8_lossy(contents), String::from_utf8_lossy(&duplicated[file]), "{}", file);
+    }
+}
+
+/// The overtakes of the synthetic race happen at 10 s and 20 s of session time, the second 5 s after a
+/// collision between the same cars. The header's session time is milliseconds, written as seconds.
+#[test]
+fn overtake_session_time() {
+    let outputs = replay("session_time", selftest::race_packets()).unwrap();
+    let mut reader = csv::Reader::from_reader(outputs["Events.csv"].as_slice());
+    let headers = reader.headers().unwrap().clone();
+    let column = |name: &str| headers.iter().position(|header| header == name).unwrap();
+    let (secs, clock, collision) = (column("Session Time [s]"), column("Session Time"), column("Collision Time [s]"));
+
+    let rows: Vec<(String, String, String)> = reader
+        .records()
+        .map(|record| {
+            let record = record.unwrap();
+            (record[secs].to_string(), record[clock].to_string(), record[collision].to_string())
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            ("10.000".to_string(), "00:10.000".to_string(), String::new()),
+            ("20.000".to_string(), "00:20.000".to_string(), "15.000".to_string()),
+        ]
+    );
+}
+
+/// The first session packet lacking the rule set, as sent before the game fills it in, only delays the
+/// decision to log the race events until the rule set arrives on the same session uid
+#[test]
+fn rule_set_arriving_late() {
+    let mut packets = selftest::race_packets();
+    let Packet::Session(first) = &mut packets[0] else {
+        panic!("The race starts with a session packet");
+    };
+    first.rule_set = None;
+    let race_session = selftest::race_packets().swap_remove(0);
+    packets.insert(2, race_session);
+
+    let clean = replay("rule_set_clean", selftest::race_packets()).unwrap();
+    let late = replay("rule_set_late", packets).unwrap();
+    for file in ["Events.csv", "Collisions.csv", "Penalties.csv"] {
+        assert_eq!(String::from_utf8_lossy(&clean[file]), String::from_utf8_lossy(&late[file]), "{}", file);
+    }
+}
+
+/// The final classification sent twice writes the results once, as if it was sent once
+#[test]
+fn repeated_classification() {
+    let mut packets = selftest::race_packets();
+    let classification = selftest::race_packets().pop().unwrap();
+    assert!(matches!(classification, Packet::FinalClassification(_)));
+    packets.push(classification);
+
+    let clean = replay("classification_once", selftest::race_packets()).unwrap();
+    let repeated = replay("classification_twice", packets).unwrap();
+    assert_eq!(clean.keys().collect::<Vec<_>>(), repeated.keys().collect::<Vec<_>>());
+    for (file, contents) in &clean {
+        assert_eq!(String::from_utf8_lossy(contents), String::from_utf8_lossy(&repeated[file]), "{}", file);
+    }
+}
+
+/// A classification sent again with changed positions, e.g. after post-race penalties, is written as amended
+#[test]
+fn amended_classification() {
+    let mut packets = selftest::race_packets();
+    let Some(Packet::FinalClassification(mut amended)) = selftest::race_packets().pop() else {
+        panic!("The race ends with the final classification");
+    };
+    amended.final_classifications[0].position = 2;
+    amended.final_classifications[1].position = 1;
+    packets.push(Packet::FinalClassification(amended));
+
+    let outputs = replay("classification_amended", packets).unwrap();
+    let amendments = String::from_utf8_lossy(&outputs["Amendments.csv"]).into_owned();
+    assert_eq!(amendments.lines().count(), 3, "{}", amendments);
+}